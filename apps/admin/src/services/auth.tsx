@@ -1,8 +1,27 @@
 import { useState, useEffect } from "react";
+import axios, { AxiosError, AxiosResponse, InternalAxiosRequestConfig } from "axios";
 import {getBlog} from "@/api/blog";
 
 const { me } = getBlog();
 
+const retried = new WeakSet<InternalAxiosRequestConfig>();
+
+// signed session tokens are rejected once expired, /me replaces them while the session is valid
+axios.interceptors.response.use(undefined, async (error: AxiosError) => {
+  const request = error.config;
+  const expired = error.response?.status === 403 && error.response.data === "session expired";
+  if (!request || !expired || request.url === "/api/auth/me" || retried.has(request)) {
+    throw error;
+  }
+
+  retried.add(request);
+  const { data } = await me<AxiosResponse<{ csrf_token: string }>>();
+  axios.defaults.headers.common["X-CSRF-Token"] = data.csrf_token;
+  request.headers["X-CSRF-Token"] = data.csrf_token;
+
+  return axios(request);
+});
+
 export type User = {
   name: string;
   avatar_url: string;
@@ -20,8 +39,9 @@ export const useAuth = (): AuthContextType => {
 
   const login = () => {
     if (isLoggedIn) return;
-    me().then(({data}) => {
-      const { name, avatar_url, login } = data;
+    me<AxiosResponse<User & { csrf_token: string }>>().then(({data}) => {
+      const { name, avatar_url, login, csrf_token } = data;
+      axios.defaults.headers.common["X-CSRF-Token"] = csrf_token;
       setUser({name, avatar_url, login});
       setIsLoggedIn(true);
     }).catch(() => {
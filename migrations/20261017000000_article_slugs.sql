@@ -0,0 +1,16 @@
+ALTER TABLE blog.articles ADD COLUMN IF NOT EXISTS slug TEXT;
+
+UPDATE blog.articles
+SET slug = trim(both '-' from lower(regexp_replace(title, '[^a-zA-Z0-9]+', '-', 'g'))) || '-' || left(id::text, 8)
+WHERE slug IS NULL;
+
+ALTER TABLE blog.articles ALTER COLUMN slug SET NOT NULL;
+
+CREATE UNIQUE INDEX IF NOT EXISTS articles_slug_idx ON blog.articles (slug);
+
+-- previous slugs of renamed articles, kept so old links can be redirected
+CREATE TABLE IF NOT EXISTS blog.article_slugs (
+    slug TEXT PRIMARY KEY,
+    article_id UUID NOT NULL REFERENCES blog.articles (id) ON DELETE CASCADE,
+    created_at TIMESTAMPTZ NOT NULL
+);
@@ -0,0 +1,16 @@
+CREATE TABLE IF NOT EXISTS blog.tags (
+    name TEXT PRIMARY KEY,
+    created_at TIMESTAMPTZ NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS blog.article_tags (
+    article_id UUID NOT NULL REFERENCES blog.articles (id) ON DELETE CASCADE,
+    tag TEXT NOT NULL REFERENCES blog.tags (name) ON DELETE CASCADE,
+    PRIMARY KEY (article_id, tag)
+);
+
+CREATE INDEX IF NOT EXISTS article_tags_tag_idx ON blog.article_tags (tag);
+
+ALTER TABLE blog.articles ADD COLUMN IF NOT EXISTS category TEXT;
+
+CREATE INDEX IF NOT EXISTS articles_category_idx ON blog.articles (category);
@@ -0,0 +1,7 @@
+ALTER TABLE blog.articles ADD COLUMN IF NOT EXISTS search TSVECTOR GENERATED ALWAYS AS (
+    setweight(to_tsvector('english', title), 'A') ||
+    setweight(to_tsvector('english', description), 'B') ||
+    setweight(to_tsvector('english', content), 'C')
+) STORED;
+
+CREATE INDEX IF NOT EXISTS articles_search_idx ON blog.articles USING GIN (search);
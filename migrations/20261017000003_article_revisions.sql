@@ -0,0 +1,21 @@
+CREATE TABLE IF NOT EXISTS blog.article_revisions (
+    id UUID PRIMARY KEY,
+    article_id UUID NOT NULL REFERENCES blog.articles (id) ON DELETE CASCADE,
+    title TEXT NOT NULL,
+    description TEXT NOT NULL,
+    content TEXT NOT NULL,
+    slug TEXT NOT NULL,
+    tags TEXT[] NOT NULL,
+    category TEXT,
+    author TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS article_revisions_article_idx ON blog.article_revisions (article_id, created_at DESC);
+
+-- articles created before revisions existed start with their current state
+INSERT INTO blog.article_revisions (id, article_id, title, description, content, slug, tags, category, author, created_at)
+SELECT gen_random_uuid(), a.id, a.title, a.description, a.content, a.slug,
+    ARRAY(SELECT tag FROM blog.article_tags t WHERE t.article_id = a.id ORDER BY tag), a.category, a.author, a.updated_at
+FROM blog.articles a
+WHERE NOT EXISTS (SELECT 1 FROM blog.article_revisions r WHERE r.article_id = a.id);
@@ -0,0 +1,6 @@
+ALTER TABLE blog.articles DROP CONSTRAINT IF EXISTS articles_status_check;
+ALTER TABLE blog.articles ADD CONSTRAINT articles_status_check CHECK (status IN ('published', 'draft', 'trash', 'scheduled'));
+
+ALTER TABLE blog.articles ADD COLUMN IF NOT EXISTS publish_at TIMESTAMPTZ;
+
+CREATE INDEX IF NOT EXISTS articles_publish_at_idx ON blog.articles (publish_at) WHERE status = 'scheduled';
@@ -0,0 +1 @@
+ALTER TABLE blog.articles ADD COLUMN IF NOT EXISTS version INTEGER NOT NULL DEFAULT 1;
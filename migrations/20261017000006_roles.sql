@@ -0,0 +1,5 @@
+CREATE TABLE IF NOT EXISTS blog.roles (
+    login TEXT PRIMARY KEY,
+    role TEXT NOT NULL CHECK (role IN ('admin', 'editor', 'author', 'viewer')),
+    updated_at TIMESTAMPTZ NOT NULL
+);
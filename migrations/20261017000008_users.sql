@@ -0,0 +1,12 @@
+CREATE SCHEMA IF NOT EXISTS auth;
+
+-- everyone who ever logged in, whatever the provider
+CREATE TABLE IF NOT EXISTS auth.users (
+    login TEXT PRIMARY KEY,
+    id BIGINT NOT NULL,
+    name TEXT NOT NULL,
+    avatar_url TEXT NOT NULL,
+    first_login_at TIMESTAMPTZ NOT NULL,
+    last_login_at TIMESTAMPTZ NOT NULL,
+    disabled BOOLEAN NOT NULL DEFAULT FALSE
+);
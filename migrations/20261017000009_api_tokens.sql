@@ -0,0 +1,15 @@
+-- personal API tokens, only the sha256 of a token is stored
+CREATE TABLE IF NOT EXISTS auth.api_tokens (
+    id UUID PRIMARY KEY,
+    token_hash TEXT NOT NULL UNIQUE,
+    login TEXT NOT NULL,
+    user_id BIGINT NOT NULL,
+    user_name TEXT NOT NULL,
+    user_avatar_url TEXT NOT NULL,
+    name TEXT NOT NULL,
+    scopes TEXT[] NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL,
+    expires_at TIMESTAMPTZ NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS api_tokens_login_idx ON auth.api_tokens (login);
@@ -0,0 +1,30 @@
+CREATE SCHEMA IF NOT EXISTS auth;
+
+-- sessions, when kept in postgres instead of keydb, by session id, the token itself is not stored
+CREATE TABLE IF NOT EXISTS auth.sessions (
+    id TEXT PRIMARY KEY,
+    login TEXT NOT NULL,
+    user_id BIGINT NOT NULL,
+    user_name TEXT NOT NULL,
+    user_avatar_url TEXT NOT NULL,
+    ip TEXT,
+    user_agent TEXT,
+    created_at TIMESTAMPTZ NOT NULL,
+    last_seen_at TIMESTAMPTZ NOT NULL,
+    expires_at TIMESTAMPTZ NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS sessions_login_idx ON auth.sessions (login);
+CREATE INDEX IF NOT EXISTS sessions_expires_at_idx ON auth.sessions (expires_at);
+
+CREATE TABLE IF NOT EXISTS auth.login_states (
+    state TEXT PRIMARY KEY,
+    verifier TEXT NOT NULL,
+    expires_at TIMESTAMPTZ NOT NULL
+);
+
+-- revoked sessions whose signed tokens may still be valid
+CREATE TABLE IF NOT EXISTS auth.denied_sessions (
+    id TEXT PRIMARY KEY,
+    expires_at TIMESTAMPTZ NOT NULL
+);
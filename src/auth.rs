@@ -1,16 +1,34 @@
 use crate::errors::Error;
+use crate::web::csrf;
 use async_trait::async_trait;
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use chrono::{DateTime, Duration, Utc};
 use mockall::predicate::*;
 use mockall::*;
+use rand::RngCore;
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use std::sync::Arc;
+use tokens::{ApiToken, ApiTokens};
 use utoipa::ToSchema;
 
+pub mod cache;
 pub mod github;
+pub mod gitlab;
 pub mod http;
+pub mod local;
+pub mod memory;
+pub mod oidc;
+pub mod postgres;
 pub mod redis;
+pub mod signed;
+pub mod tokens;
 
-#[derive(Clone, Serialize, Deserialize, Debug, ToSchema)]
+/// How stale `last_seen_at` can get before the session is refreshed, to avoid a write on every request.
+pub(crate) const LAST_SEEN_RESOLUTION: Duration = Duration::minutes(1);
+
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq, ToSchema)]
 pub struct User {
     pub id: u64,
     pub name: String,
@@ -18,53 +36,360 @@ pub struct User {
     pub login: String,
 }
 
+/// A user as recorded in the directory, on their first login and updated on every login.
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+pub struct UserRecord {
+    pub id: u64,
+    pub login: String,
+    pub name: String,
+    pub avatar_url: String,
+    pub first_login_at: DateTime<Utc>,
+    pub last_login_at: DateTime<Utc>,
+    /// Disabled users cannot log in, and their existing sessions are rejected.
+    pub disabled: bool,
+}
+
+/// Where a session was created from, kept along with the session.
+#[derive(Clone, Serialize, Deserialize, Debug, Default, PartialEq)]
+pub struct ClientInfo {
+    pub ip: Option<String>,
+    pub user_agent: Option<String>,
+}
+
+/// An active session of a user, as listed to them.
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+pub struct SessionInfo {
+    /// Identifies the session without revealing its token, see `session_id`.
+    pub id: String,
+    pub created_at: DateTime<Utc>,
+    pub last_seen_at: DateTime<Utc>,
+    #[serde(flatten)]
+    pub client_info: ClientInfo,
+}
+
+/// How long, in seconds, a login can take between redirect and callback.
+pub(crate) const LOGIN_STATE_TTL: u64 = 600;
+
+/// When a session used at `now` expires: `ttl` seconds later, but no later than `max_lifetime`
+/// seconds after it was created.
+pub(crate) fn session_expires_at(
+    created_at: DateTime<Utc>,
+    now: DateTime<Utc>,
+    ttl: i64,
+    max_lifetime: i64,
+) -> DateTime<Utc> {
+    let idle = now + Duration::seconds(ttl);
+    let absolute = created_at + Duration::seconds(max_lifetime);
+
+    idle.min(absolute)
+}
+
+/// Public identifier of the session of `token`.
+pub fn session_id(token: &str) -> String {
+    URL_SAFE_NO_PAD.encode(Sha256::digest(token.as_bytes()))
+}
+
 #[derive(Clone, Serialize, Deserialize, Debug)]
 pub struct Session {
-    user: User,
-    token: String,
+    pub(crate) user: User,
+    pub(crate) token: String,
+}
+
+/// Random values tying a login callback to the browser that started the login.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LoginState {
+    /// Sent as the oauth `state` parameter and kept in a cookie.
+    pub state: String,
+    /// PKCE code verifier, only its challenge leaves the server before the callback.
+    pub verifier: String,
+}
+
+impl LoginState {
+    pub fn new() -> Self {
+        LoginState {
+            state: random_string(),
+            verifier: random_string(),
+        }
+    }
+
+    /// PKCE S256 code challenge of the verifier.
+    pub fn challenge(&self) -> String {
+        URL_SAFE_NO_PAD.encode(Sha256::digest(self.verifier.as_bytes()))
+    }
+}
+
+fn random_string() -> String {
+    let mut bytes = [0u8; 32];
+    rand::rng().fill_bytes(&mut bytes);
+
+    URL_SAFE_NO_PAD.encode(bytes)
 }
 
 #[automock]
 #[async_trait]
 pub trait Repo: Send + Sync {
-    async fn save(&self, user: User) -> Result<String, Error>;
+    async fn save(&self, user: User, client_info: ClientInfo) -> Result<String, Error>;
     async fn get(&self, token: String) -> Result<User, Error>;
+    /// Marks the session as used, which may extend its expiry.
+    async fn refresh(&self, token: String) -> Result<(), Error>;
+
+    async fn user_sessions_list(&self, login: String) -> Result<Vec<SessionInfo>, Error>;
+    /// Deletes a session of `login` by its id.
+    async fn user_sessions_delete(&self, login: String, id: String) -> Result<(), Error>;
     async fn delete(&self, token: String) -> Result<(), Error>;
+
+    /// Keeps the state of a login in progress for a few minutes.
+    async fn login_state_save(&self, login: LoginState) -> Result<(), Error>;
+    /// Returns the verifier of a login in progress, it can only be taken once.
+    async fn login_state_take(&self, state: String) -> Result<String, Error>;
+}
+
+/// Every user who ever logged in.
+#[automock]
+#[async_trait]
+pub trait Directory: Send + Sync {
+    /// Creates or updates the user, the last login is not updated for disabled users.
+    async fn users_record_login(&self, user: User) -> Result<UserRecord, Error>;
+    async fn users_get(&self, login: String) -> Result<UserRecord, Error>;
+    async fn users_list(&self) -> Result<Vec<UserRecord>, Error>;
+    async fn users_set_disabled(&self, login: String, disabled: bool) -> Result<(), Error>;
 }
 
 #[automock]
 #[async_trait]
 pub trait Authenticator: Sync + Send {
-    async fn start_login(&self) -> Result<String, Error>;
-    async fn login(&self, code: String) -> Result<Session, Error>;
+    /// Returns the url of the provider to redirect the user to.
+    async fn start_login(&self, login: &LoginState) -> Result<String, Error>;
+    async fn login(
+        &self,
+        code: String,
+        verifier: String,
+        client_info: ClientInfo,
+    ) -> Result<Session, Error>;
+}
+
+/// Authenticators by provider name, as used in `/api/auth/login/{provider}`.
+#[derive(Default)]
+pub struct Providers {
+    authenticators: Vec<(String, Arc<dyn Authenticator>)>,
+}
+
+impl Providers {
+    pub fn new() -> Self {
+        Providers::default()
+    }
+
+    /// Registers a provider, the first one registered is the default.
+    pub fn with(mut self, name: String, authenticator: Arc<dyn Authenticator>) -> Self {
+        self.authenticators.push((name, authenticator));
+        self
+    }
+
+    pub fn get(&self, name: &str) -> Result<Arc<dyn Authenticator>, Error> {
+        self.authenticators
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, authenticator)| authenticator.clone())
+            .ok_or_else(|| Error::NotFound(format!("provider {}", name)))
+    }
+
+    /// Name of the provider used by the routes without a provider.
+    pub fn default_name(&self) -> Option<&str> {
+        self.authenticators.first().map(|(name, _)| name.as_str())
+    }
+
+    pub fn names(&self) -> Vec<String> {
+        self.authenticators
+            .iter()
+            .map(|(name, _)| name.clone())
+            .collect()
+    }
 }
 
 #[automock]
 #[async_trait]
 pub trait SessionManager: Sync + Send {
     async fn session(&self, token: String) -> Result<User, Error>;
+    /// Turns a session saved by an authenticator into the one handed to the browser.
+    async fn issue(&self, session: Session) -> Result<Session, Error>;
+    /// A replacement for `token` when it is due for one, to send back to the browser.
+    async fn renew(&self, token: String) -> Result<Option<String>, Error>;
+    /// Id of the session of `token`, as listed by `sessions`.
+    fn id(&self, token: &str) -> Result<String, Error>;
+    /// CSRF token of the session of `token`, which stays the same when the token is renewed.
+    fn csrf_token(&self, token: &str) -> Result<String, Error>;
+    /// Resolves a personal API token, sent instead of a session cookie.
+    async fn api_token(&self, token: String) -> Result<ApiToken, Error>;
     async fn logout(&self, token: String) -> Result<(), Error>;
+
+    /// Active sessions of a user, most recently used first.
+    async fn sessions(&self, login: String) -> Result<Vec<SessionInfo>, Error>;
+    async fn revoke(&self, login: String, id: String) -> Result<(), Error>;
+    /// Revokes every session of a user, except the one with id `keep` if given.
+    async fn revoke_all(&self, login: String, keep: Option<String>) -> Result<(), Error>;
 }
 
 pub struct DefaultSessionManager {
     repo: Arc<dyn Repo>,
+    directory: Option<Arc<dyn Directory>>,
+    tokens: Option<Arc<ApiTokens>>,
 }
 
 impl DefaultSessionManager {
     pub fn new(repo: Arc<dyn Repo>) -> Self {
-        DefaultSessionManager { repo }
+        DefaultSessionManager {
+            repo,
+            directory: None,
+            tokens: None,
+        }
+    }
+
+    /// Rejects the sessions of users disabled in the directory.
+    pub fn with_directory(mut self, directory: Arc<dyn Directory>) -> Self {
+        self.directory = Some(directory);
+        self
+    }
+
+    /// Accepts personal API tokens, they are rejected otherwise.
+    pub fn with_tokens(mut self, tokens: Arc<ApiTokens>) -> Self {
+        self.tokens = Some(tokens);
+        self
+    }
+
+    async fn check_enabled(&self, login: String) -> Result<(), Error> {
+        if let Some(directory) = &self.directory {
+            match directory.users_get(login).await {
+                Ok(record) if record.disabled => {
+                    return Err(Error::PermissionDenied("user disabled".to_string()));
+                }
+                // sessions from before the directory existed
+                Ok(_) | Err(Error::NotFound(_)) => (),
+                Err(err) => return Err(err),
+            }
+        }
+
+        Ok(())
     }
 }
 
 #[async_trait]
 impl SessionManager for DefaultSessionManager {
     async fn session(&self, token: String) -> Result<User, Error> {
-        self.repo.get(token).await
+        self.repo.refresh(token.clone()).await?;
+        let user = self.repo.get(token).await?;
+        self.check_enabled(user.login.clone()).await?;
+
+        Ok(user)
+    }
+
+    async fn issue(&self, session: Session) -> Result<Session, Error> {
+        Ok(session)
+    }
+
+    /// Sessions are extended in the repo, the token never changes.
+    async fn renew(&self, _token: String) -> Result<Option<String>, Error> {
+        Ok(None)
+    }
+
+    fn id(&self, token: &str) -> Result<String, Error> {
+        Ok(session_id(token))
+    }
+
+    fn csrf_token(&self, token: &str) -> Result<String, Error> {
+        Ok(csrf::token(token))
+    }
+
+    async fn api_token(&self, token: String) -> Result<ApiToken, Error> {
+        let tokens = match &self.tokens {
+            Some(tokens) => tokens,
+            None => {
+                return Err(Error::PermissionDenied(
+                    "api tokens are not enabled".to_string(),
+                ));
+            }
+        };
+
+        let token = tokens.authenticate(token).await?;
+        self.check_enabled(token.user.login.clone()).await?;
+
+        Ok(token)
     }
 
     async fn logout(&self, token: String) -> Result<(), Error> {
         self.repo.delete(token).await
     }
+
+    async fn sessions(&self, login: String) -> Result<Vec<SessionInfo>, Error> {
+        let mut sessions = self.repo.user_sessions_list(login).await?;
+        sessions.sort_by_key(|session| std::cmp::Reverse(session.last_seen_at));
+
+        Ok(sessions)
+    }
+
+    async fn revoke(&self, login: String, id: String) -> Result<(), Error> {
+        self.repo.user_sessions_delete(login, id).await
+    }
+
+    async fn revoke_all(&self, login: String, keep: Option<String>) -> Result<(), Error> {
+        for session in self.repo.user_sessions_list(login.clone()).await? {
+            if keep.as_ref() != Some(&session.id) {
+                self.repo
+                    .user_sessions_delete(login.clone(), session.id)
+                    .await?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod providers_test {
+    use super::*;
+
+    #[test]
+    fn test_get() {
+        let providers = Providers::new()
+            .with("github".to_string(), Arc::new(MockAuthenticator::new()))
+            .with("gitlab".to_string(), Arc::new(MockAuthenticator::new()));
+
+        assert!(providers.get("gitlab").is_ok());
+        assert_eq!(providers.default_name(), Some("github"));
+        assert_eq!(providers.names(), vec!["github", "gitlab"]);
+        assert_eq!(
+            providers.get("unknown").err().unwrap().to_string(),
+            "provider unknown not found"
+        );
+    }
+}
+
+#[cfg(test)]
+mod login_state_test {
+    use super::*;
+
+    #[test]
+    fn test_new_is_random() {
+        let a = LoginState::new();
+        let b = LoginState::new();
+
+        assert_eq!(a.state.len(), 43);
+        assert_ne!(a.state, b.state);
+        assert_ne!(a.state, a.verifier);
+    }
+
+    #[test]
+    fn test_challenge() {
+        // example from RFC 7636, appendix B
+        let login = LoginState {
+            state: "state".to_string(),
+            verifier: "dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjXk".to_string(),
+        };
+
+        assert_eq!(
+            login.challenge(),
+            "E9Melhoa2OwvFrEMTJguCHaoeK1t8URWbuGJSstw-cM"
+        );
+    }
 }
 
 #[cfg(test)]
@@ -74,6 +399,11 @@ mod default_session_manager_test {
     #[tokio::test]
     async fn test_session_success() {
         let mut mock_repo = MockRepo::new();
+        mock_repo
+            .expect_refresh()
+            .withf(|token| token == "test_token")
+            .times(1)
+            .returning(|_| Ok(()));
         mock_repo.expect_get().returning(|_| {
             Ok(User {
                 id: 123456,
@@ -100,6 +430,7 @@ mod default_session_manager_test {
     #[tokio::test]
     async fn test_session_not_found() {
         let mut mock_repo = MockRepo::new();
+        mock_repo.expect_refresh().returning(|_| Ok(()));
         mock_repo
             .expect_get()
             .returning(|_| Err(Error::PermissionDenied("no session".to_string())));
@@ -116,6 +447,210 @@ mod default_session_manager_test {
         );
     }
 
+    fn record(disabled: bool) -> UserRecord {
+        UserRecord {
+            id: 123456,
+            login: "john_doe".to_string(),
+            name: "John Doe".to_string(),
+            avatar_url: "https://foo.bar".to_string(),
+            first_login_at: Utc::now(),
+            last_login_at: Utc::now(),
+            disabled,
+        }
+    }
+
+    fn session_repo() -> MockRepo {
+        let mut mock_repo = MockRepo::new();
+        mock_repo.expect_refresh().returning(|_| Ok(()));
+        mock_repo.expect_get().returning(|_| {
+            Ok(User {
+                id: 123456,
+                name: "John Doe".to_string(),
+                avatar_url: "https://foo.bar".to_string(),
+                login: "john_doe".to_string(),
+            })
+        });
+        mock_repo
+    }
+
+    #[tokio::test]
+    async fn test_session_disabled_user() {
+        let mut directory = MockDirectory::new();
+        directory
+            .expect_users_get()
+            .withf(|login| login == "john_doe")
+            .returning(|_| Ok(record(true)));
+
+        let session_manager = DefaultSessionManager::new(Arc::new(session_repo()))
+            .with_directory(Arc::new(directory));
+
+        let result = session_manager.session("test_token".to_string()).await;
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "permission denied: user disabled"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_api_token() {
+        let mut token_repo = tokens::MockTokenRepo::new();
+        token_repo.expect_tokens_get().returning(|_| {
+            Ok(ApiToken {
+                id: uuid::Uuid::new_v4(),
+                user: User {
+                    id: 123456,
+                    name: "John Doe".to_string(),
+                    avatar_url: "https://foo.bar".to_string(),
+                    login: "john_doe".to_string(),
+                },
+                name: "ci".to_string(),
+                scopes: vec![tokens::Scope::Read],
+                created_at: Utc::now(),
+                expires_at: Utc::now() + chrono::Duration::days(1),
+            })
+        });
+        let token_repo: Arc<dyn tokens::TokenRepo> = Arc::new(token_repo);
+
+        let session_manager = DefaultSessionManager::new(Arc::new(MockRepo::new()));
+        assert_eq!(
+            session_manager
+                .api_token("blog_token".to_string())
+                .await
+                .unwrap_err()
+                .to_string(),
+            "permission denied: api tokens are not enabled"
+        );
+
+        let mut directory = MockDirectory::new();
+        directory
+            .expect_users_get()
+            .times(1)
+            .returning(|_| Ok(record(false)));
+        directory.expect_users_get().returning(|_| Ok(record(true)));
+
+        let session_manager = DefaultSessionManager::new(Arc::new(MockRepo::new()))
+            .with_directory(Arc::new(directory))
+            .with_tokens(Arc::new(ApiTokens::new(token_repo)));
+
+        let token = session_manager
+            .api_token("blog_token".to_string())
+            .await
+            .unwrap();
+        assert_eq!(token.user.login, "john_doe");
+        assert_eq!(
+            session_manager
+                .api_token("blog_token".to_string())
+                .await
+                .unwrap_err()
+                .to_string(),
+            "permission denied: user disabled"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_session_directory() {
+        let mut directory = MockDirectory::new();
+        directory
+            .expect_users_get()
+            .times(1)
+            .returning(|_| Ok(record(false)));
+        directory
+            .expect_users_get()
+            .returning(|login| Err(Error::NotFound(format!("user {}", login))));
+
+        let session_manager = DefaultSessionManager::new(Arc::new(session_repo()))
+            .with_directory(Arc::new(directory));
+
+        assert!(
+            session_manager
+                .session("test_token".to_string())
+                .await
+                .is_ok()
+        );
+        assert!(
+            session_manager
+                .session("test_token".to_string())
+                .await
+                .is_ok()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_session_expired() {
+        let mut mock_repo = MockRepo::new();
+        mock_repo
+            .expect_refresh()
+            .returning(|_| Err(Error::PermissionDenied("session expired".to_string())));
+        mock_repo.expect_get().never();
+
+        let session_manager = DefaultSessionManager::new(Arc::new(mock_repo));
+
+        let result = session_manager.session("test_token".to_string()).await;
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "permission denied: session expired"
+        );
+    }
+
+    fn session_info(id: &str, minutes_ago: i64) -> SessionInfo {
+        SessionInfo {
+            id: id.to_string(),
+            created_at: Utc::now() - chrono::Duration::days(1),
+            last_seen_at: Utc::now() - chrono::Duration::minutes(minutes_ago),
+            client_info: ClientInfo::default(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_sessions_sorted() {
+        let mut mock_repo = MockRepo::new();
+        mock_repo
+            .expect_user_sessions_list()
+            .withf(|login| login == "john_doe")
+            .returning(|_| Ok(vec![session_info("old", 60), session_info("new", 1)]));
+
+        let session_manager = DefaultSessionManager::new(Arc::new(mock_repo));
+
+        let sessions = session_manager
+            .sessions("john_doe".to_string())
+            .await
+            .unwrap();
+
+        assert_eq!(sessions[0].id, "new");
+        assert_eq!(sessions[1].id, "old");
+    }
+
+    #[tokio::test]
+    async fn test_revoke_all_keeps_current() {
+        let mut mock_repo = MockRepo::new();
+        mock_repo.expect_user_sessions_list().returning(|_| {
+            Ok(vec![
+                session_info("a", 1),
+                session_info("current", 1),
+                session_info("b", 1),
+            ])
+        });
+        mock_repo
+            .expect_user_sessions_delete()
+            .withf(|login, id| login == "john_doe" && id != "current")
+            .times(2)
+            .returning(|_, _| Ok(()));
+
+        let session_manager = DefaultSessionManager::new(Arc::new(mock_repo));
+
+        session_manager
+            .revoke_all("john_doe".to_string(), Some("current".to_string()))
+            .await
+            .unwrap();
+    }
+
+    #[test]
+    fn test_session_id() {
+        assert_eq!(session_id("token"), session_id("token"));
+        assert_ne!(session_id("token"), session_id("other"));
+        assert_eq!(session_id("token").len(), 43);
+    }
+
     #[tokio::test]
     async fn test_logout_success() {
         let mut mock_repo = MockRepo::new();
@@ -0,0 +1,665 @@
+use super::signed::Denylist;
+use super::tokens::ApiToken;
+use super::{LAST_SEEN_RESOLUTION, Session, SessionInfo, SessionManager, User};
+use crate::errors::Error;
+use actix_web::dev::Server;
+use actix_web::{App, HttpResponse, HttpServer, Responder, get, web};
+use async_trait::async_trait;
+use chrono::{DateTime, Duration, Utc};
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+
+#[derive(Clone)]
+struct Entry {
+    user: User,
+    id: String,
+    expires_at: DateTime<Utc>,
+    /// When the inner manager last accepted the session.
+    checked_at: DateTime<Utc>,
+}
+
+/// Counters of a session cache, since it was created.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    pub evictions: u64,
+    pub entries: usize,
+}
+
+impl CacheStats {
+    /// Share of lookups answered from the cache, 0 before any lookup.
+    pub fn hit_rate(&self) -> f64 {
+        match self.hits + self.misses {
+            0 => 0.0,
+            lookups => self.hits as f64 / lookups as f64,
+        }
+    }
+
+    /// The stats in the Prometheus text format.
+    pub fn prometheus(&self) -> String {
+        let families = [
+            (
+                "session_cache_hits_total",
+                "counter",
+                "Session lookups answered from the cache.",
+                self.hits as f64,
+            ),
+            (
+                "session_cache_misses_total",
+                "counter",
+                "Session lookups passed on to the inner session manager.",
+                self.misses as f64,
+            ),
+            (
+                "session_cache_evictions_total",
+                "counter",
+                "Sessions dropped to make room for others.",
+                self.evictions as f64,
+            ),
+            (
+                "session_cache_entries",
+                "gauge",
+                "Sessions in the cache.",
+                self.entries as f64,
+            ),
+            (
+                "session_cache_hit_ratio",
+                "gauge",
+                "Share of session lookups answered from the cache.",
+                self.hit_rate(),
+            ),
+        ];
+
+        families
+            .iter()
+            .map(|(name, kind, help, value)| {
+                format!("# HELP {name} {help}\n# TYPE {name} {kind}\n{name} {value}\n")
+            })
+            .collect()
+    }
+}
+
+/// Keeps the users of recently seen sessions in memory, in front of another session manager.
+///
+/// Entries live for `ttl` and at most `capacity` are kept. The inner manager is still asked about
+/// a cached session once every `LAST_SEEN_RESOLUTION`, so that sessions are extended and disabled
+/// users rejected as without the cache. Logging out and revoking sessions through the cache drop
+/// them right away, and deny them in the denylist if any. Hits are checked against a copy of the
+/// denylist, so sessions denied by other processes are dropped once `refresh_denied` reloaded it.
+pub struct CachingSessionManager {
+    inner: Arc<dyn SessionManager>,
+    denylist: Option<Arc<dyn Denylist>>,
+    /// As last loaded from the denylist.
+    denied: RwLock<HashSet<String>>,
+    entries: Mutex<HashMap<String, Entry>>,
+    capacity: usize,
+    ttl: Duration,
+    hits: AtomicU64,
+    misses: AtomicU64,
+    evictions: AtomicU64,
+}
+
+impl CachingSessionManager {
+    pub fn new(inner: Arc<dyn SessionManager>, capacity: usize, ttl: i64) -> Self {
+        CachingSessionManager {
+            inner,
+            denylist: None,
+            denied: RwLock::new(HashSet::new()),
+            entries: Mutex::new(HashMap::new()),
+            capacity,
+            ttl: Duration::seconds(ttl),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+            evictions: AtomicU64::new(0),
+        }
+    }
+
+    /// Shares logouts and revocations with the caches of other processes through `denylist`.
+    pub fn with_denylist(mut self, denylist: Arc<dyn Denylist>) -> Self {
+        self.denylist = Some(denylist);
+        self
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        CacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            evictions: self.evictions.load(Ordering::Relaxed),
+            entries: self.entries.lock().unwrap().len(),
+        }
+    }
+
+    fn get(&self, token: &str) -> Option<Entry> {
+        let entries = self.entries.lock().unwrap();
+
+        match entries.get(token) {
+            Some(entry) if entry.expires_at > Utc::now() => Some(entry.clone()),
+            _ => None,
+        }
+    }
+
+    fn insert(&self, token: String, entry: Entry) {
+        if self.capacity == 0 {
+            return;
+        }
+
+        let mut entries = self.entries.lock().unwrap();
+
+        if !entries.contains_key(&token) && entries.len() >= self.capacity {
+            let now = Utc::now();
+            let before = entries.len();
+            entries.retain(|_, entry| entry.expires_at > now);
+
+            // still full, make room by dropping the entry closest to expiring
+            if entries.len() >= self.capacity {
+                let oldest = entries
+                    .iter()
+                    .min_by_key(|(_, entry)| entry.expires_at)
+                    .map(|(token, _)| token.clone());
+
+                if let Some(oldest) = oldest {
+                    entries.remove(&oldest);
+                }
+            }
+
+            self.evictions
+                .fetch_add((before - entries.len()) as u64, Ordering::Relaxed);
+        }
+
+        entries.insert(token, entry);
+    }
+
+    fn invalidate(&self, keep: impl Fn(&Entry) -> bool) {
+        self.entries.lock().unwrap().retain(|_, entry| keep(entry));
+    }
+
+    /// Reloads the copy of the denylist that hits are checked against, and drops the sessions
+    /// denied since.
+    pub async fn refresh_denied(&self) -> Result<(), Error> {
+        let Some(denylist) = &self.denylist else {
+            return Ok(());
+        };
+
+        let denied: HashSet<String> = denylist.denied().await?.into_iter().collect();
+
+        self.invalidate(|entry| !denied.contains(&entry.id));
+        *self.denied.write().unwrap() = denied;
+
+        Ok(())
+    }
+
+    /// Denies `ids` for as long as other processes may have them cached.
+    async fn deny(&self, ids: Vec<String>) -> Result<(), Error> {
+        if let Some(denylist) = &self.denylist {
+            for id in ids {
+                denylist.deny(id, self.ttl).await?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl SessionManager for CachingSessionManager {
+    async fn session(&self, token: String) -> Result<User, Error> {
+        if let Some(entry) = self.get(&token) {
+            if self.denied.read().unwrap().contains(&entry.id) {
+                self.entries.lock().unwrap().remove(&token);
+                return Err(Error::PermissionDenied("session revoked".to_string()));
+            }
+
+            if Utc::now() - entry.checked_at < LAST_SEEN_RESOLUTION {
+                self.hits.fetch_add(1, Ordering::Relaxed);
+                return Ok(entry.user);
+            }
+        }
+
+        self.misses.fetch_add(1, Ordering::Relaxed);
+
+        // failures are not cached, so that a new session is picked up right away
+        let user = match self.inner.session(token.clone()).await {
+            Ok(user) => user,
+            Err(err) => {
+                self.entries.lock().unwrap().remove(&token);
+                return Err(err);
+            }
+        };
+        let id = self.inner.id(&token)?;
+        let now = Utc::now();
+
+        self.insert(
+            token,
+            Entry {
+                user: user.clone(),
+                id,
+                expires_at: now + self.ttl,
+                checked_at: now,
+            },
+        );
+
+        Ok(user)
+    }
+
+    async fn issue(&self, session: Session) -> Result<Session, Error> {
+        self.inner.issue(session).await
+    }
+
+    async fn renew(&self, token: String) -> Result<Option<String>, Error> {
+        self.inner.renew(token).await
+    }
+
+    fn id(&self, token: &str) -> Result<String, Error> {
+        self.inner.id(token)
+    }
+
+    fn csrf_token(&self, token: &str) -> Result<String, Error> {
+        self.inner.csrf_token(token)
+    }
+
+    async fn api_token(&self, token: String) -> Result<ApiToken, Error> {
+        self.inner.api_token(token).await
+    }
+
+    async fn logout(&self, token: String) -> Result<(), Error> {
+        self.entries.lock().unwrap().remove(&token);
+        let id = self.inner.id(&token)?;
+
+        self.inner.logout(token).await?;
+        self.deny(vec![id]).await
+    }
+
+    async fn sessions(&self, login: String) -> Result<Vec<SessionInfo>, Error> {
+        self.inner.sessions(login).await
+    }
+
+    async fn revoke(&self, login: String, id: String) -> Result<(), Error> {
+        // only deny sessions that belong to the user
+        self.inner.revoke(login.clone(), id.clone()).await?;
+        self.invalidate(|entry| entry.user.login != login || entry.id != id);
+
+        self.deny(vec![id]).await
+    }
+
+    async fn revoke_all(&self, login: String, keep: Option<String>) -> Result<(), Error> {
+        let revoked = match &self.denylist {
+            Some(_) => self
+                .inner
+                .sessions(login.clone())
+                .await?
+                .into_iter()
+                .map(|session| session.id)
+                .filter(|id| keep.as_ref() != Some(id))
+                .collect(),
+            None => Vec::new(),
+        };
+
+        self.inner.revoke_all(login.clone(), keep.clone()).await?;
+        self.invalidate(|entry| entry.user.login != login || keep.as_ref() == Some(&entry.id));
+
+        self.deny(revoked).await
+    }
+}
+
+/// Reloads the denylist of `cache` every `interval`.
+///
+/// Runs until the process exits; failures are logged and retried on the next tick.
+pub async fn refresher(cache: Arc<CachingSessionManager>, interval: std::time::Duration) {
+    let mut ticker = tokio::time::interval(interval);
+
+    loop {
+        ticker.tick().await;
+
+        if let Err(err) = cache.refresh_denied().await {
+            tracing::error!(error = %err, "refreshing the session denylist");
+        }
+    }
+}
+
+/// Logs the stats of `cache` every `interval`, until the process exits.
+pub async fn report(cache: Arc<CachingSessionManager>, interval: std::time::Duration) {
+    let mut ticker = tokio::time::interval(interval);
+
+    loop {
+        ticker.tick().await;
+
+        let stats = cache.stats();
+        tracing::info!(
+            hits = stats.hits,
+            misses = stats.misses,
+            evictions = stats.evictions,
+            entries = stats.entries,
+            hit_rate = stats.hit_rate(),
+            "session cache"
+        );
+    }
+}
+
+#[get("/metrics")]
+async fn metrics(cache: web::Data<CachingSessionManager>) -> impl Responder {
+    HttpResponse::Ok()
+        .content_type("text/plain; version=0.0.4")
+        .body(cache.stats().prometheus())
+}
+
+/// Serves the stats of `cache` at `/metrics`, for Prometheus to scrape, once the returned server
+/// is spawned.
+pub fn metrics_server(
+    cache: Arc<CachingSessionManager>,
+    listen_addr: String,
+) -> Result<Server, std::io::Error> {
+    let data = web::Data::from(cache);
+
+    Ok(
+        HttpServer::new(move || App::new().app_data(data.clone()).service(metrics))
+            .bind(listen_addr)?
+            .run(),
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::auth::signed::MockDenylist;
+    use crate::auth::{MockSessionManager, session_id};
+
+    fn user(login: &str) -> User {
+        User {
+            id: 123456,
+            name: "John Doe".to_string(),
+            avatar_url: "https://foo.bar".to_string(),
+            login: login.to_string(),
+        }
+    }
+
+    /// Every session belongs to john_doe, except those with a token starting with "jane".
+    fn inner(times: usize) -> MockSessionManager {
+        let mut inner = MockSessionManager::new();
+        inner.expect_session().times(times).returning(|token| {
+            Ok(user(if token.starts_with("jane") {
+                "jane_doe"
+            } else {
+                "john_doe"
+            }))
+        });
+        inner.expect_id().returning(|token| Ok(session_id(token)));
+        inner
+    }
+
+    #[tokio::test]
+    async fn test_session_cached() {
+        let cache = CachingSessionManager::new(Arc::new(inner(1)), 10, 60);
+
+        for _ in 0..3 {
+            assert_eq!(
+                cache.session("token".to_string()).await.unwrap(),
+                user("john_doe")
+            );
+        }
+
+        let stats = cache.stats();
+        assert_eq!((stats.hits, stats.misses, stats.entries), (2, 1, 1));
+        assert!((stats.hit_rate() - 2.0 / 3.0).abs() < f64::EPSILON);
+    }
+
+    #[test]
+    fn test_hit_rate() {
+        let stats = |hits, misses| CacheStats {
+            hits,
+            misses,
+            evictions: 0,
+            entries: 0,
+        };
+
+        assert_eq!(stats(0, 0).hit_rate(), 0.0);
+        assert_eq!(stats(0, 4).hit_rate(), 0.0);
+        assert_eq!(stats(3, 1).hit_rate(), 0.75);
+        assert_eq!(stats(5, 0).hit_rate(), 1.0);
+    }
+
+    #[actix_web::test]
+    async fn test_metrics() {
+        let cache = Arc::new(CachingSessionManager::new(Arc::new(inner(1)), 10, 60));
+        for _ in 0..4 {
+            assert!(cache.session("token".to_string()).await.is_ok());
+        }
+
+        let app = actix_web::test::init_service(
+            App::new().app_data(web::Data::from(cache)).service(metrics),
+        )
+        .await;
+
+        let req = actix_web::test::TestRequest::get()
+            .uri("/metrics")
+            .to_request();
+        let body = actix_web::test::call_and_read_body(&app, req).await;
+        let body = std::str::from_utf8(&body).unwrap();
+
+        assert!(
+            body.contains("# TYPE session_cache_hits_total counter\nsession_cache_hits_total 3\n")
+        );
+        assert!(body.contains("\nsession_cache_misses_total 1\n"));
+        assert!(body.contains("\nsession_cache_evictions_total 0\n"));
+        assert!(body.contains("\nsession_cache_entries 1\n"));
+        assert!(body.contains("\nsession_cache_hit_ratio 0.75\n"));
+    }
+
+    #[tokio::test]
+    async fn test_session_checked_again() {
+        let cache = CachingSessionManager::new(Arc::new(inner(2)), 10, 600);
+
+        assert!(cache.session("token".to_string()).await.is_ok());
+
+        // as if the inner manager last saw the session before the resolution
+        cache
+            .entries
+            .lock()
+            .unwrap()
+            .get_mut("token")
+            .unwrap()
+            .checked_at -= LAST_SEEN_RESOLUTION;
+
+        assert!(cache.session("token".to_string()).await.is_ok());
+        assert!(cache.session("token".to_string()).await.is_ok());
+        assert_eq!(cache.stats().hits, 1);
+    }
+
+    #[tokio::test]
+    async fn test_session_rejected_when_checked_again() {
+        let mut inner = MockSessionManager::new();
+        let mut calls = 0;
+        inner.expect_session().times(2).returning(move |_| {
+            calls += 1;
+            match calls {
+                1 => Ok(user("john_doe")),
+                _ => Err(Error::PermissionDenied("user disabled".to_string())),
+            }
+        });
+        inner.expect_id().returning(|token| Ok(session_id(token)));
+
+        let cache = CachingSessionManager::new(Arc::new(inner), 10, 600);
+
+        assert!(cache.session("token".to_string()).await.is_ok());
+        cache
+            .entries
+            .lock()
+            .unwrap()
+            .get_mut("token")
+            .unwrap()
+            .checked_at -= LAST_SEEN_RESOLUTION;
+
+        assert_eq!(
+            cache
+                .session("token".to_string())
+                .await
+                .unwrap_err()
+                .to_string(),
+            "permission denied: user disabled"
+        );
+        assert_eq!(cache.stats().entries, 0);
+    }
+
+    #[tokio::test]
+    async fn test_session_denied_elsewhere() {
+        let mut denylist = MockDenylist::new();
+        denylist
+            .expect_denied()
+            .times(1)
+            // logged out through the cache of another process
+            .returning(|| Ok(vec![session_id("token")]));
+
+        let cache = CachingSessionManager::new(Arc::new(inner(2)), 10, 60)
+            .with_denylist(Arc::new(denylist));
+
+        // hits are checked against the copy, not the denylist
+        assert!(cache.session("token".to_string()).await.is_ok());
+        assert!(cache.session("token".to_string()).await.is_ok());
+
+        cache.refresh_denied().await.unwrap();
+        assert_eq!(cache.stats().entries, 0);
+
+        // misses are still up to the inner manager, but hits are denied
+        assert!(cache.session("token".to_string()).await.is_ok());
+        assert_eq!(
+            cache
+                .session("token".to_string())
+                .await
+                .unwrap_err()
+                .to_string(),
+            "permission denied: session revoked"
+        );
+        assert_eq!(cache.stats().entries, 0);
+    }
+
+    #[tokio::test]
+    async fn test_session_expired() {
+        let cache = CachingSessionManager::new(Arc::new(inner(2)), 10, 0);
+
+        assert!(cache.session("token".to_string()).await.is_ok());
+        assert!(cache.session("token".to_string()).await.is_ok());
+        assert_eq!(cache.stats().hits, 0);
+    }
+
+    #[tokio::test]
+    async fn test_errors_not_cached() {
+        let mut inner = MockSessionManager::new();
+        inner
+            .expect_session()
+            .times(2)
+            .returning(|_| Err(Error::PermissionDenied("no session".to_string())));
+
+        let cache = CachingSessionManager::new(Arc::new(inner), 10, 60);
+
+        assert!(cache.session("token".to_string()).await.is_err());
+        assert!(cache.session("token".to_string()).await.is_err());
+        assert_eq!(cache.stats().entries, 0);
+    }
+
+    #[tokio::test]
+    async fn test_bounded() {
+        let cache = CachingSessionManager::new(Arc::new(inner(3)), 2, 60);
+
+        for token in ["a", "b", "c"] {
+            assert!(cache.session(token.to_string()).await.is_ok());
+        }
+
+        let stats = cache.stats();
+        assert_eq!((stats.entries, stats.evictions), (2, 1));
+    }
+
+    #[tokio::test]
+    async fn test_logout_invalidates() {
+        let mut inner = inner(2);
+        inner.expect_logout().times(1).returning(|_| Ok(()));
+
+        let cache = CachingSessionManager::new(Arc::new(inner), 10, 60);
+
+        assert!(cache.session("token".to_string()).await.is_ok());
+        assert!(cache.logout("token".to_string()).await.is_ok());
+        assert!(cache.session("token".to_string()).await.is_ok());
+        assert_eq!(cache.stats().hits, 0);
+    }
+
+    #[tokio::test]
+    async fn test_logout_and_revoke_deny() {
+        let mut inner = inner(0);
+        inner.expect_logout().returning(|_| Ok(()));
+        inner.expect_revoke().returning(|_, _| Ok(()));
+        inner.expect_sessions().returning(|_| {
+            Ok(["mine", "other"]
+                .into_iter()
+                .map(|token| SessionInfo {
+                    id: session_id(token),
+                    created_at: Utc::now(),
+                    last_seen_at: Utc::now(),
+                    client_info: Default::default(),
+                })
+                .collect())
+        });
+        inner.expect_revoke_all().returning(|_, _| Ok(()));
+
+        let mut denylist = MockDenylist::new();
+        for token in ["logged_out", "revoked", "other"] {
+            denylist
+                .expect_deny()
+                .withf(move |id, ttl| *id == session_id(token) && *ttl == Duration::seconds(60))
+                .times(1)
+                .returning(|_, _| Ok(()));
+        }
+
+        let cache =
+            CachingSessionManager::new(Arc::new(inner), 10, 60).with_denylist(Arc::new(denylist));
+
+        assert!(cache.logout("logged_out".to_string()).await.is_ok());
+        assert!(
+            cache
+                .revoke("john_doe".to_string(), session_id("revoked"))
+                .await
+                .is_ok()
+        );
+        assert!(
+            cache
+                .revoke_all("john_doe".to_string(), Some(session_id("mine")))
+                .await
+                .is_ok()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_revoke_invalidates() {
+        let mut inner = inner(5);
+        inner.expect_revoke().returning(|_, _| Ok(()));
+        inner.expect_revoke_all().returning(|_, _| Ok(()));
+
+        let cache = CachingSessionManager::new(Arc::new(inner), 10, 60);
+
+        for token in ["mine", "other", "jane"] {
+            assert!(cache.session(token.to_string()).await.is_ok());
+        }
+
+        assert!(
+            cache
+                .revoke("john_doe".to_string(), session_id("mine"))
+                .await
+                .is_ok()
+        );
+        assert_eq!(cache.stats().entries, 2);
+
+        assert!(cache.session("mine".to_string()).await.is_ok());
+        assert!(
+            cache
+                .revoke_all("john_doe".to_string(), Some(session_id("mine")))
+                .await
+                .is_ok()
+        );
+
+        // only the kept session and the other user remain
+        assert_eq!(cache.stats().entries, 2);
+        assert!(cache.session("mine".to_string()).await.is_ok());
+        assert!(cache.session("jane".to_string()).await.is_ok());
+        assert!(cache.session("other".to_string()).await.is_ok());
+        assert_eq!(cache.stats().hits, 2);
+    }
+}
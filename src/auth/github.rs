@@ -1,4 +1,4 @@
-use super::{Authenticator, MockRepo, Repo, Session, User};
+use super::{Authenticator, ClientInfo, LoginState, Repo, Session, User};
 use crate::errors::Error;
 use async_trait::async_trait;
 use serde::Deserialize;
@@ -10,6 +10,8 @@ pub struct GithubAuthenticator {
     api_url: String,
     base_url: String,
     repo: Arc<dyn Repo>,
+    org: Option<String>,
+    team: Option<String>,
 }
 
 impl GithubAuthenticator {
@@ -39,9 +41,88 @@ impl GithubAuthenticator {
             api_url,
             repo,
             base_url,
+            org: None,
+            team: None,
         })
     }
 
+    /// Only allows members of the `org` organization to log in, and of its `team` if not empty.
+    pub fn with_org(mut self, org: String, team: String) -> Self {
+        self.org = Some(org).filter(|org| !org.is_empty());
+        self.team = Some(team).filter(|team| !team.is_empty());
+        self
+    }
+
+    /// Returns whether the membership at `url` exists and is active (not pending).
+    async fn is_active_member(
+        &self,
+        client: &reqwest::Client,
+        access_token: &str,
+        url: String,
+    ) -> Result<bool, Error> {
+        let response = client
+            .get(url)
+            .header("Authorization", format!("token {}", access_token))
+            .header("User-Agent", "finblog")
+            .send()
+            .await
+            .map_err(|err| Error::ConnectionError(format!("getting membership: {}", err)))?;
+
+        match response.status() {
+            reqwest::StatusCode::NOT_FOUND | reqwest::StatusCode::FORBIDDEN => return Ok(false),
+            status if !status.is_success() => {
+                return Err(Error::ConnectionError(format!(
+                    "getting membership: {}",
+                    status
+                )));
+            }
+            _ => (),
+        }
+
+        let membership: GithubMembership = response
+            .json()
+            .await
+            .map_err(|err| Error::SerializationError(format!("reading membership: {}", err)))?;
+
+        Ok(membership.state == "active")
+    }
+
+    async fn check_membership(
+        &self,
+        client: &reqwest::Client,
+        access_token: &str,
+        login: &str,
+    ) -> Result<(), Error> {
+        let org = match &self.org {
+            Some(org) => org,
+            None => return Ok(()),
+        };
+
+        let url = format!("{}/user/memberships/orgs/{}", self.api_url, org);
+        if !self.is_active_member(client, access_token, url).await? {
+            return Err(Error::PermissionDenied(format!(
+                "{} is not a member of {}",
+                login, org
+            )));
+        }
+
+        if let Some(team) = &self.team {
+            let url = format!(
+                "{}/orgs/{}/teams/{}/memberships/{}",
+                self.api_url, org, team, login
+            );
+
+            if !self.is_active_member(client, access_token, url).await? {
+                return Err(Error::PermissionDenied(format!(
+                    "{} is not a member of {}/{}",
+                    login, org, team
+                )));
+            }
+        }
+
+        Ok(())
+    }
+
     #[cfg(test)]
     async fn new_test(
         repo: Arc<dyn Repo>,
@@ -80,6 +161,11 @@ struct GithubUser {
     avatar_url: String,
 }
 
+#[derive(Deserialize)]
+struct GithubMembership {
+    state: String,
+}
+
 impl GithubUser {
     fn to_user(&self) -> User {
         User {
@@ -93,21 +179,31 @@ impl GithubUser {
 
 #[async_trait]
 impl Authenticator for GithubAuthenticator {
-    async fn start_login(&self) -> Result<String, Error> {
+    async fn start_login(&self, login: &LoginState) -> Result<String, Error> {
         let url = format!(
-            "{}/login/oauth/authorize?client_id={}&scope=read:user,read:org&redirect_uri={}/api/auth/login/callback",
-            self.url, self.client_id, self.base_url,
+            "{}/login/oauth/authorize?client_id={}&scope=read:user,read:org&redirect_uri={}/api/auth/login/callback&state={}&code_challenge={}&code_challenge_method=S256",
+            self.url,
+            self.client_id,
+            self.base_url,
+            login.state,
+            login.challenge(),
         );
 
         Ok(url)
     }
 
-    async fn login(&self, code: String) -> Result<Session, Error> {
+    async fn login(
+        &self,
+        code: String,
+        verifier: String,
+        client_info: ClientInfo,
+    ) -> Result<Session, Error> {
         let client = reqwest::Client::new();
         let params = [
             ("client_id", &self.client_id),
             ("client_secret", &self.client_secret),
             ("code", &code),
+            ("code_verifier", &verifier),
         ];
 
         let gh_token: GithubAccessToken = client
@@ -116,14 +212,10 @@ impl Authenticator for GithubAuthenticator {
             .form(&params)
             .send()
             .await
-            .map_err(|err| {
-                Error::ConnectionError(format!("getting access_token: {}", err.to_string()))
-            })?
+            .map_err(|err| Error::ConnectionError(format!("getting access_token: {}", err)))?
             .json()
             .await
-            .map_err(|err| {
-                Error::SerializationError(format!("reading access_token: {}", err.to_string()))
-            })?;
+            .map_err(|err| Error::SerializationError(format!("reading access_token: {}", err)))?;
 
         if !gh_token.error.is_empty() {
             return Err(Error::PermissionDenied(format!(
@@ -142,16 +234,17 @@ impl Authenticator for GithubAuthenticator {
             .header("User-Agent", "finblog")
             .send()
             .await
-            .map_err(|err| Error::ConnectionError(format!("getting user: {}", err.to_string())))?
+            .map_err(|err| Error::ConnectionError(format!("getting user: {}", err)))?
             .json()
             .await
-            .map_err(|err| {
-                Error::SerializationError(format!("reading user: {}", err.to_string()))
-            })?;
+            .map_err(|err| Error::SerializationError(format!("reading user: {}", err)))?;
+
+        self.check_membership(&client, &gh_token.access_token, &gh_user.login)
+            .await?;
 
         let user = gh_user.to_user();
 
-        let token = match self.repo.save(user.clone()).await {
+        let token = match self.repo.save(user.clone(), client_info).await {
             Ok(token) => token,
             Err(err) => return Err(Error::ConnectionError(err.to_string())),
         };
@@ -163,6 +256,7 @@ impl Authenticator for GithubAuthenticator {
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::auth::MockRepo;
 
     #[tokio::test]
     async fn test_start_login() {
@@ -175,11 +269,16 @@ mod test {
         )
         .unwrap();
 
-        let result = authenticator.start_login().await;
+        let login = LoginState {
+            state: "test_state".to_string(),
+            verifier: "dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjXk".to_string(),
+        };
+
+        let result = authenticator.start_login(&login).await;
         assert!(result.is_ok());
         assert_eq!(
             result.unwrap(),
-            "https://github.com/login/oauth/authorize?client_id=test_client_id&scope=read:user,read:org&redirect_uri=website.local/api/auth/login/callback"
+            "https://github.com/login/oauth/authorize?client_id=test_client_id&scope=read:user,read:org&redirect_uri=website.local/api/auth/login/callback&state=test_state&code_challenge=E9Melhoa2OwvFrEMTJguCHaoeK1t8URWbuGJSstw-cM&code_challenge_method=S256"
         );
     }
 
@@ -188,7 +287,7 @@ mod test {
         let mut mock_repo = MockRepo::new();
         mock_repo
             .expect_save()
-            .returning(|_| Ok("test_token".to_string()));
+            .returning(|_, _| Ok("test_token".to_string()));
 
         let repo = Arc::new(mock_repo);
         let (mut server, authenticator) = GithubAuthenticator::new_test(
@@ -202,6 +301,10 @@ mod test {
 
         let m_token = server
             .mock("POST", "/login/oauth/access_token")
+            .match_body(mockito::Matcher::UrlEncoded(
+                "code_verifier".to_string(),
+                "test_verifier".to_string(),
+            ))
             .with_status(200)
             .with_header("content-type", "application/json")
             .with_body(r#"{"access_token": "test_access_token"}"#)
@@ -215,7 +318,9 @@ mod test {
             .create();
 
         let code = "test_code".to_string();
-        let result = authenticator.login(code).await;
+        let result = authenticator
+            .login(code, "test_verifier".to_string(), ClientInfo::default())
+            .await;
         assert!(result.is_ok());
         let session = result.unwrap();
 
@@ -251,7 +356,9 @@ mod test {
             .create();
 
         let code = "invalid_code".to_string();
-        let result = authenticator.login(code).await;
+        let result = authenticator
+            .login(code, "test_verifier".to_string(), ClientInfo::default())
+            .await;
         assert!(result.is_err());
         assert_eq!(
             result.unwrap_err().to_string(),
@@ -260,4 +367,188 @@ mod test {
 
         m_token.assert_async().await;
     }
+
+    async fn login_mocks(server: &mut mockito::ServerGuard) -> (mockito::Mock, mockito::Mock) {
+        let m_token = server
+            .mock("POST", "/login/oauth/access_token")
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"access_token": "test_access_token"}"#)
+            .create_async()
+            .await;
+
+        let m_user = server
+            .mock("GET", "/user")
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"id": 123456, "login": "test_user", "avatar_url": "https://foo.bar", "name": "John Doe"}"#)
+            .create_async()
+            .await;
+
+        (m_token, m_user)
+    }
+
+    #[tokio::test]
+    async fn test_login_org_member() {
+        let mut mock_repo = MockRepo::new();
+        mock_repo
+            .expect_save()
+            .returning(|_, _| Ok("test_token".to_string()));
+
+        let (mut server, authenticator) = GithubAuthenticator::new_test(
+            Arc::new(mock_repo),
+            "test_client_id".to_string(),
+            "test_client_secret".to_string(),
+            "website.local".to_string(),
+        )
+        .await
+        .unwrap();
+        let authenticator = authenticator.with_org("acme".to_string(), "".to_string());
+
+        let _mocks = login_mocks(&mut server).await;
+
+        let m_org = server
+            .mock("GET", "/user/memberships/orgs/acme")
+            .match_header("Authorization", "token test_access_token")
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"state": "active", "role": "member"}"#)
+            .create_async()
+            .await;
+
+        let result = authenticator
+            .login(
+                "test_code".to_string(),
+                "test_verifier".to_string(),
+                ClientInfo::default(),
+            )
+            .await;
+        assert!(result.is_ok());
+        assert_eq!(result.unwrap().token, "test_token");
+
+        m_org.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_login_not_org_member() {
+        let mut mock_repo = MockRepo::new();
+        mock_repo.expect_save().never();
+
+        let (mut server, authenticator) = GithubAuthenticator::new_test(
+            Arc::new(mock_repo),
+            "test_client_id".to_string(),
+            "test_client_secret".to_string(),
+            "website.local".to_string(),
+        )
+        .await
+        .unwrap();
+        let authenticator = authenticator.with_org("acme".to_string(), "".to_string());
+
+        let _mocks = login_mocks(&mut server).await;
+
+        let m_org = server
+            .mock("GET", "/user/memberships/orgs/acme")
+            .with_status(404)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"message": "Not Found"}"#)
+            .create_async()
+            .await;
+
+        let result = authenticator
+            .login(
+                "test_code".to_string(),
+                "test_verifier".to_string(),
+                ClientInfo::default(),
+            )
+            .await;
+        assert!(result.is_err());
+        assert_eq!(
+            result.err().unwrap().to_string(),
+            "permission denied: test_user is not a member of acme"
+        );
+
+        m_org.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_login_pending_org_member() {
+        let mut mock_repo = MockRepo::new();
+        mock_repo.expect_save().never();
+
+        let (mut server, authenticator) = GithubAuthenticator::new_test(
+            Arc::new(mock_repo),
+            "test_client_id".to_string(),
+            "test_client_secret".to_string(),
+            "website.local".to_string(),
+        )
+        .await
+        .unwrap();
+        let authenticator = authenticator.with_org("acme".to_string(), "".to_string());
+
+        let _mocks = login_mocks(&mut server).await;
+
+        let _m_org = server
+            .mock("GET", "/user/memberships/orgs/acme")
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"state": "pending", "role": "member"}"#)
+            .create_async()
+            .await;
+
+        let result = authenticator
+            .login(
+                "test_code".to_string(),
+                "test_verifier".to_string(),
+                ClientInfo::default(),
+            )
+            .await;
+        assert!(matches!(result, Err(Error::PermissionDenied(_))));
+    }
+
+    #[tokio::test]
+    async fn test_login_not_team_member() {
+        let mut mock_repo = MockRepo::new();
+        mock_repo.expect_save().never();
+
+        let (mut server, authenticator) = GithubAuthenticator::new_test(
+            Arc::new(mock_repo),
+            "test_client_id".to_string(),
+            "test_client_secret".to_string(),
+            "website.local".to_string(),
+        )
+        .await
+        .unwrap();
+        let authenticator = authenticator.with_org("acme".to_string(), "writers".to_string());
+
+        let _mocks = login_mocks(&mut server).await;
+
+        let _m_org = server
+            .mock("GET", "/user/memberships/orgs/acme")
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"state": "active", "role": "member"}"#)
+            .create_async()
+            .await;
+
+        let m_team = server
+            .mock("GET", "/orgs/acme/teams/writers/memberships/test_user")
+            .with_status(404)
+            .create_async()
+            .await;
+
+        let result = authenticator
+            .login(
+                "test_code".to_string(),
+                "test_verifier".to_string(),
+                ClientInfo::default(),
+            )
+            .await;
+        assert!(result.is_err());
+        assert_eq!(
+            result.err().unwrap().to_string(),
+            "permission denied: test_user is not a member of acme/writers"
+        );
+
+        m_team.assert_async().await;
+    }
 }
@@ -0,0 +1,270 @@
+use super::{Authenticator, ClientInfo, LoginState, Repo, Session, User};
+use crate::errors::Error;
+use async_trait::async_trait;
+use serde::Deserialize;
+use std::sync::Arc;
+
+pub struct GitlabAuthenticator {
+    name: String,
+    client_id: String,
+    client_secret: String,
+    url: String,
+    redirect_uri: String,
+    repo: Arc<dyn Repo>,
+}
+
+impl GitlabAuthenticator {
+    /// `url` is the address of the GitLab instance, e.g. https://gitlab.com.
+    /// Logins are prefixed with `name`, which is also the provider name in the login routes.
+    pub fn new(
+        repo: Arc<dyn Repo>,
+        name: String,
+        url: String,
+        client_id: String,
+        client_secret: String,
+        base_url: String,
+    ) -> Result<Self, Error> {
+        if client_id.is_empty() {
+            return Err(Error::InitializationError("Client ID is empty".to_string()));
+        }
+
+        if client_secret.is_empty() {
+            return Err(Error::InitializationError(
+                "Client Secret is empty".to_string(),
+            ));
+        }
+
+        let redirect_uri = format!("{}/api/auth/login/{}/callback", base_url, name);
+
+        Ok(GitlabAuthenticator {
+            name,
+            client_id,
+            client_secret,
+            url: url.trim_end_matches('/').to_string(),
+            redirect_uri,
+            repo,
+        })
+    }
+}
+
+#[derive(Deserialize)]
+struct GitlabAccessToken {
+    #[serde(default)]
+    access_token: String,
+    #[serde(default)]
+    error: String,
+    #[serde(default)]
+    error_description: String,
+}
+
+#[derive(Deserialize)]
+struct GitlabUser {
+    id: u64,
+    name: String,
+    #[serde(default)]
+    avatar_url: Option<String>,
+}
+
+#[async_trait]
+impl Authenticator for GitlabAuthenticator {
+    async fn start_login(&self, login: &LoginState) -> Result<String, Error> {
+        let url = format!(
+            "{}/oauth/authorize?client_id={}&redirect_uri={}&response_type=code&scope=read_user&state={}&code_challenge={}&code_challenge_method=S256",
+            self.url,
+            self.client_id,
+            self.redirect_uri,
+            login.state,
+            login.challenge(),
+        );
+
+        Ok(url)
+    }
+
+    async fn login(
+        &self,
+        code: String,
+        verifier: String,
+        client_info: ClientInfo,
+    ) -> Result<Session, Error> {
+        let client = reqwest::Client::new();
+        let params = [
+            ("client_id", self.client_id.as_str()),
+            ("client_secret", self.client_secret.as_str()),
+            ("code", code.as_str()),
+            ("grant_type", "authorization_code"),
+            ("redirect_uri", self.redirect_uri.as_str()),
+            ("code_verifier", verifier.as_str()),
+        ];
+
+        let token: GitlabAccessToken = client
+            .post(format!("{}/oauth/token", self.url))
+            .header("Accept", "application/json")
+            .form(&params)
+            .send()
+            .await
+            .map_err(|err| Error::ConnectionError(format!("getting access_token: {}", err)))?
+            .json()
+            .await
+            .map_err(|err| Error::SerializationError(format!("reading access_token: {}", err)))?;
+
+        if !token.error.is_empty() {
+            return Err(Error::PermissionDenied(format!(
+                "{} ({})",
+                token.error_description, token.error
+            )));
+        }
+
+        if token.access_token.is_empty() {
+            return Err(Error::PermissionDenied("access token is empty".to_string()));
+        }
+
+        let gl_user: GitlabUser = client
+            .get(format!("{}/api/v4/user", self.url))
+            .bearer_auth(&token.access_token)
+            .send()
+            .await
+            .map_err(|err| Error::ConnectionError(format!("getting user: {}", err)))?
+            .json()
+            .await
+            .map_err(|err| Error::SerializationError(format!("reading user: {}", err)))?;
+
+        let user = User {
+            id: gl_user.id,
+            name: gl_user.name,
+            avatar_url: gl_user.avatar_url.unwrap_or_default(),
+            // usernames can be changed and then taken by someone else, the id cannot
+            login: format!("{}:{}", self.name, gl_user.id),
+        };
+
+        let token = self.repo.save(user.clone(), client_info).await?;
+
+        Ok(Session { user, token })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::auth::MockRepo;
+
+    async fn authenticator(repo: MockRepo) -> (mockito::ServerGuard, GitlabAuthenticator) {
+        let server = mockito::Server::new_async().await;
+
+        let auth = GitlabAuthenticator::new(
+            Arc::new(repo),
+            "gitlab".to_string(),
+            server.url(),
+            "test_client_id".to_string(),
+            "test_client_secret".to_string(),
+            "website.local".to_string(),
+        )
+        .unwrap();
+
+        (server, auth)
+    }
+
+    #[tokio::test]
+    async fn test_start_login() {
+        let (server, authenticator) = authenticator(MockRepo::new()).await;
+
+        let login = LoginState {
+            state: "test_state".to_string(),
+            verifier: "dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjXk".to_string(),
+        };
+
+        let result = authenticator.start_login(&login).await;
+        assert_eq!(
+            result.unwrap(),
+            format!(
+                "{}/oauth/authorize?client_id=test_client_id&redirect_uri=website.local/api/auth/login/gitlab/callback&response_type=code&scope=read_user&state=test_state&code_challenge=E9Melhoa2OwvFrEMTJguCHaoeK1t8URWbuGJSstw-cM&code_challenge_method=S256",
+                server.url()
+            )
+        );
+    }
+
+    #[tokio::test]
+    async fn test_login_success() {
+        let mut mock_repo = MockRepo::new();
+        mock_repo
+            .expect_save()
+            .withf(|user, _| user.login == "gitlab:42")
+            .returning(|_, _| Ok("test_token".to_string()));
+
+        let (mut server, authenticator) = authenticator(mock_repo).await;
+
+        let m_token = server
+            .mock("POST", "/oauth/token")
+            .match_body(mockito::Matcher::AllOf(vec![
+                mockito::Matcher::UrlEncoded("code".to_string(), "test_code".to_string()),
+                mockito::Matcher::UrlEncoded(
+                    "code_verifier".to_string(),
+                    "test_verifier".to_string(),
+                ),
+                mockito::Matcher::UrlEncoded(
+                    "grant_type".to_string(),
+                    "authorization_code".to_string(),
+                ),
+            ]))
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"access_token": "test_access_token", "token_type": "Bearer"}"#)
+            .create_async()
+            .await;
+
+        let m_user = server
+            .mock("GET", "/api/v4/user")
+            .match_header("Authorization", "Bearer test_access_token")
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"id": 42, "username": "test_user", "name": "John Doe", "avatar_url": "https://foo.bar"}"#)
+            .create_async()
+            .await;
+
+        let result = authenticator
+            .login(
+                "test_code".to_string(),
+                "test_verifier".to_string(),
+                ClientInfo::default(),
+            )
+            .await;
+        assert!(result.is_ok());
+        let session = result.unwrap();
+
+        assert_eq!(session.token, "test_token");
+        assert_eq!(session.user.id, 42);
+        assert_eq!(session.user.login, "gitlab:42");
+        assert_eq!(session.user.name, "John Doe");
+        assert_eq!(session.user.avatar_url, "https://foo.bar");
+
+        m_token.assert_async().await;
+        m_user.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_login_invalid_code() {
+        let (mut server, authenticator) = authenticator(MockRepo::new()).await;
+
+        let m_token = server
+            .mock("POST", "/oauth/token")
+            .with_status(400)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"error": "invalid_grant", "error_description": "The provided authorization grant is invalid"}"#)
+            .create_async()
+            .await;
+
+        let result = authenticator
+            .login(
+                "invalid_code".to_string(),
+                "test_verifier".to_string(),
+                ClientInfo::default(),
+            )
+            .await;
+        assert!(result.is_err());
+        assert_eq!(
+            result.err().unwrap().to_string(),
+            "permission denied: The provided authorization grant is invalid (invalid_grant)"
+        );
+
+        m_token.assert_async().await;
+    }
+}
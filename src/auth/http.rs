@@ -1,111 +1,931 @@
-use super::{Authenticator, SessionManager, User};
+use super::local::{self, LocalAuthenticator};
+use super::tokens::{ApiToken, ApiTokens, Scope};
+use super::{
+    ClientInfo, Directory, LoginState, Providers, Repo, Session, SessionInfo, SessionManager, User,
+};
 use crate::errors::Error;
-use actix_web::{App, HttpRequest, HttpResponse, HttpServer, Responder, cookie::Cookie, get, web};
-use serde::Deserialize;
-use tracing_actix_web::TracingLogger;
+use crate::web::csrf;
+use actix_web::cookie::{Cookie, CookieBuilder, SameSite, time::Duration};
+use actix_web::dev::HttpServiceFactory;
+use actix_web::http::header;
+use actix_web::middleware::from_fn;
+use actix_web::{
+    App, HttpRequest, HttpResponse, HttpServer, Responder, delete, get, post, put, web,
+};
+use serde::{Deserialize, Serialize};
 use std::sync::Arc;
+use tracing_actix_web::TracingLogger;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+/// Attributes of the session cookie.
+#[derive(Clone, Debug)]
+pub struct CookieConfig {
+    pub name: String,
+    /// Defaults to the host that set the cookie.
+    pub domain: Option<String>,
+    pub secure: bool,
+    pub http_only: bool,
+    pub same_site: SameSite,
+    /// Seconds the cookie is kept, the maximum lifetime of a session.
+    pub max_age: i64,
+}
+
+impl CookieConfig {
+    pub fn new(name: String) -> Self {
+        CookieConfig {
+            name,
+            domain: None,
+            secure: false,
+            http_only: true,
+            same_site: SameSite::Lax,
+            max_age: 86400,
+        }
+    }
+
+    /// Refuses settings that would leak the cookie, or that browsers would reject.
+    pub fn check(&self, base_url: &str) -> Result<(), Error> {
+        if base_url.starts_with("https://") && !self.secure {
+            return Err(Error::InitializationError(
+                "cookie must be secure when base_url is https".to_string(),
+            ));
+        }
+
+        if self.same_site == SameSite::None && !self.secure {
+            return Err(Error::InitializationError(
+                "cookie with SameSite=None must be secure".to_string(),
+            ));
+        }
+
+        Ok(())
+    }
+
+    fn build(&self, name: String, value: String) -> CookieBuilder<'static> {
+        let mut builder = Cookie::build(name, value)
+            .secure(self.secure)
+            .http_only(self.http_only);
+
+        if let Some(domain) = &self.domain {
+            builder = builder.domain(domain.clone());
+        }
+
+        builder
+    }
+
+    fn session(&self, token: String) -> Cookie<'static> {
+        self.build(self.name.clone(), token)
+            .path("/")
+            .same_site(self.same_site)
+            .max_age(Duration::seconds(self.max_age))
+            .finish()
+    }
+
+    /// Makes the browser drop the session cookie.
+    fn session_removal(&self) -> Cookie<'static> {
+        let mut cookie = self.session("".to_string());
+        cookie.make_removal();
+        cookie
+    }
+
+    /// Cookie holding the oauth state between the redirect and the callback.
+    fn state_name(&self) -> String {
+        format!("{}_state", self.name)
+    }
+
+    fn state(&self, value: String) -> Cookie<'static> {
+        self.build(self.state_name(), value)
+            .path("/api/auth/login")
+            .http_only(true)
+            // the callback is a cross-site navigation from the provider
+            .same_site(SameSite::Lax)
+            .max_age(Duration::minutes(10))
+            .finish()
+    }
+
+    fn state_removal(&self) -> Cookie<'static> {
+        let mut cookie = self.state("".to_string());
+        cookie.make_removal();
+        cookie
+    }
+}
+
+/// Parses a SameSite attribute from the configuration.
+pub fn same_site(value: &str) -> Result<SameSite, Error> {
+    match value.to_lowercase().as_str() {
+        "strict" => Ok(SameSite::Strict),
+        "lax" => Ok(SameSite::Lax),
+        "none" => Ok(SameSite::None),
+        _ => Err(Error::InitializationError(format!(
+            "invalid SameSite value {}",
+            value
+        ))),
+    }
+}
 
 struct State {
     sessions: Arc<dyn SessionManager>,
-    auth: Arc<dyn Authenticator>,
+    providers: Arc<Providers>,
+    repo: Arc<dyn Repo>,
+    directory: Arc<dyn Directory>,
+    local: Option<Arc<LocalAuthenticator>>,
+    tokens: Option<Arc<ApiTokens>>,
     base_url: String,
-    cookie_name: String,
+    cookie: CookieConfig,
+}
+
+impl State {
+    fn default_provider(&self) -> Result<String, Error> {
+        self.providers
+            .default_name()
+            .map(|name| name.to_string())
+            .ok_or_else(|| Error::NotFound("provider".to_string()))
+    }
+
+    fn local(&self) -> Result<&LocalAuthenticator, Error> {
+        self.local
+            .as_deref()
+            .ok_or_else(|| Error::NotFound(format!("provider {}", local::PROVIDER)))
+    }
+
+    fn tokens(&self) -> Result<&ApiTokens, Error> {
+        self.tokens
+            .as_deref()
+            .ok_or_else(|| Error::NotFound("api tokens".to_string()))
+    }
+}
+
+/// The user a request is made on behalf of.
+pub struct Caller {
+    pub user: User,
+    /// Scopes of the API token the request was made with, `None` for sessions.
+    pub scopes: Option<Vec<Scope>>,
 }
 
+impl Caller {
+    /// Sessions can do anything their user can, API tokens only what their scopes allow.
+    pub fn allows(&self, scope: Scope) -> bool {
+        match &self.scopes {
+            Some(scopes) => scopes.contains(&scope),
+            None => true,
+        }
+    }
+}
+
+/// Loads the caller from an API token in the `Authorization: Bearer` header, or the session cookie.
 pub async fn load_user(
     req: actix_web::HttpRequest,
     sessions: &Arc<dyn SessionManager>,
     cookie_name: &str,
-) -> Result<User, Error> {
+) -> Result<Caller, Error> {
+    if let Some(authorization) = req.headers().get(header::AUTHORIZATION) {
+        let token = authorization
+            .to_str()
+            .ok()
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .ok_or_else(|| Error::PermissionDenied("invalid authorization header".to_string()))?;
+
+        let token = sessions.api_token(token.trim().to_string()).await?;
+
+        return Ok(Caller {
+            user: token.user,
+            scopes: Some(token.scopes),
+        });
+    }
+
     if let Some(cookie) = req.cookie(cookie_name) {
-        match sessions.session(cookie.value().to_string()).await {
-            Ok(user) => return Ok(user),
-            Err(err) => return Err(err),
-        }
+        let user = sessions.session(cookie.value().to_string()).await?;
+
+        return Ok(Caller { user, scopes: None });
     }
 
     Err(Error::PermissionDenied("no session found".to_string()))
 }
 
+/// Address and user agent of the browser, kept with the session it creates.
+fn client_info(req: &HttpRequest) -> ClientInfo {
+    ClientInfo {
+        // honours Forwarded / X-Forwarded-For, only informative as it can be spoofed
+        ip: req
+            .connection_info()
+            .realip_remote_addr()
+            .map(|ip| ip.to_string()),
+        user_agent: req
+            .headers()
+            .get(header::USER_AGENT)
+            .and_then(|ua| ua.to_str().ok())
+            .map(|ua| ua.to_string()),
+    }
+}
+
+/// Records a successful login in the directory, disabled users lose the new session.
+async fn record_login(state: &State, result: Result<Session, Error>) -> Result<Session, Error> {
+    let session = state.sessions.issue(result?).await?;
+
+    match state
+        .directory
+        .users_record_login(session.user.clone())
+        .await
+    {
+        Ok(record) if !record.disabled => Ok(session),
+        Ok(_) => {
+            state.sessions.logout(session.token).await?;
+            Err(Error::PermissionDenied("user disabled".to_string()))
+        }
+        Err(err) => {
+            state.sessions.logout(session.token).await?;
+            Err(err)
+        }
+    }
+}
+
+async fn start_login(state: &State, provider: &str) -> HttpResponse {
+    let auth = match state.providers.get(provider) {
+        Ok(auth) => auth,
+        Err(err) => return err.to_http_response(),
+    };
+
+    let login_state = LoginState::new();
+
+    let url = match auth.start_login(&login_state).await {
+        Ok(url) => url,
+        Err(err) => return err.to_http_response(),
+    };
+
+    let state_cookie = state.cookie.state(login_state.state.clone());
+
+    if let Err(err) = state.repo.login_state_save(login_state).await {
+        return err.to_http_response();
+    }
+
+    HttpResponse::Found()
+        .cookie(state_cookie)
+        .append_header(("Location", url))
+        .finish()
+}
+
 #[get("/api/auth/login")]
 async fn login(state: web::Data<State>) -> impl Responder {
-    match state.auth.start_login().await {
+    match state.default_provider() {
+        Ok(provider) => start_login(&state, &provider).await,
         Err(err) => err.to_http_response(),
-        Ok(url) => HttpResponse::Found()
-            .append_header(("Location", url))
-            .finish(),
     }
 }
 
+#[get("/api/auth/login/{provider}")]
+async fn provider_login(state: web::Data<State>, path: web::Path<(String,)>) -> impl Responder {
+    start_login(&state, &path.into_inner().0).await
+}
+
 #[derive(Deserialize)]
 struct LoginCallback {
     code: String,
+    state: String,
+}
+
+/// Checks that the callback comes from the browser that started the login,
+/// and returns the PKCE verifier of that login.
+async fn verify_login_state(
+    state: &State,
+    req: &HttpRequest,
+    query: &LoginCallback,
+) -> Result<String, Error> {
+    match req.cookie(&state.cookie.state_name()) {
+        Some(cookie) if cookie.value() == query.state => (),
+        _ => return Err(Error::PermissionDenied("login state mismatch".to_string())),
+    }
+
+    state.repo.login_state_take(query.state.clone()).await
+}
+
+async fn finish_login(
+    state: &State,
+    req: HttpRequest,
+    query: LoginCallback,
+    provider: &str,
+) -> HttpResponse {
+    let auth = match state.providers.get(provider) {
+        Ok(auth) => auth,
+        Err(err) => return err.to_http_response(),
+    };
+
+    let verifier = match verify_login_state(state, &req, &query).await {
+        Ok(verifier) => verifier,
+        Err(err) => return err.to_http_response(),
+    };
+
+    let state_cookie = state.cookie.state_removal();
+
+    let result = auth.login(query.code, verifier, client_info(&req)).await;
+
+    match record_login(state, result).await {
+        Err(err) => err.to_http_response(),
+        Ok(session) => HttpResponse::Found()
+            .cookie(state_cookie)
+            .cookie(state.cookie.session(session.token))
+            .append_header(("Location", state.base_url.clone()))
+            .finish(),
+    }
 }
 
 #[get("/api/auth/login/callback")]
 async fn login_callback(
     state: web::Data<State>,
+    req: HttpRequest,
     query: web::Query<LoginCallback>,
 ) -> impl Responder {
-    match state.auth.login(query.code.clone()).await {
+    match state.default_provider() {
+        Ok(provider) => finish_login(&state, req, query.into_inner(), &provider).await,
+        Err(err) => err.to_http_response(),
+    }
+}
+
+#[get("/api/auth/login/{provider}/callback")]
+async fn provider_login_callback(
+    state: web::Data<State>,
+    req: HttpRequest,
+    path: web::Path<(String,)>,
+    query: web::Query<LoginCallback>,
+) -> impl Responder {
+    finish_login(&state, req, query.into_inner(), &path.into_inner().0).await
+}
+
+#[utoipa::path(get,
+    path = "/api/auth/providers",
+    description = "List login providers, the first one is the default",
+    tag = "auth",
+    responses(
+        (status = 200, description = "Provider names, login with /api/auth/login/{provider}", body = Vec<String>)
+    ),
+)]
+#[get("/api/auth/providers")]
+pub async fn list_providers(state: web::Data<State>) -> impl Responder {
+    HttpResponse::Ok().json(state.providers.names())
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct LocalLoginRequest {
+    login: String,
+    password: String,
+}
+
+#[utoipa::path(post,
+    path = "/api/auth/local/login",
+    description = "Log in with a local account, sets the session cookie",
+    tag = "auth",
+    responses(
+        (status = 200, description = "Logged in user", body = User),
+        (status = 403, description = "Invalid login or password"),
+    ),
+    request_body(content = LocalLoginRequest, content_type = "application/json")
+)]
+#[post("/api/auth/local/login")]
+pub async fn local_login(
+    state: web::Data<State>,
+    req: HttpRequest,
+    body: web::Json<LocalLoginRequest>,
+) -> impl Responder {
+    let body = body.into_inner();
+
+    let result = match state.local() {
+        Ok(local) => {
+            local
+                .authenticate(body.login, body.password, client_info(&req))
+                .await
+        }
+        Err(err) => Err(err),
+    };
+
+    match record_login(&state, result).await {
+        Ok(session) => HttpResponse::Ok()
+            .cookie(state.cookie.session(session.token))
+            .json(session.user),
+        Err(err) => err.to_http_response(),
+    }
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct RegisterRequest {
+    /// Invite token, the invite decides the login.
+    invite: Option<String>,
+    /// Only used when registration is open to everyone.
+    login: Option<String>,
+    name: String,
+    password: String,
+}
+
+#[utoipa::path(post,
+    path = "/api/auth/local/register",
+    description = "Create a local account and log in with it",
+    tag = "auth",
+    responses(
+        (status = 201, description = "Registered user", body = User),
+        (status = 400, description = "Login already in use, or invalid password"),
+        (status = 403, description = "Missing or invalid invite"),
+    ),
+    request_body(content = RegisterRequest, content_type = "application/json")
+)]
+#[post("/api/auth/local/register")]
+pub async fn local_register(
+    state: web::Data<State>,
+    req: HttpRequest,
+    body: web::Json<RegisterRequest>,
+) -> impl Responder {
+    let body = body.into_inner();
+
+    let result = match state.local() {
+        Ok(local) => {
+            local
+                .register(
+                    body.invite,
+                    body.login,
+                    body.name,
+                    body.password,
+                    client_info(&req),
+                )
+                .await
+        }
+        Err(err) => Err(err),
+    };
+
+    match record_login(&state, result).await {
+        Ok(session) => HttpResponse::Created()
+            .cookie(state.cookie.session(session.token))
+            .json(session.user),
+        Err(err) => err.to_http_response(),
+    }
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct ChangePasswordRequest {
+    current_password: String,
+    new_password: String,
+}
+
+#[utoipa::path(put,
+    path = "/api/auth/local/password",
+    description = "Change the password of the logged in local account",
+    tag = "auth",
+    responses(
+        (status = 204, description = "Password changed, other sessions are logged out"),
+        (status = 403, description = "Not a local account, or wrong current password"),
+    ),
+    request_body(content = ChangePasswordRequest, content_type = "application/json")
+)]
+#[put("/api/auth/local/password")]
+pub async fn local_change_password(
+    state: web::Data<State>,
+    req: HttpRequest,
+    body: web::Json<ChangePasswordRequest>,
+) -> impl Responder {
+    let local = match state.local() {
+        Ok(local) => local,
+        Err(err) => return err.to_http_response(),
+    };
+
+    let (user, current) = match current_session(&state, req).await {
+        Ok(session) => session,
+        Err(err) => return err.to_http_response(),
+    };
+
+    let account = match user.login.strip_prefix(&format!("{}:", local::PROVIDER)) {
+        Some(account) => account.to_string(),
+        None => {
+            return Error::PermissionDenied("not a local account".to_string()).to_http_response();
+        }
+    };
+
+    let body = body.into_inner();
+
+    if let Err(err) = local
+        .change_password(account, body.current_password, body.new_password)
+        .await
+    {
+        return err.to_http_response();
+    }
+
+    // whoever knew the old password is logged out, the session making the change is kept
+    match state.sessions.revoke_all(user.login, Some(current)).await {
+        Ok(_) => HttpResponse::NoContent().finish(),
+        Err(err) => err.to_http_response(),
+    }
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct ResetPasswordRequest {
+    token: String,
+    password: String,
+}
+
+#[utoipa::path(post,
+    path = "/api/auth/local/reset",
+    description = "Set a new password with a reset token",
+    tag = "auth",
+    responses(
+        (status = 204, description = "Password changed, every session is logged out"),
+        (status = 403, description = "Invalid or expired token"),
+    ),
+    request_body(content = ResetPasswordRequest, content_type = "application/json")
+)]
+#[post("/api/auth/local/reset")]
+pub async fn local_reset_password(
+    state: web::Data<State>,
+    body: web::Json<ResetPasswordRequest>,
+) -> impl Responder {
+    let body = body.into_inner();
+
+    let result = match state.local() {
+        Ok(local) => local.reset_password(body.token, body.password).await,
+        Err(err) => Err(err),
+    };
+
+    let user_login = match result {
+        Ok(account) => format!("{}:{}", local::PROVIDER, account),
+        Err(err) => return err.to_http_response(),
+    };
+
+    // a reset usually follows a lost or leaked password, every session is ended
+    match state.sessions.revoke_all(user_login, None).await {
+        Ok(_) => HttpResponse::NoContent().finish(),
+        Err(err) => err.to_http_response(),
+    }
+}
+
+/// Loads the session user, along with the id of their current session.
+///
+/// Only the session cookie is accepted, API tokens cannot manage the account they belong to.
+async fn current_session(state: &State, req: HttpRequest) -> Result<(User, String), Error> {
+    let token = match req.cookie(&state.cookie.name) {
+        Some(cookie) => cookie.value().to_string(),
+        None => return Err(Error::PermissionDenied("no session found".to_string())),
+    };
+
+    let id = state.sessions.id(&token)?;
+    let user = state.sessions.session(token).await?;
+
+    Ok((user, id))
+}
+
+#[derive(Serialize)]
+struct ListedSession {
+    #[serde(flatten)]
+    session: SessionInfo,
+    current: bool,
+}
+
+// Same as ListedSession, with types utoipa can describe.
+#[allow(dead_code)]
+#[derive(ToSchema)]
+pub struct SessionResponse {
+    id: String,
+    created_at: String,
+    last_seen_at: String,
+    ip: Option<String>,
+    user_agent: Option<String>,
+    /// Whether this is the session making the request.
+    current: bool,
+}
+
+#[utoipa::path(get,
+    path = "/api/auth/sessions",
+    description = "List the active sessions of the current user, most recently used first",
+    tag = "auth",
+    responses(
+        (status = 200, description = "Active sessions", body = Vec<SessionResponse>)
+    ),
+)]
+#[get("/api/auth/sessions")]
+pub async fn list_sessions(state: web::Data<State>, req: HttpRequest) -> impl Responder {
+    let (user, current) = match current_session(&state, req).await {
+        Ok(session) => session,
+        Err(err) => return err.to_http_response(),
+    };
+
+    match state.sessions.sessions(user.login).await {
+        Ok(sessions) => HttpResponse::Ok().json(
+            sessions
+                .into_iter()
+                .map(|session| ListedSession {
+                    current: session.id == current,
+                    session,
+                })
+                .collect::<Vec<_>>(),
+        ),
+        Err(err) => err.to_http_response(),
+    }
+}
+
+#[utoipa::path(delete,
+    path = "/api/auth/sessions/{id}",
+    description = "Log out one of the sessions of the current user",
+    tag = "auth",
+    responses(
+        (status = 204, description = "Session revoked"),
+    ),
+    params(
+        ("id" = String, Path, description = "Session id"),
+    ),
+)]
+#[delete("/api/auth/sessions/{id}")]
+pub async fn revoke_session(
+    state: web::Data<State>,
+    req: HttpRequest,
+    path: web::Path<(String,)>,
+) -> impl Responder {
+    let (user, _) = match current_session(&state, req).await {
+        Ok(session) => session,
+        Err(err) => return err.to_http_response(),
+    };
+
+    match state.sessions.revoke(user.login, path.into_inner().0).await {
+        Ok(_) => HttpResponse::NoContent().finish(),
+        Err(err) => err.to_http_response(),
+    }
+}
+
+#[utoipa::path(delete,
+    path = "/api/auth/sessions",
+    description = "Log out every other session of the current user",
+    tag = "auth",
+    responses(
+        (status = 204, description = "Other sessions revoked"),
+    ),
+)]
+#[delete("/api/auth/sessions")]
+pub async fn revoke_other_sessions(state: web::Data<State>, req: HttpRequest) -> impl Responder {
+    let (user, current) = match current_session(&state, req).await {
+        Ok(session) => session,
+        Err(err) => return err.to_http_response(),
+    };
+
+    match state.sessions.revoke_all(user.login, Some(current)).await {
+        Ok(_) => HttpResponse::NoContent().finish(),
+        Err(err) => err.to_http_response(),
+    }
+}
+
+// Same as ApiToken, with types utoipa can describe.
+#[allow(dead_code)]
+#[derive(ToSchema)]
+pub struct ApiTokenResponse {
+    id: String,
+    name: String,
+    scopes: Vec<Scope>,
+    created_at: String,
+    expires_at: String,
+}
+
+#[utoipa::path(get,
+    path = "/api/auth/tokens",
+    description = "List the API tokens of the current user, including expired ones",
+    tag = "auth",
+    responses(
+        (status = 200, description = "API tokens", body = Vec<ApiTokenResponse>)
+    ),
+)]
+#[get("/api/auth/tokens")]
+pub async fn list_tokens(state: web::Data<State>, req: HttpRequest) -> impl Responder {
+    let tokens = match state.tokens() {
+        Ok(tokens) => tokens,
+        Err(err) => return err.to_http_response(),
+    };
+
+    let (user, _) = match current_session(&state, req).await {
+        Ok(session) => session,
+        Err(err) => return err.to_http_response(),
+    };
+
+    match tokens.list(user.login).await {
+        Ok(tokens) => HttpResponse::Ok().json(tokens),
+        Err(err) => err.to_http_response(),
+    }
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct CreateTokenRequest {
+    /// What the token is for, such as the script using it.
+    name: String,
+    scopes: Vec<Scope>,
+    /// Defaults to 30 days, at most 365.
+    expires_in_days: Option<i64>,
+}
+
+#[derive(Serialize)]
+struct CreatedToken {
+    #[serde(flatten)]
+    token: ApiToken,
+    secret: String,
+}
+
+// Same as CreatedToken, with types utoipa can describe.
+#[allow(dead_code)]
+#[derive(ToSchema)]
+pub struct CreatedTokenResponse {
+    id: String,
+    name: String,
+    scopes: Vec<Scope>,
+    created_at: String,
+    expires_at: String,
+    /// To send as `Authorization: Bearer <secret>`, it cannot be retrieved again.
+    secret: String,
+}
+
+#[utoipa::path(post,
+    path = "/api/auth/tokens",
+    description = "Create an API token acting as the current user",
+    tag = "auth",
+    responses(
+        (status = 201, description = "API token created", body = CreatedTokenResponse),
+        (status = 400, description = "Missing name or scopes, or invalid expiry"),
+    ),
+    request_body(content = CreateTokenRequest, content_type = "application/json")
+)]
+#[post("/api/auth/tokens")]
+pub async fn create_token(
+    state: web::Data<State>,
+    req: HttpRequest,
+    body: web::Json<CreateTokenRequest>,
+) -> impl Responder {
+    let tokens = match state.tokens() {
+        Ok(tokens) => tokens,
+        Err(err) => return err.to_http_response(),
+    };
+
+    let (user, _) = match current_session(&state, req).await {
+        Ok(session) => session,
+        Err(err) => return err.to_http_response(),
+    };
+
+    let body = body.into_inner();
+
+    match tokens
+        .create(
+            user,
+            body.name,
+            body.scopes,
+            body.expires_in_days.map(chrono::Duration::days),
+        )
+        .await
+    {
+        Ok((token, secret)) => HttpResponse::Created().json(CreatedToken { token, secret }),
+        Err(err) => err.to_http_response(),
+    }
+}
+
+#[utoipa::path(delete,
+    path = "/api/auth/tokens/{id}",
+    description = "Revoke an API token of the current user",
+    tag = "auth",
+    responses(
+        (status = 204, description = "API token revoked"),
+    ),
+    params(
+        ("id" = String, Path, description = "API token id"),
+    ),
+)]
+#[delete("/api/auth/tokens/{id}")]
+pub async fn revoke_token(
+    state: web::Data<State>,
+    req: HttpRequest,
+    path: web::Path<(Uuid,)>,
+) -> impl Responder {
+    let tokens = match state.tokens() {
+        Ok(tokens) => tokens,
+        Err(err) => return err.to_http_response(),
+    };
+
+    let (user, _) = match current_session(&state, req).await {
+        Ok(session) => session,
+        Err(err) => return err.to_http_response(),
+    };
+
+    match tokens.revoke(user.login, path.into_inner().0).await {
+        Ok(_) => HttpResponse::NoContent().finish(),
         Err(err) => err.to_http_response(),
-        Ok(session) => HttpResponse::Found()
-            .cookie(
-                Cookie::build(state.cookie_name.clone(), session.token)
-                .path("/")
-                // the following should be uncommented for non 127.0.0.1 domains
-                // .domain(state.base_url.clone())
-                // .secure(true)
-                // .http_only(true)
-                .finish(),
-            )
-            .append_header(("Location", state.base_url.clone()))
-            .finish(),
     }
 }
 
 #[get("/api/auth/logout")]
 async fn logout(state: web::Data<State>, req: HttpRequest) -> impl Responder {
-    if let Some(cookie) = req.cookie(&state.cookie_name) {
+    if let Some(cookie) = req.cookie(&state.cookie.name) {
         match state.sessions.logout(cookie.value().to_string()).await {
             Ok(_) => HttpResponse::Found()
+                .cookie(state.cookie.session_removal())
                 .append_header(("Location", state.base_url.clone()))
                 .finish(),
-            Err(err) => err.to_http_response(),
+            Err(err) => {
+                // a cookie that cannot be logged out, e.g. signed with a retired
+                // key, is of no use to the browser either
+                let mut resp = err.to_http_response();
+                let _ = resp.add_cookie(&state.cookie.session_removal());
+                resp
+            }
         }
     } else {
-        HttpResponse::Ok().finish()
+        HttpResponse::Ok()
+            .cookie(state.cookie.session_removal())
+            .finish()
     }
 }
 
-#[utoipa::path(get, 
-    path = "/api/auth/me", 
-    description = "Get current user status",
+#[derive(Serialize)]
+struct MeResponse {
+    #[serde(flatten)]
+    user: User,
+    csrf_token: String,
+}
+
+// Same as MeResponse, with types utoipa can describe.
+#[allow(dead_code)]
+#[derive(ToSchema)]
+pub struct CurrentUserResponse {
+    id: u64,
+    name: String,
+    avatar_url: String,
+    login: String,
+    /// To send in the `X-CSRF-Token` header of state-changing requests to the admin API.
+    csrf_token: String,
+}
+
+#[utoipa::path(get,
+    path = "/api/auth/me",
+    description = "Get current user status, renewing the session cookie when it is due",
     tag = "auth",
     responses(
-        (status = 200, description = "Current logged in user", body = User)
+        (status = 200, description = "Current logged in user", body = CurrentUserResponse)
     ),
 )]
 #[get("/api/auth/me")]
 pub async fn me(state: web::Data<State>, req: HttpRequest) -> impl Responder {
-    match load_user(req, &state.sessions, state.cookie_name.as_str()).await {
-        Ok(user) => HttpResponse::Ok().json(user),
+    let token = match req.cookie(&state.cookie.name) {
+        Some(cookie) => cookie.value().to_string(),
+        None => return Error::PermissionDenied("no session found".to_string()).to_http_response(),
+    };
+
+    let renewed = match state.sessions.renew(token.clone()).await {
+        Ok(renewed) => renewed,
+        Err(err) => return err.to_http_response(),
+    };
+    let token = renewed.clone().unwrap_or(token);
+
+    match state.sessions.session(token.clone()).await {
+        Ok(user) => {
+            let mut response = HttpResponse::Ok();
+            if renewed.is_some() {
+                response.cookie(state.cookie.session(token.clone()));
+            }
+
+            response.json(MeResponse {
+                user,
+                csrf_token: match state.sessions.csrf_token(&token) {
+                    Ok(csrf_token) => csrf_token,
+                    Err(err) => return err.to_http_response(),
+                },
+            })
+        }
         Err(err) => err.to_http_response(),
     }
 }
 
+/// Routes changing state with the session cookie, which browsers also send along with requests
+/// from other sites, so that these need the CSRF token as on the admin service.
+fn cookie_authenticated(
+    cookie_name: String,
+    sessions: Arc<dyn SessionManager>,
+) -> impl HttpServiceFactory {
+    web::scope("")
+        .wrap(from_fn(move |req, next| {
+            csrf::protect(cookie_name.clone(), sessions.clone(), req, next)
+        }))
+        .service(local_change_password)
+        .service(list_sessions)
+        .service(revoke_session)
+        .service(revoke_other_sessions)
+        .service(list_tokens)
+        .service(create_token)
+        .service(revoke_token)
+}
+
+#[allow(clippy::too_many_arguments)]
 pub async fn server(
     sessions: Arc<dyn SessionManager>,
-    auth: Arc<dyn Authenticator>,
+    providers: Providers,
+    repo: Arc<dyn Repo>,
+    directory: Arc<dyn Directory>,
+    local: Option<Arc<LocalAuthenticator>>,
+    tokens: Option<Arc<ApiTokens>>,
     base_url: String,
-    cookie_name: String,
+    cookie: CookieConfig,
     listen_addr: String,
 ) -> Result<(), std::io::Error> {
     let data = web::Data::new(State {
         sessions,
-        auth,
+        providers: Arc::new(providers),
+        repo,
+        directory,
+        local,
+        tokens,
         base_url,
-        cookie_name,
+        cookie,
     });
 
     HttpServer::new(move || {
@@ -113,11 +933,944 @@ pub async fn server(
             .app_data(data.clone())
             .wrap(TracingLogger::default())
             .service(login)
+            // must come before provider_login, which would otherwise match it
             .service(login_callback)
+            .service(provider_login)
+            .service(provider_login_callback)
+            .service(list_providers)
+            .service(local_login)
+            .service(local_register)
+            .service(local_reset_password)
             .service(logout)
             .service(me)
+            // matches every path, so it must come last
+            .service(cookie_authenticated(
+                data.cookie.name.clone(),
+                data.sessions.clone(),
+            ))
     })
     .bind(listen_addr)?
     .run()
     .await
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::auth::local::{Account, MockAccountRepo};
+    use crate::auth::tokens::MockTokenRepo;
+    use crate::auth::{
+        MockAuthenticator, MockDirectory, MockRepo, MockSessionManager, UserRecord, session_id,
+    };
+    use actix_web::{http::StatusCode, test};
+    use argon2::Argon2;
+    use argon2::password_hash::{PasswordHasher, SaltString};
+    use chrono::Utc;
+
+    /// Mock of a session manager handing out the tokens of the repo, as the default one does.
+    fn session_manager() -> MockSessionManager {
+        let mut sessions = MockSessionManager::new();
+        sessions.expect_issue().returning(Ok);
+        sessions.expect_renew().returning(|_| Ok(None));
+        sessions
+            .expect_id()
+            .returning(|token| Ok(session_id(token)));
+        sessions
+            .expect_csrf_token()
+            .returning(|token| Ok(csrf::token(token)));
+        sessions
+    }
+
+    fn directory(disabled: bool) -> MockDirectory {
+        let mut directory = MockDirectory::new();
+        directory
+            .expect_users_record_login()
+            .returning(move |user| {
+                Ok(UserRecord {
+                    id: user.id,
+                    login: user.login,
+                    name: user.name,
+                    avatar_url: user.avatar_url,
+                    first_login_at: Utc::now(),
+                    last_login_at: Utc::now(),
+                    disabled,
+                })
+            });
+        directory
+    }
+
+    /// Mocks behind the handlers under test, each overridable before `build`.
+    struct TestState {
+        sessions: MockSessionManager,
+        providers: Providers,
+        repo: MockRepo,
+        directory: MockDirectory,
+        accounts: Option<MockAccountRepo>,
+        tokens: Option<MockTokenRepo>,
+    }
+
+    fn state() -> TestState {
+        TestState {
+            sessions: session_manager(),
+            providers: Providers::new(),
+            repo: MockRepo::new(),
+            directory: directory(false),
+            accounts: None,
+            tokens: None,
+        }
+    }
+
+    impl TestState {
+        fn sessions(mut self, sessions: MockSessionManager) -> Self {
+            self.sessions = sessions;
+            self
+        }
+
+        fn provider(mut self, name: &str, auth: MockAuthenticator) -> Self {
+            self.providers = self.providers.with(name.to_string(), Arc::new(auth));
+            self
+        }
+
+        fn repo(mut self, repo: MockRepo) -> Self {
+            self.repo = repo;
+            self
+        }
+
+        fn directory(mut self, directory: MockDirectory) -> Self {
+            self.directory = directory;
+            self
+        }
+
+        /// Enables local accounts, with open registration.
+        fn local(mut self, accounts: MockAccountRepo) -> Self {
+            self.accounts = Some(accounts);
+            self
+        }
+
+        fn tokens(mut self, tokens: MockTokenRepo) -> Self {
+            self.tokens = Some(tokens);
+            self
+        }
+
+        fn build(self) -> web::Data<State> {
+            let repo = Arc::new(self.repo);
+            let local = self.accounts.map(|accounts| {
+                let local = LocalAuthenticator::new(
+                    Arc::new(accounts),
+                    repo.clone(),
+                    "http://website.local".to_string(),
+                )
+                .with_open_registration(true);
+
+                Arc::new(local)
+            });
+
+            web::Data::new(State {
+                sessions: Arc::new(self.sessions),
+                providers: Arc::new(self.providers),
+                repo,
+                directory: Arc::new(self.directory),
+                local,
+                tokens: self
+                    .tokens
+                    .map(|tokens| Arc::new(ApiTokens::new(Arc::new(tokens)))),
+                base_url: "http://website.local".to_string(),
+                cookie: CookieConfig::new("sid".to_string()),
+            })
+        }
+    }
+
+    #[actix_web::test]
+    async fn test_login_sets_state_cookie() {
+        let mut auth = MockAuthenticator::new();
+        auth.expect_start_login()
+            .returning(|l| Ok(format!("https://github.local/?state={}", l.state)));
+
+        let mut repo = MockRepo::new();
+        repo.expect_login_state_save().returning(|_| Ok(()));
+
+        let app = test::init_service(
+            App::new()
+                .app_data(state().provider("github", auth).repo(repo).build())
+                .service(login),
+        )
+        .await;
+
+        let req = test::TestRequest::get().uri("/api/auth/login").to_request();
+        let resp = test::call_service(&app, req).await;
+
+        assert_eq!(resp.status(), StatusCode::FOUND);
+
+        let cookie = resp
+            .response()
+            .cookies()
+            .find(|c| c.name() == "sid_state")
+            .unwrap();
+        let location = resp.headers().get("Location").unwrap().to_str().unwrap();
+
+        assert_eq!(
+            location,
+            format!("https://github.local/?state={}", cookie.value())
+        );
+        assert_eq!(cookie.http_only(), Some(true));
+    }
+
+    #[actix_web::test]
+    async fn test_callback_state_mismatch() {
+        let mut auth = MockAuthenticator::new();
+        auth.expect_login().never();
+
+        let mut repo = MockRepo::new();
+        repo.expect_login_state_take().never();
+
+        let app = test::init_service(
+            App::new()
+                .app_data(state().provider("github", auth).repo(repo).build())
+                .service(login_callback),
+        )
+        .await;
+
+        let req = test::TestRequest::get()
+            .uri("/api/auth/login/callback?code=abc&state=forged")
+            .cookie(Cookie::new("sid_state", "original"))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+
+        assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+    }
+
+    #[actix_web::test]
+    async fn test_callback_success() {
+        let mut auth = MockAuthenticator::new();
+        auth.expect_login()
+            .withf(|code, verifier, client_info| {
+                code == "abc"
+                    && verifier == "the_verifier"
+                    && client_info.user_agent.as_deref() == Some("test-agent")
+            })
+            .returning(|_, _, _| {
+                Ok(Session {
+                    user: User {
+                        id: 1,
+                        name: "John Doe".to_string(),
+                        avatar_url: "https://foo.bar".to_string(),
+                        login: "john_doe".to_string(),
+                    },
+                    token: "session_token".to_string(),
+                })
+            });
+
+        let mut repo = MockRepo::new();
+        repo.expect_login_state_take()
+            .withf(|state| state == "the_state")
+            .returning(|_| Ok("the_verifier".to_string()));
+
+        let app = test::init_service(
+            App::new()
+                .app_data(state().provider("github", auth).repo(repo).build())
+                .service(login_callback),
+        )
+        .await;
+
+        let req = test::TestRequest::get()
+            .uri("/api/auth/login/callback?code=abc&state=the_state")
+            .cookie(Cookie::new("sid_state", "the_state"))
+            .insert_header((header::USER_AGENT, "test-agent"))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+
+        assert_eq!(resp.status(), StatusCode::FOUND);
+
+        let cookie = resp
+            .response()
+            .cookies()
+            .find(|c| c.name() == "sid")
+            .unwrap();
+
+        assert_eq!(cookie.value(), "session_token");
+        assert_eq!(cookie.max_age(), Some(Duration::days(1)));
+    }
+
+    #[actix_web::test]
+    async fn test_callback_disabled_user() {
+        let mut auth = MockAuthenticator::new();
+        auth.expect_login().returning(|_, _, _| {
+            Ok(Session {
+                user: User {
+                    id: 1,
+                    name: "John Doe".to_string(),
+                    avatar_url: "https://foo.bar".to_string(),
+                    login: "john_doe".to_string(),
+                },
+                token: "session_token".to_string(),
+            })
+        });
+
+        let mut repo = MockRepo::new();
+        repo.expect_login_state_take()
+            .returning(|_| Ok("the_verifier".to_string()));
+
+        let mut sessions = session_manager();
+        sessions
+            .expect_logout()
+            .withf(|token| token == "session_token")
+            .times(1)
+            .returning(|_| Ok(()));
+
+        let data = state()
+            .sessions(sessions)
+            .provider("github", auth)
+            .repo(repo)
+            .directory(directory(true))
+            .build();
+
+        let app = test::init_service(App::new().app_data(data).service(login_callback)).await;
+
+        let req = test::TestRequest::get()
+            .uri("/api/auth/login/callback?code=abc&state=the_state")
+            .cookie(Cookie::new("sid_state", "the_state"))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+
+        assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+        assert!(!resp.response().cookies().any(|c| c.name() == "sid"));
+    }
+
+    #[actix_web::test]
+    async fn test_provider_callback() {
+        let mut github = MockAuthenticator::new();
+        github.expect_login().never();
+
+        let mut gitlab = MockAuthenticator::new();
+        gitlab.expect_login().returning(|_, _, _| {
+            Ok(Session {
+                user: User {
+                    id: 1,
+                    name: "John Doe".to_string(),
+                    avatar_url: "https://foo.bar".to_string(),
+                    login: "gitlab:john_doe".to_string(),
+                },
+                token: "session_token".to_string(),
+            })
+        });
+
+        let mut repo = MockRepo::new();
+        repo.expect_login_state_take()
+            .returning(|_| Ok("the_verifier".to_string()));
+
+        let data = state()
+            .provider("github", github)
+            .provider("gitlab", gitlab)
+            .repo(repo)
+            .build();
+
+        let app = test::init_service(
+            App::new()
+                .app_data(data)
+                .service(login_callback)
+                .service(provider_login)
+                .service(provider_login_callback),
+        )
+        .await;
+
+        let req = test::TestRequest::get()
+            .uri("/api/auth/login/gitlab/callback?code=abc&state=the_state")
+            .cookie(Cookie::new("sid_state", "the_state"))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+
+        assert_eq!(resp.status(), StatusCode::FOUND);
+    }
+
+    /// Session manager whose sessions all belong to `user_login`.
+    fn local_sessions(user_login: &'static str) -> MockSessionManager {
+        let mut sessions = session_manager();
+        sessions.expect_session().returning(move |_| {
+            Ok(User {
+                id: 1,
+                name: "John Doe".to_string(),
+                avatar_url: "https://foo.bar".to_string(),
+                login: user_login.to_string(),
+            })
+        });
+        sessions
+    }
+
+    #[actix_web::test]
+    async fn test_local_register() {
+        let mut accounts = MockAccountRepo::new();
+        accounts
+            .expect_account_create()
+            .returning(|l, name, password_hash| {
+                Ok(Account {
+                    id: 7,
+                    login: l,
+                    name,
+                    password_hash,
+                })
+            });
+
+        let mut repo = MockRepo::new();
+        repo.expect_save()
+            .returning(|_, _| Ok("session_token".to_string()));
+
+        let app = test::init_service(
+            App::new()
+                .app_data(
+                    state()
+                        .sessions(local_sessions("github_user"))
+                        .local(accounts)
+                        .repo(repo)
+                        .build(),
+                )
+                .service(local_register),
+        )
+        .await;
+
+        let req = test::TestRequest::post()
+            .uri("/api/auth/local/register")
+            .set_json(serde_json::json!({
+                "login": "jdoe",
+                "name": "John Doe",
+                "password": "correct horse",
+            }))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+
+        assert_eq!(resp.status(), StatusCode::CREATED);
+        assert!(
+            resp.response()
+                .cookies()
+                .any(|c| c.name() == "sid" && c.value() == "session_token")
+        );
+
+        let user: User = test::read_body_json(resp).await;
+        assert_eq!(user.login, "local:jdoe");
+    }
+
+    #[actix_web::test]
+    async fn test_local_change_password_not_local() {
+        let mut accounts = MockAccountRepo::new();
+        accounts.expect_account_get().never();
+
+        let app = test::init_service(
+            App::new()
+                .app_data(
+                    state()
+                        .sessions(local_sessions("github_user"))
+                        .local(accounts)
+                        .build(),
+                )
+                .service(local_change_password),
+        )
+        .await;
+
+        let req = test::TestRequest::put()
+            .uri("/api/auth/local/password")
+            .cookie(Cookie::new("sid", "session_token"))
+            .set_json(serde_json::json!({
+                "current_password": "correct horse",
+                "new_password": "battery staple",
+            }))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+
+        assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+    }
+
+    #[actix_web::test]
+    async fn test_local_change_password_revokes_other_sessions() {
+        let salt = SaltString::encode_b64(b"0123456789abcdef").unwrap();
+        let password_hash = Argon2::default()
+            .hash_password(b"correct horse", &salt)
+            .unwrap()
+            .to_string();
+
+        let mut accounts = MockAccountRepo::new();
+        accounts.expect_account_get().returning(move |l| {
+            Ok(Account {
+                id: 7,
+                login: l,
+                name: "John Doe".to_string(),
+                password_hash: password_hash.clone(),
+            })
+        });
+        accounts
+            .expect_account_set_password()
+            .returning(|_, _| Ok(()));
+
+        let mut sessions = local_sessions("local:jdoe");
+        sessions
+            .expect_revoke_all()
+            .withf(|l, keep| {
+                l == "local:jdoe" && keep.as_deref() == Some(session_id("session_token").as_str())
+            })
+            .times(1)
+            .returning(|_, _| Ok(()));
+
+        let app = test::init_service(
+            App::new()
+                .app_data(state().sessions(sessions).local(accounts).build())
+                .service(local_change_password),
+        )
+        .await;
+
+        let req = test::TestRequest::put()
+            .uri("/api/auth/local/password")
+            .cookie(Cookie::new("sid", "session_token"))
+            .set_json(serde_json::json!({
+                "current_password": "correct horse",
+                "new_password": "battery staple",
+            }))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+
+        assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+    }
+
+    #[actix_web::test]
+    async fn test_local_reset_password_revokes_all_sessions() {
+        let mut accounts = MockAccountRepo::new();
+        accounts
+            .expect_token_take()
+            .returning(|_, _| Ok("jdoe".to_string()));
+        accounts
+            .expect_account_set_password()
+            .returning(|_, _| Ok(()));
+
+        let mut sessions = session_manager();
+        sessions
+            .expect_revoke_all()
+            .withf(|l, keep| l == "local:jdoe" && keep.is_none())
+            .times(1)
+            .returning(|_, _| Ok(()));
+
+        let app = test::init_service(
+            App::new()
+                .app_data(state().sessions(sessions).local(accounts).build())
+                .service(local_reset_password),
+        )
+        .await;
+
+        let req = test::TestRequest::post()
+            .uri("/api/auth/local/reset")
+            .set_json(serde_json::json!({
+                "token": "reset_token",
+                "password": "battery staple",
+            }))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+
+        assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+    }
+
+    #[actix_web::test]
+    async fn test_local_disabled() {
+        let app =
+            test::init_service(App::new().app_data(state().build()).service(local_login)).await;
+
+        let req = test::TestRequest::post()
+            .uri("/api/auth/local/login")
+            .set_json(serde_json::json!({
+                "login": "jdoe",
+                "password": "correct horse",
+            }))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[actix_web::test]
+    async fn test_list_sessions_marks_current() {
+        let mut sessions = session_manager();
+        sessions.expect_session().returning(|_| {
+            Ok(User {
+                id: 1,
+                name: "John Doe".to_string(),
+                avatar_url: "https://foo.bar".to_string(),
+                login: "john_doe".to_string(),
+            })
+        });
+        sessions
+            .expect_sessions()
+            .withf(|l| l == "john_doe")
+            .returning(|_| {
+                Ok(["session_token", "other_token"]
+                    .iter()
+                    .map(|token| SessionInfo {
+                        id: session_id(token),
+                        created_at: Utc::now(),
+                        last_seen_at: Utc::now(),
+                        client_info: ClientInfo::default(),
+                    })
+                    .collect())
+            });
+
+        let data = state().sessions(sessions).build();
+
+        let app = test::init_service(App::new().app_data(data).service(list_sessions)).await;
+
+        let req = test::TestRequest::get()
+            .uri("/api/auth/sessions")
+            .cookie(Cookie::new("sid", "session_token"))
+            .to_request();
+        let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+
+        assert_eq!(body[0]["id"], session_id("session_token"));
+        assert_eq!(body[0]["current"], true);
+        assert_eq!(body[1]["current"], false);
+    }
+
+    #[actix_web::test]
+    async fn test_me_includes_csrf_token() {
+        let mut sessions = session_manager();
+        sessions
+            .expect_session()
+            .withf(|token| token == "session_token")
+            .returning(|_| {
+                Ok(User {
+                    id: 1,
+                    name: "John Doe".to_string(),
+                    avatar_url: "https://foo.bar".to_string(),
+                    login: "john_doe".to_string(),
+                })
+            });
+
+        let data = state().sessions(sessions).build();
+
+        let app = test::init_service(App::new().app_data(data).service(me)).await;
+
+        let req = test::TestRequest::get()
+            .uri("/api/auth/me")
+            .cookie(Cookie::new("sid", "session_token"))
+            .to_request();
+        let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+
+        assert_eq!(body["login"], "john_doe");
+        assert_eq!(body["csrf_token"], csrf::token("session_token"));
+
+        let req = test::TestRequest::get().uri("/api/auth/me").to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+    }
+
+    #[actix_web::test]
+    async fn test_revoke_other_sessions() {
+        let mut sessions = session_manager();
+        sessions.expect_session().returning(|_| {
+            Ok(User {
+                id: 1,
+                name: "John Doe".to_string(),
+                avatar_url: "https://foo.bar".to_string(),
+                login: "john_doe".to_string(),
+            })
+        });
+        sessions
+            .expect_revoke_all()
+            .withf(|l, keep| {
+                l == "john_doe" && keep.as_deref() == Some(session_id("session_token").as_str())
+            })
+            .times(1)
+            .returning(|_, _| Ok(()));
+
+        let data = state().sessions(sessions).build();
+
+        let app =
+            test::init_service(App::new().app_data(data).service(revoke_other_sessions)).await;
+
+        let req = test::TestRequest::delete()
+            .uri("/api/auth/sessions")
+            .cookie(Cookie::new("sid", "session_token"))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+
+        assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+    }
+
+    #[actix_web::test]
+    async fn test_revoke_other_sessions_requires_csrf_token() {
+        let mut sessions = session_manager();
+        sessions.expect_session().returning(|_| {
+            Ok(User {
+                id: 1,
+                name: "John Doe".to_string(),
+                avatar_url: "https://foo.bar".to_string(),
+                login: "john_doe".to_string(),
+            })
+        });
+        sessions
+            .expect_revoke_all()
+            .times(1)
+            .returning(|_, _| Ok(()));
+
+        let data = state().sessions(sessions).build();
+
+        let app = test::init_service(App::new().app_data(data.clone()).service(
+            cookie_authenticated("sid".to_string(), data.sessions.clone()),
+        ))
+        .await;
+
+        let req = test::TestRequest::delete()
+            .uri("/api/auth/sessions")
+            .cookie(Cookie::new("sid", "session_token"))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+
+        assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+
+        let req = test::TestRequest::delete()
+            .uri("/api/auth/sessions")
+            .cookie(Cookie::new("sid", "session_token"))
+            .insert_header((csrf::HEADER, csrf::token("session_token")))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+
+        assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+    }
+
+    #[actix_web::test]
+    async fn test_me_renews_cookie() {
+        let mut sessions = MockSessionManager::new();
+        sessions
+            .expect_renew()
+            .withf(|token| token == "old_token")
+            .returning(|_| Ok(Some("new_token".to_string())));
+        sessions
+            .expect_session()
+            .withf(|token| token == "new_token")
+            .returning(|_| Ok(api_token().user));
+        sessions
+            .expect_csrf_token()
+            .returning(|token| Ok(csrf::token(token)));
+
+        let data = state().sessions(sessions).build();
+
+        let app = test::init_service(App::new().app_data(data).service(me)).await;
+
+        let req = test::TestRequest::get()
+            .uri("/api/auth/me")
+            .cookie(Cookie::new("sid", "old_token"))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+
+        assert!(
+            resp.response()
+                .cookies()
+                .any(|c| c.name() == "sid" && c.value() == "new_token")
+        );
+
+        let body: serde_json::Value = test::read_body_json(resp).await;
+        assert_eq!(body["csrf_token"], csrf::token("new_token"));
+    }
+
+    fn api_token() -> ApiToken {
+        ApiToken {
+            id: Uuid::new_v4(),
+            user: User {
+                id: 1,
+                name: "John Doe".to_string(),
+                avatar_url: "https://foo.bar".to_string(),
+                login: "john_doe".to_string(),
+            },
+            name: "ci".to_string(),
+            scopes: vec![Scope::Read],
+            created_at: Utc::now(),
+            expires_at: Utc::now() + chrono::Duration::days(1),
+        }
+    }
+
+    #[actix_web::test]
+    async fn test_load_user_bearer() {
+        let mut sessions = session_manager();
+        sessions
+            .expect_api_token()
+            .withf(|token| token == "blog_token")
+            .returning(|_| Ok(api_token()));
+        sessions
+            .expect_session()
+            .returning(|_| Ok(api_token().user));
+        let sessions: Arc<dyn SessionManager> = Arc::new(sessions);
+
+        let req = test::TestRequest::get()
+            .insert_header((header::AUTHORIZATION, "Bearer blog_token"))
+            .cookie(Cookie::new("sid", "session_token"))
+            .to_http_request();
+        let caller = load_user(req, &sessions, "sid").await.unwrap();
+        assert_eq!(caller.user.login, "john_doe");
+        assert!(caller.allows(Scope::Read));
+        assert!(!caller.allows(Scope::Write));
+
+        let req = test::TestRequest::get()
+            .cookie(Cookie::new("sid", "session_token"))
+            .to_http_request();
+        let caller = load_user(req, &sessions, "sid").await.unwrap();
+        assert!(caller.scopes.is_none());
+        assert!(caller.allows(Scope::Admin));
+
+        let req = test::TestRequest::get()
+            .insert_header((header::AUTHORIZATION, "Basic Zm9vOmJhcg=="))
+            .to_http_request();
+        assert_eq!(
+            load_user(req, &sessions, "sid")
+                .await
+                .err()
+                .unwrap()
+                .to_string(),
+            "permission denied: invalid authorization header"
+        );
+    }
+
+    #[actix_web::test]
+    async fn test_create_token() {
+        let mut sessions = session_manager();
+        sessions
+            .expect_session()
+            .withf(|token| token == "session_token")
+            .returning(|_| Ok(api_token().user));
+
+        let mut token_repo = MockTokenRepo::new();
+        token_repo
+            .expect_tokens_create()
+            .withf(|token, _| token.user.login == "john_doe" && token.scopes == vec![Scope::Write])
+            .times(1)
+            .returning(|_, _| Ok(()));
+
+        let data = state().sessions(sessions).tokens(token_repo).build();
+
+        let app = test::init_service(App::new().app_data(data).service(create_token)).await;
+
+        let req = test::TestRequest::post()
+            .uri("/api/auth/tokens")
+            .cookie(Cookie::new("sid", "session_token"))
+            .set_json(serde_json::json!({"name": "ci", "scopes": ["write"], "expires_in_days": 7}))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::CREATED);
+
+        let body: serde_json::Value = test::read_body_json(resp).await;
+        assert_eq!(body["name"], "ci");
+        assert_eq!(body["scopes"], serde_json::json!(["write"]));
+        assert!(body["secret"].as_str().unwrap().starts_with("blog_"));
+        assert!(body.get("user").is_none());
+
+        // tokens cannot mint more tokens
+        let req = test::TestRequest::post()
+            .uri("/api/auth/tokens")
+            .insert_header((header::AUTHORIZATION, "Bearer blog_token"))
+            .set_json(serde_json::json!({"name": "ci", "scopes": ["write"]}))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+    }
+
+    #[actix_web::test]
+    async fn test_cookie_check() {
+        let mut cookie = CookieConfig::new("sid".to_string());
+
+        assert!(cookie.check("http://127.0.0.1:8010").is_ok());
+        assert_eq!(
+            cookie
+                .check("https://blog.example")
+                .unwrap_err()
+                .to_string(),
+            "initialization error: cookie must be secure when base_url is https"
+        );
+
+        cookie.same_site = SameSite::None;
+        assert!(cookie.check("http://127.0.0.1:8010").is_err());
+
+        cookie.secure = true;
+        assert!(cookie.check("https://blog.example").is_ok());
+    }
+
+    #[actix_web::test]
+    async fn test_session_cookie() {
+        let mut config = CookieConfig::new("sid".to_string());
+        config.domain = Some("blog.example".to_string());
+        config.secure = true;
+        config.same_site = same_site("Strict").unwrap();
+
+        let cookie = config.session("token".to_string());
+
+        assert_eq!(cookie.domain(), Some("blog.example"));
+        assert_eq!(cookie.secure(), Some(true));
+        assert_eq!(cookie.http_only(), Some(true));
+        assert_eq!(cookie.same_site(), Some(SameSite::Strict));
+        assert_eq!(cookie.max_age(), Some(Duration::days(1)));
+        assert!(same_site("sometimes").is_err());
+    }
+
+    #[actix_web::test]
+    async fn test_logout_removes_cookie() {
+        let mut sessions = session_manager();
+        sessions
+            .expect_logout()
+            .withf(|token| token == "session_token")
+            .returning(|_| Ok(()));
+
+        let data = state().sessions(sessions).build();
+
+        let app = test::init_service(App::new().app_data(data).service(logout)).await;
+
+        let req = test::TestRequest::get()
+            .uri("/api/auth/logout")
+            .cookie(Cookie::new("sid", "session_token"))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+
+        assert_eq!(resp.status(), StatusCode::FOUND);
+
+        let cookie = resp
+            .response()
+            .cookies()
+            .find(|c| c.name() == "sid")
+            .unwrap();
+
+        assert_eq!(cookie.value(), "");
+        assert_eq!(cookie.path(), Some("/"));
+        assert_eq!(cookie.max_age(), Some(Duration::ZERO));
+    }
+
+    #[actix_web::test]
+    async fn test_logout_failed_removes_cookie() {
+        let mut sessions = session_manager();
+        sessions
+            .expect_logout()
+            .returning(|_| Err(Error::PermissionDenied("unknown key".to_string())));
+
+        let data = state().sessions(sessions).build();
+
+        let app = test::init_service(App::new().app_data(data).service(logout)).await;
+
+        let req = test::TestRequest::get()
+            .uri("/api/auth/logout")
+            .cookie(Cookie::new("sid", "stale_token"))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+
+        assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+
+        let cookie = resp
+            .response()
+            .cookies()
+            .find(|c| c.name() == "sid")
+            .unwrap();
+
+        assert_eq!(cookie.value(), "");
+        assert_eq!(cookie.max_age(), Some(Duration::ZERO));
+    }
+
+    #[actix_web::test]
+    async fn test_unknown_provider() {
+        let app =
+            test::init_service(App::new().app_data(state().build()).service(provider_login)).await;
+
+        let req = test::TestRequest::get()
+            .uri("/api/auth/login/unknown")
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    }
+}
@@ -0,0 +1,747 @@
+use super::{Authenticator, ClientInfo, LoginState, Repo, Session, User, random_string};
+use crate::errors::Error;
+use argon2::Argon2;
+use argon2::password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use async_trait::async_trait;
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use chrono::{DateTime, Duration, Utc};
+use mockall::predicate::*;
+use mockall::*;
+use rand::RngCore;
+use sha2::{Digest, Sha256};
+use std::sync::Arc;
+
+pub mod memory;
+pub mod postgres;
+
+/// Provider name of local accounts, their logins are prefixed with it.
+pub const PROVIDER: &str = "local";
+
+const MIN_PASSWORD_LENGTH: usize = 8;
+
+/// How long an invite can be used to register.
+const INVITE_TTL: Duration = Duration::days(7);
+/// How long a password reset token is valid.
+const RESET_TTL: Duration = Duration::hours(1);
+
+/// Verified against when the login is unknown, so that it takes as long as a wrong password.
+/// Hashed with the default parameters, like every stored password.
+const DUMMY_HASH: &str = "$argon2id$v=19$m=19456,t=2,p=1$Gc9rgN72RiG8Ccs4AtSmcA$Zv2LrrwFtCkEeU81uYdbB8UHUm4WZNEyB7FvUAllvxM";
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Account {
+    pub id: i64,
+    pub login: String,
+    pub name: String,
+    /// Argon2id hash, in the PHC string format.
+    pub password_hash: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TokenKind {
+    Invite,
+    Reset,
+}
+
+impl std::fmt::Display for TokenKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TokenKind::Invite => write!(f, "invite"),
+            TokenKind::Reset => write!(f, "reset"),
+        }
+    }
+}
+
+#[automock]
+#[async_trait]
+pub trait AccountRepo: Send + Sync {
+    async fn account_get(&self, login: String) -> Result<Account, Error>;
+    /// Fails with `InvalidInput` when the login is already in use.
+    async fn account_create(
+        &self,
+        login: String,
+        name: String,
+        password_hash: String,
+    ) -> Result<Account, Error>;
+    async fn account_set_password(&self, login: String, password_hash: String)
+    -> Result<(), Error>;
+
+    async fn token_save(
+        &self,
+        kind: TokenKind,
+        token_hash: String,
+        login: String,
+        expires_at: DateTime<Utc>,
+    ) -> Result<(), Error>;
+    /// Returns the login of a token that has not expired, leaving the token usable.
+    async fn token_peek(&self, kind: TokenKind, token_hash: String) -> Result<String, Error>;
+    /// Returns the login of a token that has not expired, a token can only be taken once.
+    async fn token_take(&self, kind: TokenKind, token_hash: String) -> Result<String, Error>;
+}
+
+pub struct LocalAuthenticator {
+    accounts: Arc<dyn AccountRepo>,
+    repo: Arc<dyn Repo>,
+    login_url: String,
+    open_registration: bool,
+}
+
+impl LocalAuthenticator {
+    pub fn new(accounts: Arc<dyn AccountRepo>, repo: Arc<dyn Repo>, base_url: String) -> Self {
+        LocalAuthenticator {
+            accounts,
+            repo,
+            login_url: format!("{}/login", base_url),
+            open_registration: false,
+        }
+    }
+
+    /// Lets anyone register, otherwise an invite is needed.
+    pub fn with_open_registration(mut self, open_registration: bool) -> Self {
+        self.open_registration = open_registration;
+        self
+    }
+
+    async fn session(&self, account: Account, client_info: ClientInfo) -> Result<Session, Error> {
+        let user = User {
+            id: account.id as u64,
+            name: account.name,
+            avatar_url: "".to_string(),
+            login: format!("{}:{}", PROVIDER, account.login),
+        };
+
+        let token = self.repo.save(user.clone(), client_info).await?;
+
+        Ok(Session { user, token })
+    }
+
+    pub async fn authenticate(
+        &self,
+        login: String,
+        password: String,
+        client_info: ClientInfo,
+    ) -> Result<Session, Error> {
+        let account = match self.accounts.account_get(login).await {
+            Ok(account) => account,
+            Err(Error::NotFound(_)) => {
+                // verify anyway, so unknown logins take as long as wrong passwords
+                blocking(move || verify_password(&password, DUMMY_HASH)).await?;
+                return Err(invalid_credentials());
+            }
+            Err(err) => return Err(err),
+        };
+
+        let password_hash = account.password_hash.clone();
+        if !blocking(move || verify_password(&password, &password_hash)).await? {
+            return Err(invalid_credentials());
+        }
+
+        self.session(account, client_info).await
+    }
+
+    /// Creates an account, `login` is only used with open registration,
+    /// an invite decides the login otherwise.
+    ///
+    /// The invite is only taken once everything else checks out, so that a mistake does not use it up.
+    pub async fn register(
+        &self,
+        invite: Option<String>,
+        login: Option<String>,
+        name: String,
+        password: String,
+        client_info: ClientInfo,
+    ) -> Result<Session, Error> {
+        let login = match (&invite, login) {
+            (Some(invite), _) => {
+                self.accounts
+                    .token_peek(TokenKind::Invite, token_hash(invite))
+                    .await?
+            }
+            (None, Some(login)) if self.open_registration => login,
+            (None, _) => {
+                return Err(Error::PermissionDenied(
+                    "registration requires an invite".to_string(),
+                ));
+            }
+        };
+
+        validate_login(&login)?;
+
+        if name.trim().is_empty() {
+            return Err(Error::InvalidInput("name is required".to_string()));
+        }
+
+        let password_hash = blocking(move || hash_password(&password)).await??;
+
+        let login = match invite {
+            Some(invite) => {
+                match self.accounts.account_get(login.clone()).await {
+                    Ok(_) => {
+                        return Err(Error::InvalidInput(format!(
+                            "login {} is already in use",
+                            login
+                        )));
+                    }
+                    Err(Error::NotFound(_)) => (),
+                    Err(err) => return Err(err),
+                }
+
+                self.accounts
+                    .token_take(TokenKind::Invite, token_hash(&invite))
+                    .await?
+            }
+            None => login,
+        };
+
+        let account = self
+            .accounts
+            .account_create(login, name.trim().to_string(), password_hash)
+            .await?;
+
+        self.session(account, client_info).await
+    }
+
+    pub async fn change_password(
+        &self,
+        login: String,
+        current_password: String,
+        new_password: String,
+    ) -> Result<(), Error> {
+        let account = self.accounts.account_get(login.clone()).await?;
+
+        let password_hash = account.password_hash;
+        if !blocking(move || verify_password(&current_password, &password_hash)).await? {
+            return Err(Error::PermissionDenied(
+                "current password is incorrect".to_string(),
+            ));
+        }
+
+        let password_hash = blocking(move || hash_password(&new_password)).await??;
+        self.accounts
+            .account_set_password(login, password_hash)
+            .await
+    }
+
+    /// Returns an invite token for `login`, to hand over to the invitee.
+    pub async fn invite(&self, login: String) -> Result<String, Error> {
+        validate_login(&login)?;
+
+        match self.accounts.account_get(login.clone()).await {
+            Ok(_) => {
+                return Err(Error::InvalidInput(format!(
+                    "login {} is already in use",
+                    login
+                )));
+            }
+            Err(Error::NotFound(_)) => (),
+            Err(err) => return Err(err),
+        }
+
+        self.issue_token(TokenKind::Invite, login, INVITE_TTL).await
+    }
+
+    /// Returns a token allowing to set a new password for `login` without the current one.
+    pub async fn reset_token(&self, login: String) -> Result<String, Error> {
+        self.accounts.account_get(login.clone()).await?;
+
+        self.issue_token(TokenKind::Reset, login, RESET_TTL).await
+    }
+
+    /// Returns the login of the account, whose sessions are then up to the caller to revoke.
+    pub async fn reset_password(
+        &self,
+        token: String,
+        new_password: String,
+    ) -> Result<String, Error> {
+        let password_hash = blocking(move || hash_password(&new_password)).await??;
+        let login = self
+            .accounts
+            .token_take(TokenKind::Reset, token_hash(&token))
+            .await?;
+
+        self.accounts
+            .account_set_password(login.clone(), password_hash)
+            .await?;
+
+        Ok(login)
+    }
+
+    async fn issue_token(
+        &self,
+        kind: TokenKind,
+        login: String,
+        ttl: Duration,
+    ) -> Result<String, Error> {
+        let token = random_string();
+
+        self.accounts
+            .token_save(kind, token_hash(&token), login, Utc::now() + ttl)
+            .await?;
+
+        Ok(token)
+    }
+}
+
+#[async_trait]
+impl Authenticator for LocalAuthenticator {
+    /// Local accounts have no provider to redirect to, only a login form.
+    async fn start_login(&self, _login: &LoginState) -> Result<String, Error> {
+        Ok(self.login_url.clone())
+    }
+
+    async fn login(
+        &self,
+        _code: String,
+        _verifier: String,
+        _client_info: ClientInfo,
+    ) -> Result<Session, Error> {
+        Err(Error::PermissionDenied(
+            "local accounts log in with a password".to_string(),
+        ))
+    }
+}
+
+fn invalid_credentials() -> Error {
+    Error::PermissionDenied("invalid login or password".to_string())
+}
+
+fn validate_login(login: &str) -> Result<(), Error> {
+    let valid = !login.is_empty()
+        && login
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'));
+
+    if !valid {
+        return Err(Error::InvalidInput(
+            "login may only contain letters, digits, '-', '_' and '.'".to_string(),
+        ));
+    }
+
+    Ok(())
+}
+
+fn hash_password(password: &str) -> Result<String, Error> {
+    if password.chars().count() < MIN_PASSWORD_LENGTH {
+        return Err(Error::InvalidInput(format!(
+            "password must be at least {} characters",
+            MIN_PASSWORD_LENGTH
+        )));
+    }
+
+    let mut salt = [0u8; 16];
+    rand::rng().fill_bytes(&mut salt);
+
+    let salt = SaltString::encode_b64(&salt)
+        .map_err(|err| Error::InitializationError(format!("encoding salt: {}", err)))?;
+
+    // the default is argon2id with the parameters recommended by OWASP
+    let hash = Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .map_err(|err| Error::InitializationError(format!("hashing password: {}", err)))?;
+
+    Ok(hash.to_string())
+}
+
+/// Runs argon2 on the blocking pool, it takes long enough to stall the other requests of a worker.
+async fn blocking<T: Send + 'static>(f: impl FnOnce() -> T + Send + 'static) -> Result<T, Error> {
+    tokio::task::spawn_blocking(f)
+        .await
+        .map_err(|err| Error::InitializationError(format!("hashing password: {}", err)))
+}
+
+fn verify_password(password: &str, password_hash: &str) -> bool {
+    match PasswordHash::new(password_hash) {
+        Ok(hash) => Argon2::default()
+            .verify_password(password.as_bytes(), &hash)
+            .is_ok(),
+        Err(_) => false,
+    }
+}
+
+fn token_hash(token: &str) -> String {
+    URL_SAFE_NO_PAD.encode(Sha256::digest(token.as_bytes()))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::auth::MockRepo;
+
+    fn account(password: &str) -> Account {
+        Account {
+            id: 7,
+            login: "jdoe".to_string(),
+            name: "John Doe".to_string(),
+            password_hash: hash_password(password).unwrap(),
+        }
+    }
+
+    fn client_info_fixture() -> ClientInfo {
+        ClientInfo {
+            ip: Some("127.0.0.1".to_string()),
+            user_agent: Some("test".to_string()),
+        }
+    }
+
+    fn session_repo() -> MockRepo {
+        let mut repo = MockRepo::new();
+        repo.expect_save()
+            .withf(|user, client_info| {
+                user.login == "local:jdoe" && user.id == 7 && *client_info == client_info_fixture()
+            })
+            .returning(|_, _| Ok("token".to_string()));
+        repo
+    }
+
+    fn authenticator(accounts: MockAccountRepo, repo: MockRepo) -> LocalAuthenticator {
+        LocalAuthenticator::new(
+            Arc::new(accounts),
+            Arc::new(repo),
+            "http://website.local".to_string(),
+        )
+    }
+
+    #[test]
+    fn test_hash_password() {
+        let hash = hash_password("correct horse").unwrap();
+
+        assert!(hash.starts_with("$argon2id$"));
+        assert!(verify_password("correct horse", &hash));
+        assert!(!verify_password("wrong horse", &hash));
+        assert!(!verify_password("correct horse", "not a hash"));
+        assert_eq!(
+            hash_password("short").unwrap_err().to_string(),
+            "invalid input: password must be at least 8 characters"
+        );
+    }
+
+    #[test]
+    fn test_dummy_hash_matches_stored_hashes() {
+        let stored = hash_password("correct horse").unwrap();
+        let stored = PasswordHash::new(&stored).unwrap();
+        let dummy = PasswordHash::new(DUMMY_HASH).unwrap();
+
+        assert_eq!(dummy.algorithm, stored.algorithm);
+        assert_eq!(dummy.params, stored.params);
+        assert!(!verify_password("x", DUMMY_HASH));
+    }
+
+    #[tokio::test]
+    async fn test_authenticate() {
+        let mut accounts = MockAccountRepo::new();
+        accounts
+            .expect_account_get()
+            .returning(|_| Ok(account("correct horse")));
+
+        let auth = authenticator(accounts, session_repo());
+
+        let session = auth
+            .authenticate(
+                "jdoe".to_string(),
+                "correct horse".to_string(),
+                client_info_fixture(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(session.token, "token");
+        assert_eq!(session.user.login, "local:jdoe");
+        assert_eq!(session.user.name, "John Doe");
+    }
+
+    #[tokio::test]
+    async fn test_authenticate_wrong_password() {
+        let mut accounts = MockAccountRepo::new();
+        accounts
+            .expect_account_get()
+            .returning(|_| Ok(account("correct horse")));
+
+        let mut repo = MockRepo::new();
+        repo.expect_save().never();
+
+        let auth = authenticator(accounts, repo);
+
+        let result = auth
+            .authenticate(
+                "jdoe".to_string(),
+                "wrong horse".to_string(),
+                client_info_fixture(),
+            )
+            .await;
+
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "permission denied: invalid login or password"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_authenticate_unknown_login() {
+        let mut accounts = MockAccountRepo::new();
+        accounts
+            .expect_account_get()
+            .returning(|login| Err(Error::NotFound(format!("account {}", login))));
+
+        let auth = authenticator(accounts, MockRepo::new());
+
+        let result = auth
+            .authenticate(
+                "nobody".to_string(),
+                "correct horse".to_string(),
+                client_info_fixture(),
+            )
+            .await;
+
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "permission denied: invalid login or password"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_register_with_invite() {
+        let mut accounts = MockAccountRepo::new();
+        accounts
+            .expect_token_peek()
+            .withf(|kind, hash| *kind == TokenKind::Invite && *hash == token_hash("invite"))
+            .returning(|_, _| Ok("jdoe".to_string()));
+        accounts
+            .expect_account_get()
+            .returning(|login| Err(Error::NotFound(format!("account {}", login))));
+        accounts
+            .expect_token_take()
+            .withf(|kind, hash| *kind == TokenKind::Invite && *hash == token_hash("invite"))
+            .returning(|_, _| Ok("jdoe".to_string()));
+        accounts
+            .expect_account_create()
+            .withf(|login, name, hash| {
+                login == "jdoe" && name == "John Doe" && verify_password("correct horse", hash)
+            })
+            .returning(|login, name, password_hash| {
+                Ok(Account {
+                    id: 7,
+                    login,
+                    name,
+                    password_hash,
+                })
+            });
+
+        let auth = authenticator(accounts, session_repo());
+
+        let session = auth
+            .register(
+                Some("invite".to_string()),
+                Some("ignored".to_string()),
+                " John Doe ".to_string(),
+                "correct horse".to_string(),
+                client_info_fixture(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(session.user.login, "local:jdoe");
+    }
+
+    #[tokio::test]
+    async fn test_register_invalid_keeps_invite() {
+        let mut accounts = MockAccountRepo::new();
+        accounts
+            .expect_token_peek()
+            .returning(|_, _| Ok("jdoe".to_string()));
+        accounts
+            .expect_account_get()
+            .returning(|_| Ok(account("correct horse")));
+        accounts.expect_token_take().never();
+        accounts.expect_account_create().never();
+
+        let auth = authenticator(accounts, MockRepo::new());
+
+        let register = |name: &str, password: &str| {
+            auth.register(
+                Some("invite".to_string()),
+                None,
+                name.to_string(),
+                password.to_string(),
+                client_info_fixture(),
+            )
+        };
+
+        assert_eq!(
+            register("John Doe", "short")
+                .await
+                .err()
+                .unwrap()
+                .to_string(),
+            "invalid input: password must be at least 8 characters"
+        );
+        assert_eq!(
+            register(" ", "correct horse")
+                .await
+                .err()
+                .unwrap()
+                .to_string(),
+            "invalid input: name is required"
+        );
+        assert_eq!(
+            register("John Doe", "correct horse")
+                .await
+                .err()
+                .unwrap()
+                .to_string(),
+            "invalid input: login jdoe is already in use"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_register_requires_invite() {
+        let mut accounts = MockAccountRepo::new();
+        accounts.expect_account_create().never();
+
+        let auth = authenticator(accounts, MockRepo::new());
+
+        let result = auth
+            .register(
+                None,
+                Some("jdoe".to_string()),
+                "John Doe".to_string(),
+                "correct horse".to_string(),
+                client_info_fixture(),
+            )
+            .await;
+
+        assert_eq!(
+            result.err().unwrap().to_string(),
+            "permission denied: registration requires an invite"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_register_open() {
+        let mut accounts = MockAccountRepo::new();
+        accounts
+            .expect_account_create()
+            .returning(|login, name, password_hash| {
+                Ok(Account {
+                    id: 7,
+                    login,
+                    name,
+                    password_hash,
+                })
+            });
+
+        let auth = authenticator(accounts, session_repo()).with_open_registration(true);
+
+        let session = auth
+            .register(
+                None,
+                Some("jdoe".to_string()),
+                "John Doe".to_string(),
+                "correct horse".to_string(),
+                client_info_fixture(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(session.user.login, "local:jdoe");
+
+        let result = auth
+            .register(
+                None,
+                Some("j:doe".to_string()),
+                "John Doe".to_string(),
+                "correct horse".to_string(),
+                client_info_fixture(),
+            )
+            .await;
+
+        assert!(matches!(result, Err(Error::InvalidInput(_))));
+    }
+
+    #[tokio::test]
+    async fn test_change_password() {
+        let mut accounts = MockAccountRepo::new();
+        accounts
+            .expect_account_get()
+            .returning(|_| Ok(account("correct horse")));
+        accounts
+            .expect_account_set_password()
+            .withf(|login, hash| login == "jdoe" && verify_password("battery staple", hash))
+            .times(1)
+            .returning(|_, _| Ok(()));
+
+        let auth = authenticator(accounts, MockRepo::new());
+
+        let result = auth
+            .change_password(
+                "jdoe".to_string(),
+                "wrong horse".to_string(),
+                "battery staple".to_string(),
+            )
+            .await;
+
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "permission denied: current password is incorrect"
+        );
+
+        auth.change_password(
+            "jdoe".to_string(),
+            "correct horse".to_string(),
+            "battery staple".to_string(),
+        )
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_reset_password() {
+        let mut accounts = MockAccountRepo::new();
+        accounts
+            .expect_account_get()
+            .returning(|_| Ok(account("correct horse")));
+        accounts
+            .expect_token_save()
+            .withf(|kind, _, login, expires_at| {
+                *kind == TokenKind::Reset && login == "jdoe" && *expires_at > Utc::now()
+            })
+            .returning(|_, _, _, _| Ok(()));
+        accounts
+            .expect_token_take()
+            .withf(|kind, hash| *kind == TokenKind::Reset && *hash == token_hash("reset"))
+            .returning(|_, _| Ok("jdoe".to_string()));
+        accounts
+            .expect_account_set_password()
+            .withf(|login, hash| login == "jdoe" && verify_password("battery staple", hash))
+            .returning(|_, _| Ok(()));
+
+        let auth = authenticator(accounts, MockRepo::new());
+
+        let token = auth.reset_token("jdoe".to_string()).await.unwrap();
+        assert_eq!(token.len(), 43);
+
+        let login = auth
+            .reset_password("reset".to_string(), "battery staple".to_string())
+            .await
+            .unwrap();
+        assert_eq!(login, "jdoe");
+    }
+
+    #[tokio::test]
+    async fn test_invite_existing_login() {
+        let mut accounts = MockAccountRepo::new();
+        accounts
+            .expect_account_get()
+            .returning(|_| Ok(account("correct horse")));
+        accounts.expect_token_save().never();
+
+        let auth = authenticator(accounts, MockRepo::new());
+
+        let result = auth.invite("jdoe".to_string()).await;
+
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "invalid input: login jdoe is already in use"
+        );
+    }
+}
@@ -0,0 +1,135 @@
+use super::{Account, AccountRepo, TokenKind};
+use crate::errors::Error;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use std::collections::HashMap;
+use std::sync::RwLock;
+
+struct StoredToken {
+    kind: TokenKind,
+    login: String,
+    expires_at: DateTime<Utc>,
+}
+
+#[derive(Default)]
+struct Data {
+    /// Accounts by login.
+    accounts: HashMap<String, Account>,
+    /// Invite and reset tokens by hash.
+    tokens: HashMap<String, StoredToken>,
+}
+
+/// Keeps local accounts in memory, they are lost when the process exits.
+#[derive(Default)]
+pub struct MemoryAccountRepo {
+    data: RwLock<Data>,
+}
+
+impl MemoryAccountRepo {
+    pub fn new() -> Self {
+        MemoryAccountRepo::default()
+    }
+}
+
+fn check_token(kind: TokenKind, token: Option<&StoredToken>) -> Result<String, Error> {
+    match token {
+        Some(token) if token.kind == kind && token.expires_at > Utc::now() => {
+            Ok(token.login.clone())
+        }
+        Some(token) if token.kind == kind => {
+            Err(Error::PermissionDenied(format!("{} token expired", kind)))
+        }
+        _ => Err(Error::PermissionDenied(format!("invalid {} token", kind))),
+    }
+}
+
+#[async_trait]
+impl AccountRepo for MemoryAccountRepo {
+    async fn account_get(&self, login: String) -> Result<Account, Error> {
+        self.data
+            .read()
+            .unwrap()
+            .accounts
+            .get(&login)
+            .cloned()
+            .ok_or_else(|| Error::NotFound(format!("account {}", login)))
+    }
+
+    async fn account_create(
+        &self,
+        login: String,
+        name: String,
+        password_hash: String,
+    ) -> Result<Account, Error> {
+        let mut data = self.data.write().unwrap();
+
+        if data.accounts.contains_key(&login) {
+            return Err(Error::InvalidInput(format!(
+                "login {} is already in use",
+                login
+            )));
+        }
+
+        let account = Account {
+            id: data.accounts.len() as i64 + 1,
+            login: login.clone(),
+            name,
+            password_hash,
+        };
+        data.accounts.insert(login, account.clone());
+
+        Ok(account)
+    }
+
+    async fn account_set_password(
+        &self,
+        login: String,
+        password_hash: String,
+    ) -> Result<(), Error> {
+        match self.data.write().unwrap().accounts.get_mut(&login) {
+            Some(account) => {
+                account.password_hash = password_hash;
+                Ok(())
+            }
+            None => Err(Error::NotFound(format!("account {}", login))),
+        }
+    }
+
+    async fn token_save(
+        &self,
+        kind: TokenKind,
+        token_hash: String,
+        login: String,
+        expires_at: DateTime<Utc>,
+    ) -> Result<(), Error> {
+        self.data.write().unwrap().tokens.insert(
+            token_hash,
+            StoredToken {
+                kind,
+                login,
+                expires_at,
+            },
+        );
+
+        Ok(())
+    }
+
+    async fn token_peek(&self, kind: TokenKind, token_hash: String) -> Result<String, Error> {
+        check_token(kind, self.data.read().unwrap().tokens.get(&token_hash))
+    }
+
+    async fn token_take(&self, kind: TokenKind, token_hash: String) -> Result<String, Error> {
+        let mut data = self.data.write().unwrap();
+
+        let login = check_token(kind, data.tokens.get(&token_hash));
+        if data
+            .tokens
+            .get(&token_hash)
+            .is_some_and(|token| token.kind == kind)
+        {
+            data.tokens.remove(&token_hash);
+        }
+
+        login
+    }
+}
@@ -0,0 +1,159 @@
+use super::{Account, AccountRepo, TokenKind};
+use crate::errors::Error;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use sqlx::postgres::PgPool;
+use std::sync::Arc;
+
+pub struct PostgresAccountRepo {
+    db: Arc<PgPool>,
+}
+
+impl PostgresAccountRepo {
+    pub async fn new(dsn: String) -> Result<PostgresAccountRepo, Error> {
+        let db = match PgPool::connect(&dsn).await {
+            Ok(pool) => Arc::new(pool),
+            Err(err) => {
+                return Err(Error::ConnectionError(format!("connecting to db: {}", err)));
+            }
+        };
+
+        Ok(PostgresAccountRepo { db })
+    }
+}
+
+#[async_trait]
+impl AccountRepo for PostgresAccountRepo {
+    async fn account_get(&self, login: String) -> Result<Account, Error> {
+        let row = sqlx::query!(
+            r#"SELECT id, login, name, password_hash FROM auth.accounts WHERE login = $1"#,
+            login
+        )
+        .fetch_optional(&*self.db)
+        .await
+        .map_err(|err| Error::ConnectionError(format!("fetching account: {}", err)))?;
+
+        match row {
+            Some(row) => Ok(Account {
+                id: row.id,
+                login: row.login,
+                name: row.name,
+                password_hash: row.password_hash,
+            }),
+            None => Err(Error::NotFound(format!("account {}", login))),
+        }
+    }
+
+    async fn account_create(
+        &self,
+        login: String,
+        name: String,
+        password_hash: String,
+    ) -> Result<Account, Error> {
+        let now = Utc::now();
+
+        let row = sqlx::query!(
+            r#"INSERT INTO auth.accounts (login, name, password_hash, created_at, updated_at)
+            VALUES ($1, $2, $3, $4, $4)
+            ON CONFLICT (login) DO NOTHING
+            RETURNING id"#,
+            login,
+            name,
+            password_hash,
+            now,
+        )
+        .fetch_optional(&*self.db)
+        .await
+        .map_err(|err| Error::ConnectionError(format!("creating account: {}", err)))?;
+
+        match row {
+            Some(row) => Ok(Account {
+                id: row.id,
+                login,
+                name,
+                password_hash,
+            }),
+            None => Err(Error::InvalidInput(format!(
+                "login {} is already in use",
+                login
+            ))),
+        }
+    }
+
+    async fn account_set_password(
+        &self,
+        login: String,
+        password_hash: String,
+    ) -> Result<(), Error> {
+        let result = sqlx::query!(
+            r#"UPDATE auth.accounts SET password_hash = $2, updated_at = $3 WHERE login = $1"#,
+            login,
+            password_hash,
+            Utc::now(),
+        )
+        .execute(&*self.db)
+        .await
+        .map_err(|err| Error::ConnectionError(format!("updating password: {}", err)))?;
+
+        if result.rows_affected() == 0 {
+            return Err(Error::NotFound(format!("account {}", login)));
+        }
+
+        Ok(())
+    }
+
+    async fn token_save(
+        &self,
+        kind: TokenKind,
+        token_hash: String,
+        login: String,
+        expires_at: DateTime<Utc>,
+    ) -> Result<(), Error> {
+        sqlx::query!(
+            r#"INSERT INTO auth.account_tokens (token_hash, kind, login, expires_at) VALUES ($1, $2, $3, $4)"#,
+            token_hash,
+            kind.to_string(),
+            login,
+            expires_at,
+        )
+        .execute(&*self.db)
+        .await
+        .map_err(|err| Error::ConnectionError(format!("saving token: {}", err)))?;
+
+        Ok(())
+    }
+
+    async fn token_peek(&self, kind: TokenKind, token_hash: String) -> Result<String, Error> {
+        let row = sqlx::query!(
+            r#"SELECT login, expires_at FROM auth.account_tokens WHERE token_hash = $1 AND kind = $2"#,
+            token_hash,
+            kind.to_string(),
+        )
+        .fetch_optional(&*self.db)
+        .await
+        .map_err(|err| Error::ConnectionError(format!("fetching token: {}", err)))?;
+
+        match row {
+            Some(row) if row.expires_at > Utc::now() => Ok(row.login),
+            Some(_) => Err(Error::PermissionDenied(format!("{} token expired", kind))),
+            None => Err(Error::PermissionDenied(format!("invalid {} token", kind))),
+        }
+    }
+
+    async fn token_take(&self, kind: TokenKind, token_hash: String) -> Result<String, Error> {
+        let row = sqlx::query!(
+            r#"DELETE FROM auth.account_tokens WHERE token_hash = $1 AND kind = $2 RETURNING login, expires_at"#,
+            token_hash,
+            kind.to_string(),
+        )
+        .fetch_optional(&*self.db)
+        .await
+        .map_err(|err| Error::ConnectionError(format!("taking token: {}", err)))?;
+
+        match row {
+            Some(row) if row.expires_at > Utc::now() => Ok(row.login),
+            Some(_) => Err(Error::PermissionDenied(format!("{} token expired", kind))),
+            None => Err(Error::PermissionDenied(format!("invalid {} token", kind))),
+        }
+    }
+}
@@ -0,0 +1,399 @@
+use super::signed::Denylist;
+use super::{
+    ClientInfo, Directory, LAST_SEEN_RESOLUTION, LOGIN_STATE_TTL, LoginState, Repo, SessionInfo,
+    User, UserRecord, session_expires_at, session_id,
+};
+use crate::errors::Error;
+use async_trait::async_trait;
+use chrono::{DateTime, Duration, Utc};
+use std::collections::HashMap;
+use std::sync::RwLock;
+use uuid::Uuid;
+
+struct StoredSession {
+    user: User,
+    created_at: DateTime<Utc>,
+    last_seen_at: DateTime<Utc>,
+    expires_at: DateTime<Utc>,
+    client_info: ClientInfo,
+}
+
+#[derive(Default)]
+struct Data {
+    /// Sessions by token.
+    sessions: HashMap<String, StoredSession>,
+    /// Verifiers of logins in progress by state, with their expiry.
+    login_states: HashMap<String, (String, DateTime<Utc>)>,
+    /// Denied session ids, with when they stop being denied.
+    denied: HashMap<String, DateTime<Utc>>,
+}
+
+/// Keeps sessions in memory, for development and tests, they are lost when the process exits.
+///
+/// Expires sessions and login states as the redis repo does. Expired entries are ignored when
+/// read and dropped on the next login.
+pub struct MemoryRepo {
+    data: RwLock<Data>,
+    /// Seconds a session lives without being used.
+    ttl: i64,
+    /// Seconds a session lives at most, however much it is used.
+    max_lifetime: i64,
+}
+
+impl MemoryRepo {
+    pub fn new(ttl: i64) -> Self {
+        MemoryRepo {
+            data: RwLock::new(Data::default()),
+            ttl,
+            max_lifetime: ttl,
+        }
+    }
+
+    /// Lets sessions be extended by `ttl` on use, up to `max_lifetime` seconds after login.
+    pub fn with_max_lifetime(mut self, max_lifetime: i64) -> Self {
+        self.max_lifetime = max_lifetime.max(self.ttl);
+        self
+    }
+}
+
+#[async_trait]
+impl Repo for MemoryRepo {
+    async fn save(&self, user: User, client_info: ClientInfo) -> Result<String, Error> {
+        let token = Uuid::new_v4().to_string();
+        let now = Utc::now();
+
+        let mut data = self.data.write().unwrap();
+
+        data.sessions.retain(|_, session| session.expires_at > now);
+        data.login_states
+            .retain(|_, (_, expires_at)| *expires_at > now);
+        data.denied.retain(|_, expires_at| *expires_at > now);
+
+        data.sessions.insert(
+            token.clone(),
+            StoredSession {
+                user,
+                created_at: now,
+                last_seen_at: now,
+                expires_at: now + Duration::seconds(self.ttl),
+                client_info,
+            },
+        );
+
+        Ok(token)
+    }
+
+    async fn get(&self, token: String) -> Result<User, Error> {
+        match self.data.read().unwrap().sessions.get(&token) {
+            Some(session) if session.expires_at > Utc::now() => Ok(session.user.clone()),
+            _ => Err(Error::PermissionDenied("no session".to_string())),
+        }
+    }
+
+    async fn refresh(&self, token: String) -> Result<(), Error> {
+        let now = Utc::now();
+        let mut data = self.data.write().unwrap();
+
+        let session = match data.sessions.get_mut(&token) {
+            Some(session) if session.expires_at > now => session,
+            _ => return Err(Error::PermissionDenied("no session".to_string())),
+        };
+
+        if now - session.last_seen_at < LAST_SEEN_RESOLUTION {
+            return Ok(());
+        }
+
+        let expires_at = session_expires_at(session.created_at, now, self.ttl, self.max_lifetime);
+        if expires_at <= now {
+            data.sessions.remove(&token);
+
+            return Err(Error::PermissionDenied("session expired".to_string()));
+        }
+
+        session.last_seen_at = now;
+        session.expires_at = expires_at;
+
+        Ok(())
+    }
+
+    async fn user_sessions_list(&self, login: String) -> Result<Vec<SessionInfo>, Error> {
+        let now = Utc::now();
+
+        Ok(self
+            .data
+            .read()
+            .unwrap()
+            .sessions
+            .iter()
+            .filter(|(_, session)| session.user.login == login && session.expires_at > now)
+            .map(|(token, session)| SessionInfo {
+                id: session_id(token),
+                created_at: session.created_at,
+                last_seen_at: session.last_seen_at,
+                client_info: session.client_info.clone(),
+            })
+            .collect())
+    }
+
+    async fn user_sessions_delete(&self, login: String, id: String) -> Result<(), Error> {
+        let now = Utc::now();
+        let mut data = self.data.write().unwrap();
+
+        let token = data
+            .sessions
+            .iter()
+            .find(|(token, session)| {
+                session.user.login == login && session.expires_at > now && session_id(token) == id
+            })
+            .map(|(token, _)| token.clone())
+            .ok_or_else(|| Error::NotFound(format!("session {}", id)))?;
+
+        data.sessions.remove(&token);
+
+        Ok(())
+    }
+
+    async fn delete(&self, token: String) -> Result<(), Error> {
+        self.data.write().unwrap().sessions.remove(&token);
+
+        Ok(())
+    }
+
+    async fn login_state_save(&self, login: LoginState) -> Result<(), Error> {
+        let expires_at = Utc::now() + Duration::seconds(LOGIN_STATE_TTL as i64);
+
+        self.data
+            .write()
+            .unwrap()
+            .login_states
+            .insert(login.state, (login.verifier, expires_at));
+
+        Ok(())
+    }
+
+    async fn login_state_take(&self, state: String) -> Result<String, Error> {
+        match self.data.write().unwrap().login_states.remove(&state) {
+            Some((verifier, expires_at)) if expires_at > Utc::now() => Ok(verifier),
+            _ => Err(Error::PermissionDenied("unknown login state".to_string())),
+        }
+    }
+}
+
+#[async_trait]
+impl Denylist for MemoryRepo {
+    async fn deny(&self, id: String, ttl: Duration) -> Result<(), Error> {
+        let expires_at = Utc::now() + Duration::seconds(ttl.num_seconds().max(1));
+
+        let mut data = self.data.write().unwrap();
+        let denied = data.denied.entry(id).or_insert(expires_at);
+        *denied = (*denied).max(expires_at);
+
+        Ok(())
+    }
+
+    async fn denied(&self) -> Result<Vec<String>, Error> {
+        let now = Utc::now();
+
+        Ok(self
+            .data
+            .read()
+            .unwrap()
+            .denied
+            .iter()
+            .filter(|(_, expires_at)| **expires_at > now)
+            .map(|(id, _)| id.clone())
+            .collect())
+    }
+}
+
+/// Keeps the users who logged in in memory, they are lost when the process exits.
+#[derive(Default)]
+pub struct MemoryDirectory {
+    users: RwLock<HashMap<String, UserRecord>>,
+}
+
+impl MemoryDirectory {
+    pub fn new() -> Self {
+        MemoryDirectory::default()
+    }
+}
+
+#[async_trait]
+impl Directory for MemoryDirectory {
+    async fn users_record_login(&self, user: User) -> Result<UserRecord, Error> {
+        let now = Utc::now();
+        let mut users = self.users.write().unwrap();
+
+        let record = users
+            .entry(user.login.clone())
+            .and_modify(|record| {
+                record.id = user.id;
+                record.name = user.name.clone();
+                record.avatar_url = user.avatar_url.clone();
+
+                if !record.disabled {
+                    record.last_login_at = now;
+                }
+            })
+            .or_insert_with(|| UserRecord {
+                id: user.id,
+                login: user.login.clone(),
+                name: user.name.clone(),
+                avatar_url: user.avatar_url.clone(),
+                first_login_at: now,
+                last_login_at: now,
+                disabled: false,
+            });
+
+        Ok(record.clone())
+    }
+
+    async fn users_get(&self, login: String) -> Result<UserRecord, Error> {
+        self.users
+            .read()
+            .unwrap()
+            .get(&login)
+            .cloned()
+            .ok_or_else(|| Error::NotFound(format!("user {}", login)))
+    }
+
+    async fn users_list(&self) -> Result<Vec<UserRecord>, Error> {
+        let mut users: Vec<UserRecord> = self.users.read().unwrap().values().cloned().collect();
+        users.sort_by_key(|user| std::cmp::Reverse(user.last_login_at));
+
+        Ok(users)
+    }
+
+    async fn users_set_disabled(&self, login: String, disabled: bool) -> Result<(), Error> {
+        match self.users.write().unwrap().get_mut(&login) {
+            Some(record) => {
+                record.disabled = disabled;
+                Ok(())
+            }
+            None => Err(Error::NotFound(format!("user {}", login))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn user(login: &str) -> User {
+        User {
+            id: 123456,
+            name: "John Doe".to_string(),
+            avatar_url: "https://foo.bar".to_string(),
+            login: login.to_string(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_sessions() {
+        let repo = MemoryRepo::new(3600);
+
+        let token = repo
+            .save(user("john_doe"), ClientInfo::default())
+            .await
+            .unwrap();
+        repo.save(user("jane_doe"), ClientInfo::default())
+            .await
+            .unwrap();
+
+        assert_eq!(repo.get(token.clone()).await.unwrap(), user("john_doe"));
+        assert!(repo.refresh(token.clone()).await.is_ok());
+
+        let sessions = repo
+            .user_sessions_list("john_doe".to_string())
+            .await
+            .unwrap();
+        assert_eq!(sessions.len(), 1);
+        assert_eq!(sessions[0].id, session_id(&token));
+
+        assert!(matches!(
+            repo.user_sessions_delete("jane_doe".to_string(), session_id(&token))
+                .await,
+            Err(Error::NotFound(_))
+        ));
+        repo.user_sessions_delete("john_doe".to_string(), session_id(&token))
+            .await
+            .unwrap();
+
+        assert!(repo.get(token).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_session_expired() {
+        let repo = MemoryRepo::new(0);
+
+        let token = repo
+            .save(user("john_doe"), ClientInfo::default())
+            .await
+            .unwrap();
+
+        assert!(repo.get(token.clone()).await.is_err());
+        assert!(repo.refresh(token).await.is_err());
+        assert!(
+            repo.user_sessions_list("john_doe".to_string())
+                .await
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_login_state_taken_once() {
+        let repo = MemoryRepo::new(3600);
+        let login = LoginState::new();
+
+        repo.login_state_save(login.clone()).await.unwrap();
+
+        assert_eq!(
+            repo.login_state_take(login.state.clone()).await.unwrap(),
+            login.verifier
+        );
+        assert!(repo.login_state_take(login.state).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_deny_keeps_longest() {
+        let repo = MemoryRepo::new(3600);
+
+        repo.deny("id".to_string(), Duration::seconds(900))
+            .await
+            .unwrap();
+        repo.deny("id".to_string(), Duration::seconds(10))
+            .await
+            .unwrap();
+
+        assert!(repo.data.read().unwrap().denied["id"] > Utc::now() + Duration::seconds(800));
+        assert_eq!(repo.denied().await.unwrap(), vec!["id".to_string()]);
+    }
+
+    #[tokio::test]
+    async fn test_directory() {
+        let directory = MemoryDirectory::new();
+
+        let first = directory
+            .users_record_login(user("john_doe"))
+            .await
+            .unwrap();
+        directory
+            .users_set_disabled("john_doe".to_string(), true)
+            .await
+            .unwrap();
+
+        // disabled users keep their last login
+        let again = directory
+            .users_record_login(user("john_doe"))
+            .await
+            .unwrap();
+        assert_eq!(again.last_login_at, first.last_login_at);
+        assert!(again.disabled);
+
+        assert!(matches!(
+            directory.users_get("jane_doe".to_string()).await,
+            Err(Error::NotFound(_))
+        ));
+    }
+}
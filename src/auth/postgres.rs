@@ -0,0 +1,414 @@
+use super::signed::Denylist;
+use super::{
+    ClientInfo, Directory, LAST_SEEN_RESOLUTION, LOGIN_STATE_TTL, LoginState, Repo, SessionInfo,
+    User, UserRecord, session_expires_at, session_id,
+};
+use crate::errors::Error;
+use async_trait::async_trait;
+use chrono::{Duration, Utc};
+use sqlx::postgres::PgPool;
+use std::sync::Arc;
+use uuid::Uuid;
+
+pub struct PostgresDirectory {
+    db: Arc<PgPool>,
+}
+
+impl PostgresDirectory {
+    pub async fn new(dsn: String) -> Result<PostgresDirectory, Error> {
+        let db = match PgPool::connect(&dsn).await {
+            Ok(pool) => Arc::new(pool),
+            Err(err) => {
+                return Err(Error::ConnectionError(format!("connecting to db: {}", err)));
+            }
+        };
+
+        Ok(PostgresDirectory { db })
+    }
+}
+
+#[async_trait]
+impl Directory for PostgresDirectory {
+    async fn users_record_login(&self, user: User) -> Result<UserRecord, Error> {
+        let row = sqlx::query!(
+            r#"INSERT INTO auth.users (login, id, name, avatar_url, first_login_at, last_login_at)
+            VALUES ($1, $2, $3, $4, $5, $5)
+            ON CONFLICT (login) DO UPDATE SET
+                id = EXCLUDED.id,
+                name = EXCLUDED.name,
+                avatar_url = EXCLUDED.avatar_url,
+                last_login_at = CASE WHEN users.disabled THEN users.last_login_at ELSE EXCLUDED.last_login_at END
+            RETURNING first_login_at, last_login_at, disabled"#,
+            user.login,
+            user.id as i64,
+            user.name,
+            user.avatar_url,
+            Utc::now(),
+        )
+        .fetch_one(&*self.db)
+        .await
+        .map_err(|err| Error::ConnectionError(format!("recording login: {}", err)))?;
+
+        Ok(UserRecord {
+            id: user.id,
+            login: user.login,
+            name: user.name,
+            avatar_url: user.avatar_url,
+            first_login_at: row.first_login_at,
+            last_login_at: row.last_login_at,
+            disabled: row.disabled,
+        })
+    }
+
+    async fn users_get(&self, login: String) -> Result<UserRecord, Error> {
+        let row = sqlx::query!(
+            r#"SELECT login, id, name, avatar_url, first_login_at, last_login_at, disabled
+            FROM auth.users WHERE login = $1"#,
+            login
+        )
+        .fetch_optional(&*self.db)
+        .await
+        .map_err(|err| Error::ConnectionError(format!("fetching user: {}", err)))?;
+
+        match row {
+            Some(row) => Ok(UserRecord {
+                id: row.id as u64,
+                login: row.login,
+                name: row.name,
+                avatar_url: row.avatar_url,
+                first_login_at: row.first_login_at,
+                last_login_at: row.last_login_at,
+                disabled: row.disabled,
+            }),
+            None => Err(Error::NotFound(format!("user {}", login))),
+        }
+    }
+
+    async fn users_list(&self) -> Result<Vec<UserRecord>, Error> {
+        let rows = sqlx::query!(
+            r#"SELECT login, id, name, avatar_url, first_login_at, last_login_at, disabled
+            FROM auth.users ORDER BY last_login_at DESC"#
+        )
+        .fetch_all(&*self.db)
+        .await
+        .map_err(|err| Error::ConnectionError(format!("fetching users: {}", err)))?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| UserRecord {
+                id: row.id as u64,
+                login: row.login,
+                name: row.name,
+                avatar_url: row.avatar_url,
+                first_login_at: row.first_login_at,
+                last_login_at: row.last_login_at,
+                disabled: row.disabled,
+            })
+            .collect())
+    }
+
+    async fn users_set_disabled(&self, login: String, disabled: bool) -> Result<(), Error> {
+        let result = sqlx::query!(
+            r#"UPDATE auth.users SET disabled = $2 WHERE login = $1"#,
+            login,
+            disabled,
+        )
+        .execute(&*self.db)
+        .await
+        .map_err(|err| Error::ConnectionError(format!("updating user: {}", err)))?;
+
+        if result.rows_affected() == 0 {
+            return Err(Error::NotFound(format!("user {}", login)));
+        }
+
+        Ok(())
+    }
+}
+
+/// Sessions kept in postgres, for deployments without keydb.
+///
+/// Sessions are stored by id, a hash of their token. Expired rows are ignored when read and
+/// deleted by `sweep`, which has to be called periodically, see `sweeper`.
+pub struct PostgresRepo {
+    db: Arc<PgPool>,
+    /// Seconds a session lives without being used.
+    ttl: i64,
+    /// Seconds a session lives at most, however much it is used.
+    max_lifetime: i64,
+}
+
+impl PostgresRepo {
+    pub async fn new(dsn: String, ttl: i64) -> Result<PostgresRepo, Error> {
+        let db = match PgPool::connect(&dsn).await {
+            Ok(pool) => Arc::new(pool),
+            Err(err) => {
+                return Err(Error::ConnectionError(format!("connecting to db: {}", err)));
+            }
+        };
+
+        Ok(PostgresRepo {
+            db,
+            ttl,
+            max_lifetime: ttl,
+        })
+    }
+
+    /// Lets sessions be extended by `ttl` on use, up to `max_lifetime` seconds after login.
+    pub fn with_max_lifetime(mut self, max_lifetime: i64) -> Self {
+        self.max_lifetime = max_lifetime.max(self.ttl);
+        self
+    }
+
+    /// Deletes expired sessions, login states and denied sessions, returns how many rows went.
+    pub async fn sweep(&self) -> Result<u64, Error> {
+        let now = Utc::now();
+
+        let sessions = sqlx::query!(r#"DELETE FROM auth.sessions WHERE expires_at <= $1"#, now)
+            .execute(&*self.db)
+            .await
+            .map_err(|err| Error::ConnectionError(format!("sweeping sessions: {}", err)))?;
+
+        let login_states = sqlx::query!(
+            r#"DELETE FROM auth.login_states WHERE expires_at <= $1"#,
+            now
+        )
+        .execute(&*self.db)
+        .await
+        .map_err(|err| Error::ConnectionError(format!("sweeping login states: {}", err)))?;
+
+        let denied = sqlx::query!(
+            r#"DELETE FROM auth.denied_sessions WHERE expires_at <= $1"#,
+            now
+        )
+        .execute(&*self.db)
+        .await
+        .map_err(|err| Error::ConnectionError(format!("sweeping denied sessions: {}", err)))?;
+
+        Ok(sessions.rows_affected() + login_states.rows_affected() + denied.rows_affected())
+    }
+}
+
+/// Deletes what expired in `repo` every `interval`, until the process exits.
+pub async fn sweeper(repo: Arc<PostgresRepo>, interval: std::time::Duration) {
+    let mut ticker = tokio::time::interval(interval);
+
+    loop {
+        ticker.tick().await;
+
+        match repo.sweep().await {
+            Ok(0) => (),
+            Ok(deleted) => tracing::info!(deleted, "swept expired sessions"),
+            Err(err) => tracing::error!(error = %err, "sweeping expired sessions"),
+        }
+    }
+}
+
+#[async_trait]
+impl Repo for PostgresRepo {
+    async fn save(&self, user: User, client_info: ClientInfo) -> Result<String, Error> {
+        let token = Uuid::new_v4().to_string();
+        let now = Utc::now();
+
+        sqlx::query!(
+            r#"INSERT INTO auth.sessions (id, login, user_id, user_name, user_avatar_url, ip, user_agent, created_at, last_seen_at, expires_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $8, $9)"#,
+            session_id(&token),
+            user.login,
+            user.id as i64,
+            user.name,
+            user.avatar_url,
+            client_info.ip,
+            client_info.user_agent,
+            now,
+            now + Duration::seconds(self.ttl),
+        )
+        .execute(&*self.db)
+        .await
+        .map_err(|err| Error::ConnectionError(format!("saving session: {}", err)))?;
+
+        Ok(token)
+    }
+
+    async fn get(&self, token: String) -> Result<User, Error> {
+        let row = sqlx::query!(
+            r#"SELECT login, user_id, user_name, user_avatar_url
+            FROM auth.sessions WHERE id = $1 AND expires_at > $2"#,
+            session_id(&token),
+            Utc::now(),
+        )
+        .fetch_optional(&*self.db)
+        .await
+        .map_err(|err| Error::ConnectionError(format!("fetching session: {}", err)))?;
+
+        match row {
+            Some(row) => Ok(User {
+                id: row.user_id as u64,
+                name: row.user_name,
+                avatar_url: row.user_avatar_url,
+                login: row.login,
+            }),
+            None => Err(Error::PermissionDenied("no session".to_string())),
+        }
+    }
+
+    async fn refresh(&self, token: String) -> Result<(), Error> {
+        let id = session_id(&token);
+        let now = Utc::now();
+
+        let row = sqlx::query!(
+            r#"SELECT created_at, last_seen_at FROM auth.sessions WHERE id = $1 AND expires_at > $2"#,
+            id,
+            now,
+        )
+        .fetch_optional(&*self.db)
+        .await
+        .map_err(|err| Error::ConnectionError(format!("fetching session: {}", err)))?;
+
+        let row = row.ok_or_else(|| Error::PermissionDenied("no session".to_string()))?;
+
+        if now - row.last_seen_at < LAST_SEEN_RESOLUTION {
+            return Ok(());
+        }
+
+        let expires_at = session_expires_at(row.created_at, now, self.ttl, self.max_lifetime);
+        if expires_at <= now {
+            self.delete(token).await?;
+
+            return Err(Error::PermissionDenied("session expired".to_string()));
+        }
+
+        // the session may have been deleted meanwhile
+        let result = sqlx::query!(
+            r#"UPDATE auth.sessions SET last_seen_at = $2, expires_at = $3 WHERE id = $1"#,
+            id,
+            now,
+            expires_at,
+        )
+        .execute(&*self.db)
+        .await
+        .map_err(|err| Error::ConnectionError(format!("refreshing session: {}", err)))?;
+
+        if result.rows_affected() == 0 {
+            return Err(Error::PermissionDenied("no session".to_string()));
+        }
+
+        Ok(())
+    }
+
+    async fn user_sessions_list(&self, login: String) -> Result<Vec<SessionInfo>, Error> {
+        let rows = sqlx::query!(
+            r#"SELECT id, ip, user_agent, created_at, last_seen_at
+            FROM auth.sessions WHERE login = $1 AND expires_at > $2
+            ORDER BY last_seen_at DESC"#,
+            login,
+            Utc::now(),
+        )
+        .fetch_all(&*self.db)
+        .await
+        .map_err(|err| Error::ConnectionError(format!("fetching sessions: {}", err)))?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| SessionInfo {
+                id: row.id,
+                created_at: row.created_at,
+                last_seen_at: row.last_seen_at,
+                client_info: ClientInfo {
+                    ip: row.ip,
+                    user_agent: row.user_agent,
+                },
+            })
+            .collect())
+    }
+
+    async fn user_sessions_delete(&self, login: String, id: String) -> Result<(), Error> {
+        let result = sqlx::query!(
+            r#"DELETE FROM auth.sessions WHERE login = $1 AND id = $2 AND expires_at > $3"#,
+            login,
+            id,
+            Utc::now(),
+        )
+        .execute(&*self.db)
+        .await
+        .map_err(|err| Error::ConnectionError(format!("deleting session: {}", err)))?;
+
+        if result.rows_affected() == 0 {
+            return Err(Error::NotFound(format!("session {}", id)));
+        }
+
+        Ok(())
+    }
+
+    async fn delete(&self, token: String) -> Result<(), Error> {
+        sqlx::query!(
+            r#"DELETE FROM auth.sessions WHERE id = $1"#,
+            session_id(&token)
+        )
+        .execute(&*self.db)
+        .await
+        .map_err(|err| Error::ConnectionError(format!("deleting session: {}", err)))?;
+
+        Ok(())
+    }
+
+    async fn login_state_save(&self, login: LoginState) -> Result<(), Error> {
+        sqlx::query!(
+            r#"INSERT INTO auth.login_states (state, verifier, expires_at) VALUES ($1, $2, $3)"#,
+            login.state,
+            login.verifier,
+            Utc::now() + Duration::seconds(LOGIN_STATE_TTL as i64),
+        )
+        .execute(&*self.db)
+        .await
+        .map_err(|err| Error::ConnectionError(format!("saving login state: {}", err)))?;
+
+        Ok(())
+    }
+
+    async fn login_state_take(&self, state: String) -> Result<String, Error> {
+        let row = sqlx::query!(
+            r#"DELETE FROM auth.login_states WHERE state = $1 RETURNING verifier, expires_at"#,
+            state
+        )
+        .fetch_optional(&*self.db)
+        .await
+        .map_err(|err| Error::ConnectionError(format!("taking login state: {}", err)))?;
+
+        match row {
+            Some(row) if row.expires_at > Utc::now() => Ok(row.verifier),
+            _ => Err(Error::PermissionDenied("unknown login state".to_string())),
+        }
+    }
+}
+
+#[async_trait]
+impl Denylist for PostgresRepo {
+    async fn deny(&self, id: String, ttl: Duration) -> Result<(), Error> {
+        let expires_at = Utc::now() + Duration::seconds(ttl.num_seconds().max(1));
+
+        sqlx::query!(
+            r#"INSERT INTO auth.denied_sessions (id, expires_at) VALUES ($1, $2)
+            ON CONFLICT (id) DO UPDATE
+            SET expires_at = GREATEST(auth.denied_sessions.expires_at, EXCLUDED.expires_at)"#,
+            id,
+            expires_at,
+        )
+        .execute(&*self.db)
+        .await
+        .map_err(|err| Error::ConnectionError(format!("denying session: {}", err)))?;
+
+        Ok(())
+    }
+
+    async fn denied(&self) -> Result<Vec<String>, Error> {
+        let rows = sqlx::query!(
+            r#"SELECT id FROM auth.denied_sessions WHERE expires_at > $1"#,
+            Utc::now()
+        )
+        .fetch_all(&*self.db)
+        .await
+        .map_err(|err| Error::ConnectionError(format!("fetching denied sessions: {}", err)))?;
+
+        Ok(rows.into_iter().map(|row| row.id).collect())
+    }
+}
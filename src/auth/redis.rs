@@ -1,14 +1,107 @@
-use super::{Repo, User};
+use super::signed::Denylist;
+use super::{
+    ClientInfo, LAST_SEEN_RESOLUTION, LOGIN_STATE_TTL, LoginState, Repo, SessionInfo, User,
+    session_expires_at, session_id,
+};
 use crate::errors::Error;
 use async_trait::async_trait;
 use bb8::Pool;
 use bb8_redis::RedisConnectionManager;
-use bb8_redis::redis::AsyncCommands;
+use bb8_redis::redis::{self, AsyncCommands, ExistenceCheck, SetExpiry, SetOptions};
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use uuid::Uuid;
 
+/// Version of the session format, to bump on incompatible changes.
+const SESSION_FORMAT: u32 = 1;
+
+/// A session as stored in redis, as json.
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+struct StoredSession {
+    v: u32,
+    user: User,
+    created_at: DateTime<Utc>,
+    last_seen_at: DateTime<Utc>,
+    #[serde(flatten)]
+    client_info: ClientInfo,
+}
+
+#[derive(Debug, PartialEq)]
+enum Decoded {
+    Current(StoredSession),
+    /// Sessions saved as `id|login|avatar_url|name`, before the json format.
+    Legacy(User),
+}
+
+fn decode(data: &str) -> Result<Decoded, Error> {
+    if data.starts_with('{') {
+        let session: StoredSession = serde_json::from_str(data)
+            .map_err(|err| Error::SerializationError(format!("reading session: {}", err)))?;
+
+        if session.v != SESSION_FORMAT {
+            return Err(Error::SerializationError(format!(
+                "unsupported session format {}",
+                session.v
+            )));
+        }
+
+        return Ok(Decoded::Current(session));
+    }
+
+    let segments: Vec<&str> = data.splitn(4, '|').collect();
+    if segments.len() != 4 {
+        return Err(Error::SerializationError("Invalid data format".to_string()));
+    }
+
+    Ok(Decoded::Legacy(User {
+        id: segments[0]
+            .parse::<u64>()
+            .map_err(|_| Error::SerializationError("Invalid ID format".to_string()))?,
+        login: segments[1].to_string(),
+        avatar_url: segments[2].to_string(),
+        name: segments[3].to_string(),
+    }))
+}
+
+/// Hash of the sessions of a user, session id to token.
+fn user_sessions_key(login: &str) -> String {
+    format!("user_sessions:{}", login)
+}
+
+/// Options for rewriting a session on refresh: XX keeps a session that was
+/// logged out or revoked since it was read from coming back.
+fn refresh_options(ttl: u64) -> SetOptions {
+    SetOptions::default()
+        .conditional_set(ExistenceCheck::XX)
+        .with_expiration(SetExpiry::EX(ttl))
+}
+
+/// Converts a legacy session, which was saved for `ttl` seconds and has `ttl_left` of them
+/// left, dating the login back from there.
+fn upgrade(user: User, ttl: i64, ttl_left: i64, now: DateTime<Utc>) -> StoredSession {
+    let created_at = now - Duration::seconds(ttl - ttl_left.clamp(0, ttl));
+
+    StoredSession {
+        v: SESSION_FORMAT,
+        user,
+        created_at,
+        last_seen_at: created_at,
+        client_info: ClientInfo::default(),
+    }
+}
+
+fn encode(session: &StoredSession) -> Result<String, Error> {
+    serde_json::to_string(session)
+        .map_err(|err| Error::SerializationError(format!("writing session: {}", err)))
+}
+
 pub struct RedisRepo {
     pool: Pool<RedisConnectionManager>,
+    /// Seconds a session lives without being used.
     ttl: i64,
+    /// Seconds a session lives at most, however much it is used.
+    max_lifetime: i64,
 }
 
 impl RedisRepo {
@@ -19,37 +112,57 @@ impl RedisRepo {
         };
 
         match Pool::builder().build(manager).await {
-            Ok(pool) => return Ok(RedisRepo { pool, ttl }),
-            Err(err) => return Err(Error::ConnectionError(err.to_string())),
-        };
+            Ok(pool) => Ok(RedisRepo {
+                pool,
+                ttl,
+                max_lifetime: ttl,
+            }),
+            Err(err) => Err(Error::ConnectionError(err.to_string())),
+        }
+    }
+
+    /// Lets sessions be extended by `ttl` on use, up to `max_lifetime` seconds after login.
+    pub fn with_max_lifetime(mut self, max_lifetime: i64) -> Self {
+        self.max_lifetime = max_lifetime.max(self.ttl);
+        self
+    }
+
+    fn expires_at(&self, session: &StoredSession, now: DateTime<Utc>) -> DateTime<Utc> {
+        session_expires_at(session.created_at, now, self.ttl, self.max_lifetime)
     }
 }
 
 #[async_trait]
 impl Repo for RedisRepo {
-    async fn save(&self, user: User) -> Result<String, Error> {
+    async fn save(&self, user: User, client_info: ClientInfo) -> Result<String, Error> {
         let mut con = match self.pool.get().await {
             Ok(con) => con,
             Err(err) => return Err(Error::ConnectionError(err.to_string())),
         };
 
         let token = Uuid::new_v4().to_string();
+        let key = user_sessions_key(&user.login);
 
-        let data = format!(
-            "{}|{}|{}|{}",
-            user.id, user.login, user.avatar_url, user.name
-        );
+        let now = Utc::now();
+        let data = encode(&StoredSession {
+            v: SESSION_FORMAT,
+            user,
+            created_at: now,
+            last_seen_at: now,
+            client_info,
+        })?;
 
-        match con.set::<&str, String, ()>(token.as_str(), data).await {
-            Ok(_) => {
-                match con.expire::<&str, u32>(token.as_str(), self.ttl).await {
-                    Ok(_) => (),
-                    Err(err) => return Err(Error::ConnectionError(err.to_string())),
-                }
-                return Ok(token);
-            }
-            Err(err) => return Err(Error::ConnectionError(err.to_string())),
-        }
+        // no session outlives max_lifetime, so neither does the index of the latest one
+        redis::pipe()
+            .atomic()
+            .set_ex(token.as_str(), data, self.ttl as u64)
+            .hset(&key, session_id(&token), token.as_str())
+            .expire(&key, self.max_lifetime)
+            .query_async::<()>(&mut *con)
+            .await
+            .map_err(|err| Error::ConnectionError(err.to_string()))?;
+
+        Ok(token)
     }
 
     async fn get(&self, token: String) -> Result<User, Error> {
@@ -63,21 +176,79 @@ impl Repo for RedisRepo {
             .await
             .map_err(|_| Error::PermissionDenied("no session".to_string()))?;
 
-        let segments: Vec<&str> = data.splitn(4, '|').collect();
-        if segments.len() != 4 {
-            return Err(Error::SerializationError("Invalid data format".to_string()));
+        match decode(&data)? {
+            Decoded::Current(session) => Ok(session.user),
+            Decoded::Legacy(user) => Ok(user),
         }
+    }
 
-        let user = User {
-            id: segments[0]
-                .parse::<u64>()
-                .map_err(|_| Error::SerializationError("Invalid ID format".to_string()))?,
-            login: segments[1].to_string(),
-            avatar_url: segments[2].to_string(),
-            name: segments[3].to_string(),
+    async fn refresh(&self, token: String) -> Result<(), Error> {
+        let mut con = match self.pool.get().await {
+            Ok(con) => con,
+            Err(err) => return Err(Error::ConnectionError(err.to_string())),
         };
 
-        Ok(user)
+        let data: Option<String> = con
+            .get(token.as_str())
+            .await
+            .map_err(|err| Error::ConnectionError(err.to_string()))?;
+
+        let data = data.ok_or_else(|| Error::PermissionDenied("no session".to_string()))?;
+
+        let now = Utc::now();
+
+        let (mut session, legacy) = match decode(&data)? {
+            Decoded::Current(session) => (session, false),
+            // legacy sessions are not in the index of their user, they are rewritten and
+            // indexed on first use so that they can be listed and revoked
+            Decoded::Legacy(user) => {
+                let ttl_left: i64 = con
+                    .ttl(token.as_str())
+                    .await
+                    .map_err(|err| Error::ConnectionError(err.to_string()))?;
+
+                (upgrade(user, self.ttl, ttl_left, now), true)
+            }
+        };
+
+        if !legacy && now - session.last_seen_at < LAST_SEEN_RESOLUTION {
+            return Ok(());
+        }
+
+        let expires_at = self.expires_at(&session, now);
+        if expires_at <= now {
+            con.del::<&str, ()>(token.as_str())
+                .await
+                .map_err(|err| Error::ConnectionError(err.to_string()))?;
+
+            return Err(Error::PermissionDenied("session expired".to_string()));
+        }
+
+        session.last_seen_at = now;
+
+        let options = refresh_options((expires_at - now).num_seconds().max(1) as u64);
+
+        let mut pipe = redis::pipe();
+        pipe.atomic()
+            .set_options(token.as_str(), encode(&session)?, options);
+
+        if legacy {
+            let key = user_sessions_key(&session.user.login);
+            pipe.hset(&key, session_id(&token), token.as_str())
+                .ignore()
+                .expire(&key, self.max_lifetime)
+                .ignore();
+        }
+
+        // a nil reply means the session was deleted meanwhile
+        let (refreshed,): (Option<String>,) = pipe
+            .query_async(&mut *con)
+            .await
+            .map_err(|err| Error::ConnectionError(err.to_string()))?;
+
+        refreshed
+            .map(|_| ())
+            .ok_or_else(|| Error::PermissionDenied("no session".to_string()))
     }
 
     async fn delete(&self, token: String) -> Result<(), Error> {
@@ -86,9 +257,321 @@ impl Repo for RedisRepo {
             Err(err) => return Err(Error::ConnectionError(err.to_string())),
         };
 
-        match con.del::<&str, ()>(token.as_str()).await {
+        let data: Option<String> = con
+            .get(token.as_str())
+            .await
+            .map_err(|err| Error::ConnectionError(err.to_string()))?;
+
+        let login = match data.as_deref().map(decode) {
+            Some(Ok(Decoded::Current(session))) => Some(session.user.login),
+            Some(Ok(Decoded::Legacy(user))) => Some(user.login),
+            _ => None,
+        };
+
+        let mut pipe = redis::pipe();
+        pipe.atomic().del(token.as_str());
+
+        if let Some(login) = login {
+            pipe.hdel(user_sessions_key(&login), session_id(&token));
+        }
+
+        match pipe.query_async::<()>(&mut *con).await {
             Ok(_) => Ok(()),
             Err(err) => Err(Error::ConnectionError(err.to_string())),
         }
     }
+
+    async fn user_sessions_list(&self, login: String) -> Result<Vec<SessionInfo>, Error> {
+        let mut con = match self.pool.get().await {
+            Ok(con) => con,
+            Err(err) => return Err(Error::ConnectionError(err.to_string())),
+        };
+
+        let key = user_sessions_key(&login);
+
+        let tokens: HashMap<String, String> = con
+            .hgetall(&key)
+            .await
+            .map_err(|err| Error::ConnectionError(err.to_string()))?;
+
+        let mut sessions = Vec::with_capacity(tokens.len());
+        let mut expired = Vec::new();
+
+        for (id, token) in tokens {
+            let data: Option<String> = con
+                .get(token.as_str())
+                .await
+                .map_err(|err| Error::ConnectionError(err.to_string()))?;
+
+            match data.as_deref().map(decode) {
+                Some(Ok(Decoded::Current(session))) => sessions.push(SessionInfo {
+                    id,
+                    created_at: session.created_at,
+                    last_seen_at: session.last_seen_at,
+                    client_info: session.client_info,
+                }),
+                Some(_) => (),
+                None => expired.push(id),
+            }
+        }
+
+        if !expired.is_empty() {
+            con.hdel::<&str, Vec<String>, ()>(&key, expired)
+                .await
+                .map_err(|err| Error::ConnectionError(err.to_string()))?;
+        }
+
+        Ok(sessions)
+    }
+
+    async fn user_sessions_delete(&self, login: String, id: String) -> Result<(), Error> {
+        let mut con = match self.pool.get().await {
+            Ok(con) => con,
+            Err(err) => return Err(Error::ConnectionError(err.to_string())),
+        };
+
+        let key = user_sessions_key(&login);
+
+        let token: Option<String> = con
+            .hget(&key, id.as_str())
+            .await
+            .map_err(|err| Error::ConnectionError(err.to_string()))?;
+
+        let token = token.ok_or_else(|| Error::NotFound(format!("session {}", id)))?;
+
+        redis::pipe()
+            .atomic()
+            .del(token.as_str())
+            .hdel(&key, id.as_str())
+            .query_async::<()>(&mut *con)
+            .await
+            .map_err(|err| Error::ConnectionError(err.to_string()))
+    }
+
+    async fn login_state_save(&self, login: LoginState) -> Result<(), Error> {
+        let mut con = match self.pool.get().await {
+            Ok(con) => con,
+            Err(err) => return Err(Error::ConnectionError(err.to_string())),
+        };
+
+        let key = format!("login_state:{}", login.state);
+
+        con.set_ex::<String, String, ()>(key, login.verifier, LOGIN_STATE_TTL)
+            .await
+            .map_err(|err| Error::ConnectionError(err.to_string()))
+    }
+
+    async fn login_state_take(&self, state: String) -> Result<String, Error> {
+        let mut con = match self.pool.get().await {
+            Ok(con) => con,
+            Err(err) => return Err(Error::ConnectionError(err.to_string())),
+        };
+
+        let key = format!("login_state:{}", state);
+
+        let verifier: Option<String> = con
+            .get_del(key)
+            .await
+            .map_err(|err| Error::ConnectionError(err.to_string()))?;
+
+        verifier.ok_or_else(|| Error::PermissionDenied("unknown login state".to_string()))
+    }
+}
+
+/// Sorted set of denied session ids, scored by when they stop being denied.
+const DENIED_SESSIONS_KEY: &str = "denied_sessions";
+
+#[async_trait]
+impl Denylist for RedisRepo {
+    async fn deny(&self, id: String, ttl: Duration) -> Result<(), Error> {
+        let mut con = match self.pool.get().await {
+            Ok(con) => con,
+            Err(err) => return Err(Error::ConnectionError(err.to_string())),
+        };
+
+        let now = Utc::now().timestamp();
+        let ttl = ttl.num_seconds().max(1);
+
+        // ids are denied for different ttls, GT keeps the longest denial of an id and expired
+        // ones are dropped on the next denial
+        redis::pipe()
+            .atomic()
+            .zrembyscore(DENIED_SESSIONS_KEY, "-inf", now)
+            .cmd("ZADD")
+            .arg(DENIED_SESSIONS_KEY)
+            .arg("GT")
+            .arg(now + ttl)
+            .arg(id)
+            .query_async::<()>(&mut *con)
+            .await
+            .map_err(|err| Error::ConnectionError(err.to_string()))
+    }
+
+    async fn denied(&self) -> Result<Vec<String>, Error> {
+        let mut con = match self.pool.get().await {
+            Ok(con) => con,
+            Err(err) => return Err(Error::ConnectionError(err.to_string())),
+        };
+
+        con.zrangebyscore(
+            DENIED_SESSIONS_KEY,
+            format!("({}", Utc::now().timestamp()),
+            "+inf",
+        )
+        .await
+        .map_err(|err| Error::ConnectionError(err.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::auth::{DefaultSessionManager, SessionManager};
+    use std::sync::Arc;
+
+    fn user() -> User {
+        User {
+            id: 123456,
+            name: "John | Doe".to_string(),
+            avatar_url: "https://foo.bar/a|b".to_string(),
+            login: "john_doe".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_encode_decode() {
+        let session = StoredSession {
+            v: SESSION_FORMAT,
+            user: user(),
+            created_at: Utc::now(),
+            last_seen_at: Utc::now(),
+            client_info: ClientInfo {
+                ip: Some("127.0.0.1".to_string()),
+                user_agent: Some("curl/8.0".to_string()),
+            },
+        };
+
+        let data = encode(&session).unwrap();
+
+        assert!(data.contains(r#""ip":"127.0.0.1""#));
+        assert_eq!(decode(&data).unwrap(), Decoded::Current(session));
+    }
+
+    #[test]
+    fn test_refresh_only_existing() {
+        let args: Vec<String> = redis::ToRedisArgs::to_redis_args(&refresh_options(60))
+            .into_iter()
+            .map(|arg| String::from_utf8(arg).unwrap())
+            .collect();
+
+        assert_eq!(args, vec!["XX", "EX", "60"]);
+    }
+
+    #[tokio::test]
+    async fn test_expires_at() {
+        // the pool connects lazily, no redis is needed
+        let repo = RedisRepo::new("redis://127.0.0.1:1", 3600)
+            .await
+            .unwrap()
+            .with_max_lifetime(86400);
+
+        let now = Utc::now();
+        let mut session = StoredSession {
+            v: SESSION_FORMAT,
+            user: user(),
+            created_at: now,
+            last_seen_at: now,
+            client_info: ClientInfo::default(),
+        };
+
+        assert_eq!(repo.expires_at(&session, now), now + Duration::hours(1));
+
+        session.created_at = now - Duration::minutes(23 * 60 + 30);
+        assert_eq!(repo.expires_at(&session, now), now + Duration::minutes(30));
+    }
+
+    #[test]
+    fn test_decode_legacy() {
+        let decoded = decode("123456|john_doe|https://foo.bar|John | Doe").unwrap();
+
+        assert_eq!(
+            decoded,
+            Decoded::Legacy(User {
+                avatar_url: "https://foo.bar".to_string(),
+                ..user()
+            })
+        );
+        assert!(decode("abc|john_doe|https://foo.bar|John").is_err());
+        assert!(decode("john_doe").is_err());
+    }
+
+    #[test]
+    fn test_upgrade_legacy() {
+        let now = Utc::now();
+
+        let session = upgrade(user(), 3600, 600, now);
+        assert_eq!(session.created_at, now - Duration::minutes(50));
+        assert_eq!(session.user, user());
+
+        // a session without expiry is taken as just created
+        assert_eq!(
+            upgrade(user(), 3600, -1, now).created_at,
+            now - Duration::hours(1)
+        );
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a redis server at REDIS_URL"]
+    async fn test_revoke_all_legacy() {
+        let url = std::env::var("REDIS_URL").unwrap();
+        let repo = Arc::new(RedisRepo::new(&url, 3600).await.unwrap());
+
+        let user = User {
+            login: format!("legacy_{}", Uuid::new_v4().simple()),
+            // the legacy format cannot hold a | outside of the name
+            avatar_url: "https://foo.bar".to_string(),
+            ..user()
+        };
+
+        let current = repo
+            .save(user.clone(), ClientInfo::default())
+            .await
+            .unwrap();
+
+        let legacy = Uuid::new_v4().to_string();
+        let mut con = repo.pool.get().await.unwrap();
+        con.set_ex::<&str, String, ()>(
+            legacy.as_str(),
+            format!(
+                "{}|{}|{}|{}",
+                user.id, user.login, user.avatar_url, user.name
+            ),
+            3600,
+        )
+        .await
+        .unwrap();
+        drop(con);
+
+        let sessions = DefaultSessionManager::new(repo.clone());
+        assert_eq!(sessions.session(legacy.clone()).await.unwrap(), user);
+        assert_eq!(
+            sessions.sessions(user.login.clone()).await.unwrap().len(),
+            2
+        );
+
+        sessions.revoke_all(user.login.clone(), None).await.unwrap();
+
+        assert!(repo.get(current).await.is_err());
+        assert!(repo.get(legacy).await.is_err());
+    }
+
+    #[test]
+    fn test_decode_unknown_version() {
+        let data = r#"{"v":2,"user":{"id":1,"name":"","avatar_url":"","login":""},"created_at":"2026-01-01T00:00:00Z","last_seen_at":"2026-01-01T00:00:00Z"}"#;
+
+        assert_eq!(
+            decode(data).unwrap_err().to_string(),
+            "serialization error: unsupported session format 2"
+        );
+    }
 }
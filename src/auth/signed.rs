@@ -0,0 +1,604 @@
+use super::tokens::ApiToken;
+use super::{Session, SessionInfo, SessionManager, User, session_id};
+use crate::errors::Error;
+use async_trait::async_trait;
+use chrono::{DateTime, Duration, Utc};
+use jsonwebtoken::{
+    Algorithm, DecodingKey, EncodingKey, Header, Validation, decode, decode_header, encode,
+};
+use mockall::predicate::*;
+use mockall::*;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, RwLock};
+
+/// A secret signing session tokens, identified by the `kid` of the tokens it signs.
+#[derive(Clone, Debug)]
+pub struct SigningKey {
+    pub id: String,
+    pub secret: String,
+}
+
+/// Sessions revoked while signed tokens for them may still be valid.
+#[automock]
+#[async_trait]
+pub trait Denylist: Send + Sync {
+    /// Rejects the session `id` for the next `ttl`.
+    async fn deny(&self, id: String, ttl: Duration) -> Result<(), Error>;
+    /// Ids of the sessions rejected right now.
+    async fn denied(&self) -> Result<Vec<String>, Error>;
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+struct Claims {
+    sub: String,
+    uid: u64,
+    name: String,
+    avatar_url: String,
+    /// Token of the session in the repo, which decides whether an expired token is renewed.
+    sst: String,
+    iat: i64,
+    exp: i64,
+}
+
+impl Claims {
+    fn user(self) -> User {
+        User {
+            id: self.uid,
+            name: self.name,
+            avatar_url: self.avatar_url,
+            login: self.sub,
+        }
+    }
+}
+
+/// Hands out sessions as short-lived signed tokens, verified locally and rejected once expired.
+///
+/// Each token wraps a session of `inner`, which is only consulted to renew the token, expired
+/// ones included. Revoked sessions are kept in the denylist until their tokens have expired,
+/// and checked against a copy of it, so verifying a token takes no I/O: revocations made by
+/// other processes apply once `refresh_denied` has reloaded it. Users are not looked up in the
+/// directory while their token is valid, disabling a user revokes their sessions instead.
+pub struct SignedSessionManager {
+    inner: Arc<dyn SessionManager>,
+    denylist: Arc<dyn Denylist>,
+    denied: RwLock<Denied>,
+    keys: Vec<SigningKey>,
+    ttl: Duration,
+}
+
+/// Copy of the denylist.
+#[derive(Default)]
+struct Denied {
+    /// As last loaded from the denylist.
+    loaded: HashSet<String>,
+    /// Denied by this process, until their tokens have expired, whether loaded yet or not.
+    local: HashMap<String, DateTime<Utc>>,
+}
+
+impl Denied {
+    fn contains(&self, id: &str) -> bool {
+        self.loaded.contains(id)
+            || matches!(self.local.get(id), Some(expires_at) if *expires_at > Utc::now())
+    }
+}
+
+impl SignedSessionManager {
+    /// Tokens are signed with the first of `keys`, the others are only used to verify tokens
+    /// signed before a rotation.
+    pub fn new(
+        inner: Arc<dyn SessionManager>,
+        denylist: Arc<dyn Denylist>,
+        keys: Vec<SigningKey>,
+        ttl: i64,
+    ) -> Result<Self, Error> {
+        if keys.is_empty() {
+            return Err(Error::InitializationError(
+                "at least one signing key is required".to_string(),
+            ));
+        }
+
+        if keys.iter().any(|key| key.secret.len() < 32) {
+            return Err(Error::InitializationError(
+                "signing keys must be at least 32 characters".to_string(),
+            ));
+        }
+
+        Ok(SignedSessionManager {
+            inner,
+            denylist,
+            denied: RwLock::new(Denied::default()),
+            keys,
+            ttl: Duration::seconds(ttl),
+        })
+    }
+
+    fn sign(&self, user: User, session_token: String) -> Result<String, Error> {
+        let key = &self.keys[0];
+        let now = Utc::now();
+
+        let claims = Claims {
+            sub: user.login,
+            uid: user.id,
+            name: user.name,
+            avatar_url: user.avatar_url,
+            sst: session_token,
+            iat: now.timestamp(),
+            exp: (now + self.ttl).timestamp(),
+        };
+
+        let mut header = Header::new(Algorithm::HS256);
+        header.kid = Some(key.id.clone());
+
+        encode(
+            &header,
+            &claims,
+            &EncodingKey::from_secret(key.secret.as_bytes()),
+        )
+        .map_err(|err| Error::SerializationError(format!("signing session: {}", err)))
+    }
+
+    /// Reloads the copy of the denylist that tokens are checked against.
+    pub async fn refresh_denied(&self) -> Result<(), Error> {
+        let loaded = self.denylist.denied().await?;
+
+        let mut denied = self.denied.write().unwrap();
+        denied.loaded = loaded.into_iter().collect();
+        denied
+            .local
+            .retain(|_, expires_at| *expires_at > Utc::now());
+
+        Ok(())
+    }
+
+    async fn deny(&self, id: String) -> Result<(), Error> {
+        self.denied
+            .write()
+            .unwrap()
+            .local
+            .insert(id.clone(), Utc::now() + self.ttl);
+
+        self.denylist.deny(id, self.ttl).await
+    }
+
+    /// Checks the signature of `token`, expired tokens are returned too.
+    fn verify(&self, token: &str) -> Result<Claims, Error> {
+        let header = decode_header(token).map_err(|_| invalid_session())?;
+
+        let key = self
+            .keys
+            .iter()
+            .find(|key| Some(&key.id) == header.kid.as_ref())
+            .ok_or_else(|| {
+                Error::PermissionDenied("session signed with an unknown key".to_string())
+            })?;
+
+        let mut validation = Validation::new(Algorithm::HS256);
+        validation.validate_exp = false;
+        validation.set_required_spec_claims(&["exp", "sub"]);
+
+        decode::<Claims>(
+            token,
+            &DecodingKey::from_secret(key.secret.as_bytes()),
+            &validation,
+        )
+        .map(|data| data.claims)
+        .map_err(|_| invalid_session())
+    }
+}
+
+fn invalid_session() -> Error {
+    Error::PermissionDenied("invalid session".to_string())
+}
+
+/// Reloads the denylist of `manager` every `interval`.
+///
+/// Runs until the process exits; failures are logged and retried on the next tick.
+pub async fn refresher(manager: Arc<SignedSessionManager>, interval: std::time::Duration) {
+    let mut ticker = tokio::time::interval(interval);
+
+    loop {
+        ticker.tick().await;
+
+        if let Err(err) = manager.refresh_denied().await {
+            tracing::error!(error = %err, "refreshing the session denylist");
+        }
+    }
+}
+
+#[async_trait]
+impl SessionManager for SignedSessionManager {
+    async fn session(&self, token: String) -> Result<User, Error> {
+        let claims = self.verify(&token)?;
+
+        // only `renew` checks the session in the repo, which `/api/auth/me` does
+        if claims.exp <= Utc::now().timestamp() {
+            return Err(Error::PermissionDenied("session expired".to_string()));
+        }
+
+        if self
+            .denied
+            .read()
+            .unwrap()
+            .contains(&session_id(&claims.sst))
+        {
+            return Err(Error::PermissionDenied("session revoked".to_string()));
+        }
+
+        Ok(claims.user())
+    }
+
+    async fn issue(&self, session: Session) -> Result<Session, Error> {
+        let session = self.inner.issue(session).await?;
+        let token = self.sign(session.user.clone(), session.token)?;
+
+        Ok(Session {
+            user: session.user,
+            token,
+        })
+    }
+
+    /// Replaces tokens past half their lifetime or expired, as long as their session is still valid.
+    async fn renew(&self, token: String) -> Result<Option<String>, Error> {
+        let claims = self.verify(&token)?;
+
+        if claims.exp - Utc::now().timestamp() > self.ttl.num_seconds() / 2 {
+            return Ok(None);
+        }
+
+        let user = self.inner.session(claims.sst.clone()).await?;
+
+        self.sign(user, claims.sst).map(Some)
+    }
+
+    fn id(&self, token: &str) -> Result<String, Error> {
+        self.inner.id(&self.verify(token)?.sst)
+    }
+
+    /// Derived from the inner session, so that renewed tokens keep it.
+    fn csrf_token(&self, token: &str) -> Result<String, Error> {
+        self.inner.csrf_token(&self.verify(token)?.sst)
+    }
+
+    async fn api_token(&self, token: String) -> Result<ApiToken, Error> {
+        self.inner.api_token(token).await
+    }
+
+    async fn logout(&self, token: String) -> Result<(), Error> {
+        let claims = self.verify(&token)?;
+
+        self.deny(session_id(&claims.sst)).await?;
+        self.inner.logout(claims.sst).await
+    }
+
+    async fn sessions(&self, login: String) -> Result<Vec<SessionInfo>, Error> {
+        self.inner.sessions(login).await
+    }
+
+    async fn revoke(&self, login: String, id: String) -> Result<(), Error> {
+        // only deny sessions that belong to the user
+        self.inner.revoke(login, id.clone()).await?;
+        self.deny(id).await
+    }
+
+    async fn revoke_all(&self, login: String, keep: Option<String>) -> Result<(), Error> {
+        for session in self.inner.sessions(login.clone()).await? {
+            if keep.as_ref() != Some(&session.id) {
+                self.revoke(login.clone(), session.id).await?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::auth::MockSessionManager;
+    use crate::web::csrf;
+    use actix_web::cookie::Cookie;
+    use actix_web::middleware::from_fn;
+    use actix_web::{App, HttpResponse, http::StatusCode, web};
+
+    fn user() -> User {
+        User {
+            id: 123456,
+            name: "John Doe".to_string(),
+            avatar_url: "https://foo.bar".to_string(),
+            login: "john_doe".to_string(),
+        }
+    }
+
+    fn key(id: &str) -> SigningKey {
+        SigningKey {
+            id: id.to_string(),
+            secret: format!("{}-0123456789abcdef0123456789abcdef", id),
+        }
+    }
+
+    fn manager(
+        inner: MockSessionManager,
+        denylist: MockDenylist,
+        keys: Vec<SigningKey>,
+    ) -> SignedSessionManager {
+        SignedSessionManager::new(Arc::new(inner), Arc::new(denylist), keys, 900).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_session_verified_locally() {
+        // neither the inner manager nor the denylist is asked about a valid token
+        let signed = manager(
+            MockSessionManager::new(),
+            MockDenylist::new(),
+            vec![key("k1")],
+        );
+
+        let token = signed.sign(user(), "session_token".to_string()).unwrap();
+
+        assert_eq!(signed.session(token).await.unwrap(), user());
+    }
+
+    #[tokio::test]
+    async fn test_session_key_rotation() {
+        let old = manager(
+            MockSessionManager::new(),
+            MockDenylist::new(),
+            vec![key("k1")],
+        );
+        let token = old.sign(user(), "session_token".to_string()).unwrap();
+
+        let rotated = manager(
+            MockSessionManager::new(),
+            MockDenylist::new(),
+            vec![key("k2"), key("k1")],
+        );
+        assert!(rotated.session(token.clone()).await.is_ok());
+
+        let retired = manager(
+            MockSessionManager::new(),
+            MockDenylist::new(),
+            vec![key("k2")],
+        );
+        assert_eq!(
+            retired.session(token).await.unwrap_err().to_string(),
+            "permission denied: session signed with an unknown key"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_session_tampered() {
+        let signed = manager(
+            MockSessionManager::new(),
+            MockDenylist::new(),
+            vec![key("k1")],
+        );
+        let forged = SignedSessionManager::new(
+            Arc::new(MockSessionManager::new()),
+            Arc::new(MockDenylist::new()),
+            vec![SigningKey {
+                id: "k1".to_string(),
+                secret: "not-the-secret-0123456789abcdef0123".to_string(),
+            }],
+            900,
+        )
+        .unwrap()
+        .sign(user(), "session_token".to_string())
+        .unwrap();
+
+        assert_eq!(
+            signed.session(forged).await.unwrap_err().to_string(),
+            "permission denied: invalid session"
+        );
+        assert!(signed.session("session_token".to_string()).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_session_denied() {
+        let mut denylist = MockDenylist::new();
+        denylist
+            .expect_denied()
+            .times(1)
+            .returning(|| Ok(vec![session_id("session_token")]));
+
+        let signed = manager(MockSessionManager::new(), denylist, vec![key("k1")]);
+        let token = signed.sign(user(), "session_token".to_string()).unwrap();
+        assert!(signed.session(token.clone()).await.is_ok());
+
+        signed.refresh_denied().await.unwrap();
+        assert_eq!(
+            signed.session(token).await.unwrap_err().to_string(),
+            "permission denied: session revoked"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_session_expired() {
+        let signed = SignedSessionManager::new(
+            Arc::new(MockSessionManager::new()),
+            Arc::new(MockDenylist::new()),
+            vec![key("k1")],
+            -60,
+        )
+        .unwrap();
+        let token = signed.sign(user(), "session_token".to_string()).unwrap();
+
+        assert_eq!(
+            signed.session(token).await.unwrap_err().to_string(),
+            "permission denied: session expired"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_renew() {
+        let mut inner = MockSessionManager::new();
+        inner
+            .expect_session()
+            .withf(|token| token == "session_token")
+            .times(1)
+            .returning(|_| Ok(user()));
+
+        let signed = manager(inner, MockDenylist::new(), vec![key("k1")]);
+
+        let fresh = signed.sign(user(), "session_token".to_string()).unwrap();
+        assert_eq!(signed.renew(fresh).await.unwrap(), None);
+
+        let expired = SignedSessionManager::new(
+            Arc::new(MockSessionManager::new()),
+            Arc::new(MockDenylist::new()),
+            vec![key("k1")],
+            -60,
+        )
+        .unwrap()
+        .sign(user(), "session_token".to_string())
+        .unwrap();
+
+        let renewed = signed.renew(expired).await.unwrap().unwrap();
+        let claims = signed.verify(&renewed).unwrap();
+
+        assert_eq!(claims.sst, "session_token");
+        assert!(claims.exp > Utc::now().timestamp());
+    }
+
+    #[actix_web::test]
+    async fn test_csrf_token_kept_on_renew() {
+        let mut inner = MockSessionManager::new();
+        inner.expect_session().returning(|_| Ok(user()));
+        inner
+            .expect_csrf_token()
+            .returning(|token| Ok(csrf::token(token)));
+
+        let signed = Arc::new(manager(inner, MockDenylist::new(), vec![key("k1")]));
+        let expired = SignedSessionManager::new(
+            Arc::new(MockSessionManager::new()),
+            Arc::new(MockDenylist::new()),
+            vec![key("k1")],
+            -60,
+        )
+        .unwrap()
+        .sign(user(), "session_token".to_string())
+        .unwrap();
+
+        // given out to a tab before another one renewed the token
+        let csrf_token = signed.csrf_token(&expired).unwrap();
+        let renewed = signed.renew(expired).await.unwrap().unwrap();
+        assert_eq!(signed.csrf_token(&renewed).unwrap(), csrf_token);
+
+        let sessions: Arc<dyn SessionManager> = signed;
+        let app = actix_web::test::init_service(
+            App::new()
+                .wrap(from_fn(move |req, next| {
+                    csrf::protect("sid".to_string(), sessions.clone(), req, next)
+                }))
+                .route("/", web::post().to(HttpResponse::Ok)),
+        )
+        .await;
+
+        let req = actix_web::test::TestRequest::post()
+            .uri("/")
+            .cookie(Cookie::new("sid", renewed))
+            .insert_header((csrf::HEADER, csrf_token))
+            .to_request();
+        assert_eq!(
+            actix_web::test::call_service(&app, req).await.status(),
+            StatusCode::OK
+        );
+    }
+
+    #[tokio::test]
+    async fn test_issue_and_logout() {
+        let mut inner = MockSessionManager::new();
+        inner.expect_issue().returning(Ok);
+        inner
+            .expect_logout()
+            .withf(|token| token == "session_token")
+            .times(1)
+            .returning(|_| Ok(()));
+        inner.expect_id().returning(|token| Ok(session_id(token)));
+
+        let mut denylist = MockDenylist::new();
+        denylist
+            .expect_deny()
+            .withf(|id, ttl| *id == session_id("session_token") && *ttl == Duration::seconds(900))
+            .times(1)
+            .returning(|_, _| Ok(()));
+
+        let signed = manager(inner, denylist, vec![key("k1")]);
+        let session = signed
+            .issue(Session {
+                user: user(),
+                token: "session_token".to_string(),
+            })
+            .await
+            .unwrap();
+
+        assert_ne!(session.token, "session_token");
+        assert_eq!(
+            signed.id(&session.token).unwrap(),
+            session_id("session_token")
+        );
+        assert!(signed.logout(session.token.clone()).await.is_ok());
+
+        // denied right away in this process, before the denylist is reloaded
+        assert_eq!(
+            signed.session(session.token).await.unwrap_err().to_string(),
+            "permission denied: session revoked"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_revoke_denies_only_owned_sessions() {
+        let mut inner = MockSessionManager::new();
+        inner
+            .expect_revoke()
+            .withf(|_, id| id == "mine")
+            .returning(|_, _| Ok(()));
+        inner
+            .expect_revoke()
+            .returning(|_, id| Err(Error::NotFound(format!("session {}", id))));
+
+        let mut denylist = MockDenylist::new();
+        denylist
+            .expect_deny()
+            .withf(|id, _| id == "mine")
+            .times(1)
+            .returning(|_, _| Ok(()));
+
+        let signed = manager(inner, denylist, vec![key("k1")]);
+
+        assert!(
+            signed
+                .revoke("john_doe".to_string(), "mine".to_string())
+                .await
+                .is_ok()
+        );
+        assert!(
+            signed
+                .revoke("john_doe".to_string(), "theirs".to_string())
+                .await
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_new_validates_keys() {
+        let new = |keys| {
+            SignedSessionManager::new(
+                Arc::new(MockSessionManager::new()),
+                Arc::new(MockDenylist::new()),
+                keys,
+                900,
+            )
+        };
+
+        assert!(new(vec![]).is_err());
+        assert!(
+            new(vec![SigningKey {
+                id: "k1".to_string(),
+                secret: "short".to_string(),
+            }])
+            .is_err()
+        );
+        assert!(new(vec![key("k1")]).is_ok());
+    }
+}
@@ -0,0 +1,303 @@
+use super::{User, random_string, session_id};
+use crate::errors::Error;
+use async_trait::async_trait;
+use chrono::{DateTime, Duration, Utc};
+use mockall::predicate::*;
+use mockall::*;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+pub mod memory;
+pub mod postgres;
+
+/// Prefix of API tokens, so they are easy to tell apart from session tokens and to find in leaked code.
+const PREFIX: &str = "blog_";
+
+/// Lifetime of a token created without an explicit one.
+pub const DEFAULT_TTL: Duration = Duration::days(30);
+/// Tokens cannot be created for longer than this.
+pub const MAX_TTL: Duration = Duration::days(365);
+
+/// What an API token may be used for, on top of the role of its user.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Scope {
+    /// Reading articles, tags and revisions.
+    Read,
+    /// Creating articles, changing their content and tags.
+    Write,
+    /// Publishing, scheduling, trashing and deleting articles.
+    Publish,
+    /// Managing roles and users.
+    Admin,
+}
+
+impl std::fmt::Display for Scope {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Scope::Read => write!(f, "read"),
+            Scope::Write => write!(f, "write"),
+            Scope::Publish => write!(f, "publish"),
+            Scope::Admin => write!(f, "admin"),
+        }
+    }
+}
+
+impl std::str::FromStr for Scope {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "read" => Ok(Scope::Read),
+            "write" => Ok(Scope::Write),
+            "publish" => Ok(Scope::Publish),
+            "admin" => Ok(Scope::Admin),
+            _ => Err(Error::SerializationError(format!("unknown scope {}", s))),
+        }
+    }
+}
+
+/// A personal API token, the token itself is only known when it is created.
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct ApiToken {
+    pub id: Uuid,
+    /// The user the token acts as, as they were when it was created.
+    #[serde(skip)]
+    pub user: User,
+    pub name: String,
+    pub scopes: Vec<Scope>,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+}
+
+#[automock]
+#[async_trait]
+pub trait TokenRepo: Send + Sync {
+    async fn tokens_create(&self, token: ApiToken, token_hash: String) -> Result<(), Error>;
+    async fn tokens_get(&self, token_hash: String) -> Result<ApiToken, Error>;
+    /// Tokens of a user, including expired ones, most recent first.
+    async fn tokens_list(&self, login: String) -> Result<Vec<ApiToken>, Error>;
+    /// Fails with `NotFound` when the user has no token `id`.
+    async fn tokens_delete(&self, login: String, id: Uuid) -> Result<(), Error>;
+}
+
+pub struct ApiTokens {
+    repo: Arc<dyn TokenRepo>,
+}
+
+impl ApiTokens {
+    pub fn new(repo: Arc<dyn TokenRepo>) -> Self {
+        ApiTokens { repo }
+    }
+
+    /// Creates a token acting as `user`, returned along with the token to hand over.
+    pub async fn create(
+        &self,
+        user: User,
+        name: String,
+        scopes: Vec<Scope>,
+        ttl: Option<Duration>,
+    ) -> Result<(ApiToken, String), Error> {
+        if name.trim().is_empty() {
+            return Err(Error::InvalidInput("name is required".to_string()));
+        }
+
+        let mut unique = Vec::new();
+        for scope in scopes {
+            if !unique.contains(&scope) {
+                unique.push(scope);
+            }
+        }
+        let scopes = unique;
+
+        if scopes.is_empty() {
+            return Err(Error::InvalidInput(
+                "at least one scope is required".to_string(),
+            ));
+        }
+
+        let ttl = ttl.unwrap_or(DEFAULT_TTL);
+        if ttl <= Duration::zero() || ttl > MAX_TTL {
+            return Err(Error::InvalidInput(format!(
+                "tokens expire within {} days",
+                MAX_TTL.num_days()
+            )));
+        }
+
+        let now = Utc::now();
+        let token = ApiToken {
+            id: Uuid::new_v4(),
+            user,
+            name: name.trim().to_string(),
+            scopes,
+            created_at: now,
+            expires_at: now + ttl,
+        };
+
+        let secret = format!("{}{}", PREFIX, random_string());
+        self.repo
+            .tokens_create(token.clone(), session_id(&secret))
+            .await?;
+
+        Ok((token, secret))
+    }
+
+    /// Finds the token `secret` was handed out for, unless it has expired.
+    pub async fn authenticate(&self, secret: String) -> Result<ApiToken, Error> {
+        if !secret.starts_with(PREFIX) {
+            return Err(invalid_token());
+        }
+
+        let token = match self.repo.tokens_get(session_id(&secret)).await {
+            Ok(token) => token,
+            Err(Error::NotFound(_)) => return Err(invalid_token()),
+            Err(err) => return Err(err),
+        };
+
+        if token.expires_at <= Utc::now() {
+            return Err(Error::PermissionDenied("api token expired".to_string()));
+        }
+
+        Ok(token)
+    }
+
+    pub async fn list(&self, login: String) -> Result<Vec<ApiToken>, Error> {
+        self.repo.tokens_list(login).await
+    }
+
+    pub async fn revoke(&self, login: String, id: Uuid) -> Result<(), Error> {
+        self.repo.tokens_delete(login, id).await
+    }
+}
+
+fn invalid_token() -> Error {
+    Error::PermissionDenied("invalid api token".to_string())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn user() -> User {
+        User {
+            id: 1,
+            name: "John Doe".to_string(),
+            avatar_url: "https://foo.bar".to_string(),
+            login: "john_doe".to_string(),
+        }
+    }
+
+    fn token(expires_at: DateTime<Utc>) -> ApiToken {
+        ApiToken {
+            id: Uuid::new_v4(),
+            user: user(),
+            name: "ci".to_string(),
+            scopes: vec![Scope::Read],
+            created_at: Utc::now() - Duration::days(1),
+            expires_at,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_create_stores_hash() {
+        let mut repo = MockTokenRepo::new();
+        repo.expect_tokens_create()
+            .withf(|token, hash| {
+                token.name == "ci"
+                    && token.scopes == vec![Scope::Read, Scope::Write]
+                    && token.expires_at - token.created_at == DEFAULT_TTL
+                    && !hash.starts_with(PREFIX)
+            })
+            .times(1)
+            .returning(|_, _| Ok(()));
+
+        let tokens = ApiTokens::new(Arc::new(repo));
+        let (token, secret) = tokens
+            .create(
+                user(),
+                " ci ".to_string(),
+                vec![Scope::Read, Scope::Write, Scope::Write],
+                None,
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(token.user, user());
+        assert!(secret.starts_with(PREFIX));
+    }
+
+    #[tokio::test]
+    async fn test_create_validates() {
+        let tokens = ApiTokens::new(Arc::new(MockTokenRepo::new()));
+
+        for (name, scopes, ttl) in [
+            ("", vec![Scope::Read], None),
+            ("ci", vec![], None),
+            ("ci", vec![Scope::Read], Some(Duration::days(366))),
+            ("ci", vec![Scope::Read], Some(Duration::days(-1))),
+        ] {
+            assert!(matches!(
+                tokens.create(user(), name.to_string(), scopes, ttl).await,
+                Err(Error::InvalidInput(_))
+            ));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_authenticate() {
+        let valid = token(Utc::now() + Duration::days(1));
+        let expected = valid.clone();
+
+        let mut repo = MockTokenRepo::new();
+        repo.expect_tokens_get()
+            .with(eq(session_id("blog_valid")))
+            .returning(move |_| Ok(valid.clone()));
+        repo.expect_tokens_get()
+            .with(eq(session_id("blog_expired")))
+            .returning(|_| Ok(token(Utc::now() - Duration::days(1))));
+        repo.expect_tokens_get()
+            .returning(|_| Err(Error::NotFound("token".to_string())));
+
+        let tokens = ApiTokens::new(Arc::new(repo));
+
+        assert_eq!(
+            tokens.authenticate("blog_valid".to_string()).await.unwrap(),
+            expected
+        );
+        assert_eq!(
+            tokens
+                .authenticate("blog_expired".to_string())
+                .await
+                .unwrap_err()
+                .to_string(),
+            "permission denied: api token expired"
+        );
+        assert_eq!(
+            tokens
+                .authenticate("blog_unknown".to_string())
+                .await
+                .unwrap_err()
+                .to_string(),
+            "permission denied: invalid api token"
+        );
+        assert_eq!(
+            tokens
+                .authenticate("session_token".to_string())
+                .await
+                .unwrap_err()
+                .to_string(),
+            "permission denied: invalid api token"
+        );
+    }
+
+    #[test]
+    fn test_scope_round_trip() {
+        for scope in [Scope::Read, Scope::Write, Scope::Publish, Scope::Admin] {
+            assert_eq!(scope.to_string().parse::<Scope>().unwrap(), scope);
+        }
+
+        assert!("root".parse::<Scope>().is_err());
+    }
+}
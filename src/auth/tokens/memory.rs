@@ -0,0 +1,64 @@
+use super::{ApiToken, TokenRepo};
+use crate::errors::Error;
+use async_trait::async_trait;
+use std::collections::HashMap;
+use std::sync::RwLock;
+use uuid::Uuid;
+
+/// Keeps API tokens in memory, by hash, they are lost when the process exits.
+#[derive(Default)]
+pub struct MemoryTokenRepo {
+    tokens: RwLock<HashMap<String, ApiToken>>,
+}
+
+impl MemoryTokenRepo {
+    pub fn new() -> Self {
+        MemoryTokenRepo::default()
+    }
+}
+
+#[async_trait]
+impl TokenRepo for MemoryTokenRepo {
+    async fn tokens_create(&self, token: ApiToken, token_hash: String) -> Result<(), Error> {
+        self.tokens.write().unwrap().insert(token_hash, token);
+
+        Ok(())
+    }
+
+    async fn tokens_get(&self, token_hash: String) -> Result<ApiToken, Error> {
+        self.tokens
+            .read()
+            .unwrap()
+            .get(&token_hash)
+            .cloned()
+            .ok_or_else(|| Error::NotFound("api token".to_string()))
+    }
+
+    async fn tokens_list(&self, login: String) -> Result<Vec<ApiToken>, Error> {
+        let mut tokens: Vec<ApiToken> = self
+            .tokens
+            .read()
+            .unwrap()
+            .values()
+            .filter(|token| token.user.login == login)
+            .cloned()
+            .collect();
+
+        tokens.sort_by_key(|token| std::cmp::Reverse(token.created_at));
+
+        Ok(tokens)
+    }
+
+    async fn tokens_delete(&self, login: String, id: Uuid) -> Result<(), Error> {
+        let mut tokens = self.tokens.write().unwrap();
+        let before = tokens.len();
+
+        tokens.retain(|_, token| token.user.login != login || token.id != id);
+
+        if tokens.len() == before {
+            return Err(Error::NotFound(format!("api token {}", id)));
+        }
+
+        Ok(())
+    }
+}
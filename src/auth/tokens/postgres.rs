@@ -0,0 +1,155 @@
+use super::{ApiToken, Scope, TokenRepo};
+use crate::auth::User;
+use crate::errors::Error;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use sqlx::postgres::PgPool;
+use std::sync::Arc;
+use uuid::Uuid;
+
+pub struct PostgresTokenRepo {
+    db: Arc<PgPool>,
+}
+
+impl PostgresTokenRepo {
+    pub async fn new(dsn: String) -> Result<PostgresTokenRepo, Error> {
+        let db = match PgPool::connect(&dsn).await {
+            Ok(pool) => Arc::new(pool),
+            Err(err) => {
+                return Err(Error::ConnectionError(format!("connecting to db: {}", err)));
+            }
+        };
+
+        Ok(PostgresTokenRepo { db })
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn token_from_row(
+    id: Uuid,
+    login: String,
+    user_id: i64,
+    user_name: String,
+    user_avatar_url: String,
+    name: String,
+    scopes: Vec<String>,
+    created_at: DateTime<Utc>,
+    expires_at: DateTime<Utc>,
+) -> Result<ApiToken, Error> {
+    Ok(ApiToken {
+        id,
+        user: User {
+            id: user_id as u64,
+            name: user_name,
+            avatar_url: user_avatar_url,
+            login,
+        },
+        name,
+        scopes: scopes
+            .iter()
+            .map(|scope| scope.parse::<Scope>())
+            .collect::<Result<_, _>>()?,
+        created_at,
+        expires_at,
+    })
+}
+
+#[async_trait]
+impl TokenRepo for PostgresTokenRepo {
+    async fn tokens_create(&self, token: ApiToken, token_hash: String) -> Result<(), Error> {
+        sqlx::query!(
+            r#"INSERT INTO auth.api_tokens
+            (id, token_hash, login, user_id, user_name, user_avatar_url, name, scopes, created_at, expires_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)"#,
+            token.id,
+            token_hash,
+            token.user.login,
+            token.user.id as i64,
+            token.user.name,
+            token.user.avatar_url,
+            token.name,
+            &token
+                .scopes
+                .iter()
+                .map(|scope| scope.to_string())
+                .collect::<Vec<_>>(),
+            token.created_at,
+            token.expires_at,
+        )
+        .execute(&*self.db)
+        .await
+        .map_err(|err| Error::ConnectionError(format!("creating api token: {}", err)))?;
+
+        Ok(())
+    }
+
+    async fn tokens_get(&self, token_hash: String) -> Result<ApiToken, Error> {
+        let row = sqlx::query!(
+            r#"SELECT id, login, user_id, user_name, user_avatar_url, name, scopes, created_at, expires_at
+            FROM auth.api_tokens WHERE token_hash = $1"#,
+            token_hash
+        )
+        .fetch_optional(&*self.db)
+        .await
+        .map_err(|err| Error::ConnectionError(format!("fetching api token: {}", err)))?;
+
+        match row {
+            Some(row) => token_from_row(
+                row.id,
+                row.login,
+                row.user_id,
+                row.user_name,
+                row.user_avatar_url,
+                row.name,
+                row.scopes,
+                row.created_at,
+                row.expires_at,
+            ),
+            None => Err(Error::NotFound("api token".to_string())),
+        }
+    }
+
+    async fn tokens_list(&self, login: String) -> Result<Vec<ApiToken>, Error> {
+        let rows = sqlx::query!(
+            r#"SELECT id, login, user_id, user_name, user_avatar_url, name, scopes, created_at, expires_at
+            FROM auth.api_tokens WHERE login = $1 ORDER BY created_at DESC"#,
+            login
+        )
+        .fetch_all(&*self.db)
+        .await
+        .map_err(|err| Error::ConnectionError(format!("fetching api tokens: {}", err)))?;
+
+        rows.into_iter()
+            .map(|row| {
+                token_from_row(
+                    row.id,
+                    row.login,
+                    row.user_id,
+                    row.user_name,
+                    row.user_avatar_url,
+                    row.name,
+                    row.scopes,
+                    row.created_at,
+                    row.expires_at,
+                )
+            })
+            .collect()
+    }
+
+    async fn tokens_delete(&self, login: String, id: Uuid) -> Result<(), Error> {
+        let result = sqlx::query!(
+            r#"DELETE FROM auth.api_tokens WHERE login = $1 AND id = $2"#,
+            login,
+            id,
+        )
+        .execute(&*self.db)
+        .await
+        .map_err(|err| Error::ConnectionError(format!("deleting api token: {}", err)))?;
+
+        if result.rows_affected() == 0 {
+            return Err(Error::NotFound(format!("api token {}", id)));
+        }
+
+        Ok(())
+    }
+}
@@ -1,7 +1,7 @@
 use crate::errors::Error;
 use crate::web::Listing;
 use async_trait::async_trait;
-use chrono::{DateTime, TimeZone, Utc};
+use chrono::{DateTime, Utc};
 use mockall::predicate::*;
 use mockall::*;
 use serde::{Deserialize, Serialize};
@@ -10,7 +10,12 @@ use std::sync::Arc;
 use uuid::Uuid;
 
 pub mod http;
+pub mod markdown;
+pub mod memory;
 pub mod postgres;
+pub mod public;
+pub mod scheduler;
+pub mod search;
 
 #[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
 pub struct Article {
@@ -22,6 +27,14 @@ pub struct Article {
     pub created_at: DateTime<Utc>,
     pub status: Status,
     pub author: String,
+    pub slug: String,
+    pub tags: Vec<String>,
+    /// Single category the article is filed under, unlike tags an article has at most one.
+    pub category: Option<String>,
+    /// When a scheduled article goes live, only set while the status is `Scheduled`.
+    pub publish_at: Option<DateTime<Utc>>,
+    /// Incremented on every update, used to detect concurrent edits.
+    pub version: i32,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -29,45 +42,177 @@ pub enum Status {
     Published,
     Draft,
     Trash,
+    Scheduled,
 }
 
-impl Status {
-    fn to_string(&self) -> String {
+impl std::fmt::Display for Status {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            Status::Published => "published".to_string(),
-            Status::Draft => "draft".to_string(),
-            Status::Trash => "trash".to_string(),
+            Status::Published => write!(f, "published"),
+            Status::Draft => write!(f, "draft"),
+            Status::Trash => write!(f, "trash"),
+            Status::Scheduled => write!(f, "scheduled"),
         }
     }
+}
 
+impl Status {
     fn from_string(s: String) -> Status {
         match s.as_str() {
             "published" => Status::Published,
             "draft" => Status::Draft,
             "trash" => Status::Trash,
+            "scheduled" => Status::Scheduled,
             _ => panic!("Invalid status string"),
         }
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
-pub enum ArticlesListOptions {
-    All,
-    Filtered(Status),
+/// Editable fields of an article, as submitted by an editor.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ArticleChanges {
+    pub title: String,
+    pub description: String,
+    pub content: String,
+    /// None keeps the current slug.
+    pub slug: Option<String>,
+    /// None keeps the current tags.
+    pub tags: Option<Vec<String>>,
+    /// None keeps the current category, Some(None) clears it.
+    pub category: Option<Option<String>>,
+    /// Version the changes were made against, None skips the check.
+    pub version: Option<i32>,
+}
+
+/// Snapshot of an article, recorded each time it is created or updated.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Revision {
+    pub id: Uuid,
+    pub article_id: Uuid,
+    pub title: String,
+    pub description: String,
+    pub content: String,
+    pub slug: String,
+    pub tags: Vec<String>,
+    pub category: Option<String>,
+    /// Who made the change.
+    pub author: String,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Unified diffs between two revisions, empty when a field did not change.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RevisionDiff {
+    pub from: Uuid,
+    pub to: Uuid,
+    pub title: String,
+    pub description: String,
+    pub content: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SearchResult {
+    #[serde(flatten)]
+    pub article: Article,
+    pub rank: f32,
+    /// Excerpt of the content, html escaped, with matches wrapped in `<mark>`.
+    pub snippet: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct ArticlesListOptions {
+    pub status: Option<Status>,
+    pub tag: Option<String>,
+    pub category: Option<String>,
 }
 
 impl ArticlesListOptions {
     pub fn from_str(s: &str) -> Self {
-        match s {
-            "all" => ArticlesListOptions::All,
-            "published" => ArticlesListOptions::Filtered(Status::Published),
-            "draft" => ArticlesListOptions::Filtered(Status::Draft),
-            "trash" => ArticlesListOptions::Filtered(Status::Trash),
-            _ => ArticlesListOptions::All,
+        let status = match s {
+            "published" => Some(Status::Published),
+            "draft" => Some(Status::Draft),
+            "trash" => Some(Status::Trash),
+            "scheduled" => Some(Status::Scheduled),
+            _ => None,
+        };
+
+        ArticlesListOptions {
+            status,
+            tag: None,
+            category: None,
+        }
+    }
+
+    pub fn with_tag(mut self, tag: Option<String>) -> Self {
+        self.tag = tag.map(|tag| slugify(&tag)).filter(|tag| !tag.is_empty());
+        self
+    }
+
+    pub fn with_category(mut self, category: Option<String>) -> Self {
+        self.category = category
+            .map(|category| slugify(&category))
+            .filter(|category| !category.is_empty());
+        self
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    /// Everything editors can do, plus managing roles.
+    Admin,
+    /// Edits, publishes and deletes any article.
+    Editor,
+    /// Creates articles and edits their own drafts.
+    Author,
+    /// Read-only access.
+    Viewer,
+}
+
+impl std::fmt::Display for Role {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Role::Admin => write!(f, "admin"),
+            Role::Editor => write!(f, "editor"),
+            Role::Author => write!(f, "author"),
+            Role::Viewer => write!(f, "viewer"),
+        }
+    }
+}
+
+impl Role {
+    fn from_string(s: String) -> Role {
+        match s.as_str() {
+            "admin" => Role::Admin,
+            "editor" => Role::Editor,
+            "author" => Role::Author,
+            "viewer" => Role::Viewer,
+            _ => panic!("Invalid role string"),
         }
     }
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct RoleAssignment {
+    pub login: String,
+    pub role: Role,
+}
+
+/// Operations checked by `Admin::authorize`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Action {
+    Read,
+    Create,
+    /// Changing the content or tags of an article.
+    Edit(Uuid),
+    /// Publishing, scheduling, or moving an article to draft or trash.
+    Publish,
+    Delete,
+    ManageRoles,
+    /// Listing users, disabling and enabling them.
+    ManageUsers,
+}
+
 #[automock]
 #[async_trait]
 pub trait Repo: Sync + Send {
@@ -87,21 +232,78 @@ pub trait Repo: Sync + Send {
 
     async fn articles_exists(&self, id: Uuid) -> Result<(), Error>;
 
+    /// Full-text search, ordered by relevance.
+    async fn articles_search(
+        &self,
+        query: String,
+        opts: ArticlesListOptions,
+        limit: i64,
+        offset: i64,
+    ) -> Result<(Vec<SearchResult>, i64), Error>;
+
+    async fn articles_get_by_slug(&self, slug: String) -> Result<Article, Error>;
+
+    /// Returns the current slug of the article that previously used `slug`.
+    async fn articles_slug_redirect(&self, slug: String) -> Result<String, Error>;
+
+    /// Returns the article using `slug`, either currently or previously.
+    async fn articles_slug_owner(&self, slug: String) -> Result<Option<Uuid>, Error>;
+
+    async fn tags_list(&self) -> Result<Vec<String>, Error>;
+
+    async fn categories_list(&self) -> Result<Vec<String>, Error>;
+
+    /// Revisions of an article, most recent first.
+    async fn revisions_list(&self, article_id: Uuid) -> Result<Vec<Revision>, Error>;
+
+    async fn revisions_get(&self, article_id: Uuid, id: Uuid) -> Result<Revision, Error>;
+
     // update
 
-    async fn article_update(
+    /// Saves the editable fields of `article` and records them as a new revision made by `editor`.
+    ///
+    /// Only succeeds if the stored version still matches `article.version`, the version is then incremented.
+    async fn article_update(&self, article: Article, editor: String) -> Result<(), Error>;
+
+    /// Sets the status, clearing any pending schedule.
+    async fn article_set_status(&self, id: Uuid, status: Status) -> Result<(), Error>;
+
+    /// Marks the article as scheduled to be published at `publish_at`, if it is a draft or already scheduled.
+    ///
+    /// Returns false, leaving the article untouched, when it is missing or has any other status.
+    async fn article_schedule(&self, id: Uuid, publish_at: DateTime<Utc>) -> Result<bool, Error>;
+
+    /// Publishes scheduled articles whose time has come, returning their ids.
+    async fn articles_publish_due(&self, now: DateTime<Utc>) -> Result<Vec<Uuid>, Error>;
+
+    /// Attaches `tag` and, if the article did not have it yet, records a revision made by `editor`.
+    async fn article_tag_attach(&self, id: Uuid, tag: String, editor: String) -> Result<(), Error>;
+
+    /// Detaches `tag` and, if the article had it, records a revision made by `editor`.
+    async fn article_tag_detach(&self, id: Uuid, tag: String, editor: String) -> Result<(), Error>;
+
+    /// Files the article under `category`, or clears it when None, and records a revision made by
+    /// `editor` if the category changed.
+    async fn article_category_set(
         &self,
         id: Uuid,
-        title: String,
-        description: String,
-        content: String,
+        category: Option<String>,
+        editor: String,
     ) -> Result<(), Error>;
 
-    async fn article_set_status(&self, id: Uuid, status: Status) -> Result<(), Error>;
-
     // delete
 
     async fn article_delete(&self, id: Uuid) -> Result<(), Error>;
+
+    // roles
+
+    async fn roles_get(&self, login: String) -> Result<Option<Role>, Error>;
+
+    async fn roles_list(&self) -> Result<Vec<RoleAssignment>, Error>;
+
+    async fn roles_set(&self, login: String, role: Role) -> Result<(), Error>;
+
+    async fn roles_delete(&self, login: String) -> Result<(), Error>;
 }
 
 #[async_trait]
@@ -114,6 +316,8 @@ pub trait Admin: Send + Sync {
         description: String,
         content: String,
         author: String,
+        tags: Vec<String>,
+        category: Option<String>,
     ) -> Result<Article, Error>;
 
     //read
@@ -122,18 +326,48 @@ pub trait Admin: Send + Sync {
 
     async fn list(&self, opts: ArticlesListOptions, page: i64) -> Result<Listing<Article>, Error>;
 
+    async fn search(
+        &self,
+        query: String,
+        opts: ArticlesListOptions,
+        page: i64,
+    ) -> Result<Listing<SearchResult>, Error>;
+
+    async fn tags(&self) -> Result<Vec<String>, Error>;
+
+    async fn categories(&self) -> Result<Vec<String>, Error>;
+
+    async fn revisions(&self, id: Uuid) -> Result<Vec<Revision>, Error>;
+
+    async fn diff(&self, id: Uuid, from: Uuid, to: Uuid) -> Result<RevisionDiff, Error>;
+
     // update
 
-    async fn update(
-        &self,
-        id: Uuid,
-        title: String,
-        description: String,
-        content: String,
-    ) -> Result<(), Error>;
+    async fn update(&self, id: Uuid, changes: ArticleChanges, editor: String) -> Result<(), Error>;
+
+    /// Brings back the title, description, content, tags and category of a revision.
+    /// The slug is left untouched so that links keep working.
+    async fn restore(&self, id: Uuid, revision: Uuid, editor: String) -> Result<(), Error>;
+
+    async fn tag(&self, id: Uuid, tag: String, editor: String) -> Result<(), Error>;
+
+    async fn untag(&self, id: Uuid, tag: String, editor: String) -> Result<(), Error>;
+
+    async fn categorize(&self, id: Uuid, category: String, editor: String) -> Result<(), Error>;
+
+    async fn uncategorize(&self, id: Uuid, editor: String) -> Result<(), Error>;
 
     async fn publish(&self, id: Uuid) -> Result<(), Error>;
 
+    /// Schedules the article to be published later, or moves an existing schedule.
+    async fn schedule(&self, id: Uuid, publish_at: DateTime<Utc>) -> Result<(), Error>;
+
+    /// Cancels a pending schedule, moving the article back to draft.
+    async fn unschedule(&self, id: Uuid) -> Result<(), Error>;
+
+    /// Publishes every scheduled article that is due, called periodically by the scheduler.
+    async fn publish_due(&self) -> Result<Vec<Uuid>, Error>;
+
     async fn move_to_draft(&self, id: Uuid) -> Result<(), Error>;
 
     async fn move_to_trash(&self, id: Uuid) -> Result<(), Error>;
@@ -141,11 +375,26 @@ pub trait Admin: Send + Sync {
     // delete
 
     async fn delete(&self, id: Uuid) -> Result<(), Error>;
+
+    // roles
+
+    /// Role of `login`, users without one are denied access.
+    async fn role(&self, login: String) -> Result<Role, Error>;
+
+    /// Checks that `login` may perform `action`, returning their role.
+    async fn authorize(&self, login: String, action: Action) -> Result<Role, Error>;
+
+    async fn roles(&self) -> Result<Vec<RoleAssignment>, Error>;
+
+    async fn assign_role(&self, login: String, role: Role) -> Result<(), Error>;
+
+    async fn revoke_role(&self, login: String) -> Result<(), Error>;
 }
 
 pub struct DefaultAdmin {
     repo: Arc<dyn Repo>,
     list_page_size: i64,
+    admins: Vec<String>,
 }
 
 impl DefaultAdmin {
@@ -153,8 +402,72 @@ impl DefaultAdmin {
         DefaultAdmin {
             repo,
             list_page_size,
+            admins: Vec::new(),
+        }
+    }
+
+    /// Logins that are always admins, so that roles can be bootstrapped.
+    pub fn with_admins(mut self, admins: Vec<String>) -> Self {
+        self.admins = admins;
+        self
+    }
+
+    /// Finds a free slug derived from `title`, suffixing it with a counter if needed.
+    async fn unique_slug(&self, title: &str) -> Result<String, Error> {
+        let base = match slugify(title) {
+            s if s.is_empty() => "article".to_string(),
+            s => s,
+        };
+
+        let mut slug = base.clone();
+        let mut n = 1;
+
+        while self.repo.articles_slug_owner(slug.clone()).await?.is_some() {
+            n += 1;
+            slug = format!("{}-{}", base, n);
         }
+
+        Ok(slug)
+    }
+}
+
+fn unified_diff(from: &str, to: &str) -> String {
+    if from == to {
+        return String::new();
+    }
+
+    similar::TextDiff::from_lines(from, to)
+        .unified_diff()
+        .header("from", "to")
+        .to_string()
+}
+
+fn slugify(s: &str) -> String {
+    slug::slugify(s)
+}
+
+/// Tags are stored slugified, sorted and without duplicates.
+fn normalize_tags(tags: Vec<String>) -> Vec<String> {
+    let mut tags: Vec<String> = tags
+        .iter()
+        .map(|tag| slugify(tag))
+        .filter(|tag| !tag.is_empty())
+        .collect();
+
+    tags.sort();
+    tags.dedup();
+
+    tags
+}
+
+/// Categories are stored slugified like tags, an empty one is rejected.
+fn normalize_category(category: &str) -> Result<String, Error> {
+    let category = slugify(category);
+    if category.is_empty() {
+        return Err(Error::InvalidInput("category cannot be empty".to_string()));
     }
+
+    Ok(category)
 }
 
 #[async_trait]
@@ -165,6 +478,8 @@ impl Admin for DefaultAdmin {
         description: String,
         content: String,
         author: String,
+        tags: Vec<String>,
+        category: Option<String>,
     ) -> Result<Article, Error> {
         if title.is_empty() {
             return Err(Error::InvalidInput("title cannot be empty".to_string()));
@@ -188,6 +503,8 @@ impl Admin for DefaultAdmin {
         let created_at: DateTime<Utc> = Utc::now();
         let updated_at = Utc::now();
         let status = Status::Draft;
+        let category = category.as_deref().map(normalize_category).transpose()?;
+        let slug = self.unique_slug(&title).await?;
 
         let article = Article {
             id,
@@ -198,6 +515,11 @@ impl Admin for DefaultAdmin {
             created_at,
             updated_at,
             status,
+            slug,
+            tags: normalize_tags(tags),
+            category,
+            publish_at: None,
+            version: 1,
         };
 
         let article = self.repo.article_create(article).await?;
@@ -229,14 +551,83 @@ impl Admin for DefaultAdmin {
         Ok(listing)
     }
 
-    async fn update(
+    async fn search(
         &self,
-        id: Uuid,
-        title: String,
-        description: String,
-        content: String,
-    ) -> Result<(), Error> {
-        self.repo.articles_get(id).await?;
+        query: String,
+        opts: ArticlesListOptions,
+        page: i64,
+    ) -> Result<Listing<SearchResult>, Error> {
+        let query = query.trim().to_string();
+        if query.is_empty() {
+            return Err(Error::InvalidInput(
+                "search query cannot be empty".to_string(),
+            ));
+        }
+
+        let page = if page <= 0 { 1 } else { page };
+
+        let offset = (page - 1) * self.list_page_size;
+        let (results, count) = self
+            .repo
+            .articles_search(query, opts, self.list_page_size, offset)
+            .await?;
+
+        let pages = (count as f64 / self.list_page_size as f64).ceil() as i64;
+        let listing = Listing {
+            items: results,
+            pages,
+        };
+
+        Ok(listing)
+    }
+
+    async fn tags(&self) -> Result<Vec<String>, Error> {
+        self.repo.tags_list().await
+    }
+
+    async fn categories(&self) -> Result<Vec<String>, Error> {
+        self.repo.categories_list().await
+    }
+
+    async fn revisions(&self, id: Uuid) -> Result<Vec<Revision>, Error> {
+        self.repo.articles_exists(id).await?;
+        self.repo.revisions_list(id).await
+    }
+
+    async fn diff(&self, id: Uuid, from: Uuid, to: Uuid) -> Result<RevisionDiff, Error> {
+        let older = self.repo.revisions_get(id, from).await?;
+        let newer = self.repo.revisions_get(id, to).await?;
+
+        Ok(RevisionDiff {
+            from,
+            to,
+            title: unified_diff(&older.title, &newer.title),
+            description: unified_diff(&older.description, &newer.description),
+            content: unified_diff(&older.content, &newer.content),
+        })
+    }
+
+    async fn update(&self, id: Uuid, changes: ArticleChanges, editor: String) -> Result<(), Error> {
+        let article = self.repo.articles_get(id).await?;
+
+        let ArticleChanges {
+            title,
+            description,
+            content,
+            slug,
+            tags,
+            category,
+            version,
+        } = changes;
+
+        if let Some(version) = version
+            && version != article.version
+        {
+            return Err(Error::PreconditionFailed(format!(
+                "article {} is at version {}, not {}",
+                id, article.version, version
+            )));
+        }
 
         if title.is_empty() {
             return Err(Error::InvalidInput("title cannot be empty".to_string()));
@@ -252,16 +643,139 @@ impl Admin for DefaultAdmin {
             return Err(Error::InvalidInput("content cannot be empty".to_string()));
         }
 
+        let slug = match slug {
+            None => article.slug,
+            Some(slug) => {
+                let slug = slugify(&slug);
+
+                if slug.is_empty() {
+                    return Err(Error::InvalidInput("slug cannot be empty".to_string()));
+                }
+
+                match self.repo.articles_slug_owner(slug.clone()).await? {
+                    Some(owner) if owner != id => {
+                        return Err(Error::InvalidInput(format!(
+                            "slug {} is already in use",
+                            slug
+                        )));
+                    }
+                    _ => slug,
+                }
+            }
+        };
+
+        let tags = match tags {
+            Some(tags) => normalize_tags(tags),
+            None => article.tags.clone(),
+        };
+
+        let category = match category {
+            Some(Some(category)) => Some(normalize_category(&category)?),
+            Some(None) => None,
+            None => article.category.clone(),
+        };
+
+        let article = Article {
+            title,
+            description,
+            content,
+            slug,
+            tags,
+            category,
+            updated_at: Utc::now(),
+            ..article
+        };
+
+        self.repo.article_update(article, editor).await
+    }
+
+    async fn restore(&self, id: Uuid, revision: Uuid, editor: String) -> Result<(), Error> {
+        let revision = self.repo.revisions_get(id, revision).await?;
+
+        let changes = ArticleChanges {
+            title: revision.title,
+            description: revision.description,
+            content: revision.content,
+            slug: None,
+            tags: Some(revision.tags),
+            category: Some(revision.category),
+            version: None,
+        };
+
+        self.update(id, changes, editor).await
+    }
+
+    async fn tag(&self, id: Uuid, tag: String, editor: String) -> Result<(), Error> {
+        let tag = slugify(&tag);
+        if tag.is_empty() {
+            return Err(Error::InvalidInput("tag cannot be empty".to_string()));
+        }
+
+        self.repo.articles_exists(id).await?;
+        self.repo.article_tag_attach(id, tag, editor).await
+    }
+
+    async fn untag(&self, id: Uuid, tag: String, editor: String) -> Result<(), Error> {
+        self.repo.articles_exists(id).await?;
+        self.repo
+            .article_tag_detach(id, slugify(&tag), editor)
+            .await
+    }
+
+    async fn categorize(&self, id: Uuid, category: String, editor: String) -> Result<(), Error> {
+        let category = normalize_category(&category)?;
+
+        self.repo.articles_exists(id).await?;
         self.repo
-            .article_update(id, title, description, content)
+            .article_category_set(id, Some(category), editor)
             .await
     }
 
+    async fn uncategorize(&self, id: Uuid, editor: String) -> Result<(), Error> {
+        self.repo.articles_exists(id).await?;
+        self.repo.article_category_set(id, None, editor).await
+    }
+
     async fn publish(&self, id: Uuid) -> Result<(), Error> {
         self.repo.articles_exists(id).await?;
         self.repo.article_set_status(id, Status::Published).await
     }
 
+    async fn schedule(&self, id: Uuid, publish_at: DateTime<Utc>) -> Result<(), Error> {
+        if publish_at <= Utc::now() {
+            return Err(Error::InvalidInput(
+                "publish_at must be in the future".to_string(),
+            ));
+        }
+
+        if self.repo.article_schedule(id, publish_at).await? {
+            return Ok(());
+        }
+
+        // the status is checked by the update itself, this only explains the refusal
+        let article = self.repo.articles_get(id).await?;
+        let reason = match article.status {
+            Status::Published => "article is already published",
+            Status::Trash => "article is in the trash",
+            _ => "article cannot be scheduled",
+        };
+
+        Err(Error::InvalidInput(reason.to_string()))
+    }
+
+    async fn unschedule(&self, id: Uuid) -> Result<(), Error> {
+        let article = self.repo.articles_get(id).await?;
+        if article.status != Status::Scheduled {
+            return Err(Error::InvalidInput("article is not scheduled".to_string()));
+        }
+
+        self.repo.article_set_status(id, Status::Draft).await
+    }
+
+    async fn publish_due(&self) -> Result<Vec<Uuid>, Error> {
+        self.repo.articles_publish_due(Utc::now()).await
+    }
+
     async fn move_to_draft(&self, id: Uuid) -> Result<(), Error> {
         self.repo.articles_exists(id).await?;
         self.repo.article_set_status(id, Status::Draft).await
@@ -276,44 +790,204 @@ impl Admin for DefaultAdmin {
         self.repo.articles_exists(id).await?;
         self.repo.article_delete(id).await
     }
-}
 
-#[cfg(test)]
-mod default_admin_test {
-    use super::*;
+    async fn role(&self, login: String) -> Result<Role, Error> {
+        if self.admins.contains(&login) {
+            return Ok(Role::Admin);
+        }
 
-    #[tokio::test]
-    async fn test_create_article() {
-        let mut repo = MockRepo::new();
-        let article = Article {
-            id: Uuid::new_v4(),
-            title: "title".to_string(),
-            description: "description".to_string(),
-            content: "content".to_string(),
-            author: "author".to_string(),
-            created_at: Utc::now(),
-            updated_at: Utc::now(),
-            status: Status::Draft,
+        match self.repo.roles_get(login.clone()).await? {
+            Some(role) => Ok(role),
+            None => Err(Error::PermissionDenied(format!(
+                "no role assigned to {}",
+                login
+            ))),
+        }
+    }
+
+    async fn authorize(&self, login: String, action: Action) -> Result<Role, Error> {
+        let role = self.role(login.clone()).await?;
+
+        let allowed = match (role, &action) {
+            (Role::Admin, _) => true,
+            (_, Action::ManageRoles | Action::ManageUsers) => false,
+            (Role::Editor, _) => true,
+            (Role::Author, Action::Read | Action::Create) => true,
+            (Role::Author, Action::Edit(id)) => {
+                let article = self.repo.articles_get(*id).await?;
+                article.author == login && article.status == Status::Draft
+            }
+            (Role::Author, _) => false,
+            (Role::Viewer, action) => *action == Action::Read,
         };
 
-        repo.expect_article_create()
-            .returning(move |_| Ok(article.clone()));
+        if !allowed {
+            return Err(Error::PermissionDenied(format!(
+                "{} {} cannot perform {:?}",
+                role, login, action
+            )));
+        }
 
-        let admin = DefaultAdmin::new(Arc::new(repo), 10);
+        Ok(role)
+    }
 
-        let result = admin
-            .create(
-                "title".to_string(),
-                "description".to_string(),
-                "content".to_string(),
-                "author".to_string(),
-            )
-            .await;
+    async fn roles(&self) -> Result<Vec<RoleAssignment>, Error> {
+        self.repo.roles_list().await
+    }
 
-        assert!(result.is_ok());
-        let created_article = result.unwrap();
-        assert_eq!(created_article.title, "title");
-        assert_eq!(created_article.description, "description");
+    async fn assign_role(&self, login: String, role: Role) -> Result<(), Error> {
+        if login.trim().is_empty() {
+            return Err(Error::InvalidInput("login cannot be empty".to_string()));
+        }
+
+        self.repo.roles_set(login, role).await
+    }
+
+    async fn revoke_role(&self, login: String) -> Result<(), Error> {
+        self.repo.roles_delete(login).await
+    }
+}
+
+pub enum SlugLookup {
+    Found(Box<Article>),
+    /// The slug used to belong to an article which has since been renamed.
+    Moved(String),
+}
+
+#[async_trait]
+pub trait Reader: Send + Sync {
+    async fn get(&self, id: Uuid) -> Result<Article, Error>;
+
+    async fn get_by_slug(&self, slug: String) -> Result<SlugLookup, Error>;
+
+    /// Lists published articles, the status in `opts` is ignored.
+    async fn list(&self, opts: ArticlesListOptions, page: i64) -> Result<Listing<Article>, Error>;
+}
+
+pub struct DefaultReader {
+    repo: Arc<dyn Repo>,
+    list_page_size: i64,
+}
+
+impl DefaultReader {
+    pub fn new(repo: Arc<dyn Repo>, list_page_size: i64) -> Self {
+        DefaultReader {
+            repo,
+            list_page_size,
+        }
+    }
+}
+
+#[async_trait]
+impl Reader for DefaultReader {
+    async fn get(&self, id: Uuid) -> Result<Article, Error> {
+        let article = self.repo.articles_get(id).await?;
+
+        // readers must not be able to tell drafts apart from missing articles
+        if article.status != Status::Published {
+            return Err(Error::NotFound(format!("article {}", id)));
+        }
+
+        Ok(article)
+    }
+
+    async fn get_by_slug(&self, slug: String) -> Result<SlugLookup, Error> {
+        let not_found = || Error::NotFound(format!("article {}", slug));
+
+        let article = match self.repo.articles_get_by_slug(slug.clone()).await {
+            Ok(article) => article,
+            Err(Error::NotFound(_)) => {
+                let current = match self.repo.articles_slug_redirect(slug.clone()).await {
+                    Ok(current) => current,
+                    Err(Error::NotFound(_)) => return Err(not_found()),
+                    Err(err) => return Err(err),
+                };
+
+                let article = self.repo.articles_get_by_slug(current).await?;
+                if article.status != Status::Published {
+                    return Err(not_found());
+                }
+
+                return Ok(SlugLookup::Moved(article.slug));
+            }
+            Err(err) => return Err(err),
+        };
+
+        if article.status != Status::Published {
+            return Err(not_found());
+        }
+
+        Ok(SlugLookup::Found(Box::new(article)))
+    }
+
+    async fn list(&self, opts: ArticlesListOptions, page: i64) -> Result<Listing<Article>, Error> {
+        let page = if page <= 0 { 1 } else { page };
+
+        let opts = ArticlesListOptions {
+            status: Some(Status::Published),
+            ..opts
+        };
+
+        let offset = (page - 1) * self.list_page_size;
+        let (articles, count) = self
+            .repo
+            .articles_list(opts, self.list_page_size, offset)
+            .await?;
+
+        let pages = (count as f64 / self.list_page_size as f64).ceil() as i64;
+        let listing = Listing {
+            items: articles,
+            pages,
+        };
+
+        Ok(listing)
+    }
+}
+
+#[cfg(test)]
+mod default_admin_test {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_create_article() {
+        let mut repo = MockRepo::new();
+        let article = Article {
+            id: Uuid::new_v4(),
+            title: "title".to_string(),
+            description: "description".to_string(),
+            content: "content".to_string(),
+            author: "author".to_string(),
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+            status: Status::Draft,
+            publish_at: None,
+            version: 1,
+            slug: "title".to_string(),
+            tags: vec![],
+            category: None,
+        };
+
+        repo.expect_articles_slug_owner().returning(|_| Ok(None));
+        repo.expect_article_create()
+            .returning(move |_| Ok(article.clone()));
+
+        let admin = DefaultAdmin::new(Arc::new(repo), 10);
+
+        let result = admin
+            .create(
+                "title".to_string(),
+                "description".to_string(),
+                "content".to_string(),
+                "author".to_string(),
+                vec![],
+                None,
+            )
+            .await;
+
+        assert!(result.is_ok());
+        let created_article = result.unwrap();
+        assert_eq!(created_article.title, "title");
+        assert_eq!(created_article.description, "description");
         assert_eq!(created_article.content, "content");
         assert_eq!(created_article.author, "author");
         assert_eq!(created_article.status, Status::Draft);
@@ -330,6 +1004,8 @@ mod default_admin_test {
                 "description".to_string(),
                 "content".to_string(),
                 "author".to_string(),
+                vec![],
+                None,
             )
             .await;
 
@@ -351,6 +1027,8 @@ mod default_admin_test {
                 "".to_string(),
                 "content".to_string(),
                 "author".to_string(),
+                vec![],
+                None,
             )
             .await;
 
@@ -372,6 +1050,8 @@ mod default_admin_test {
                 "description".to_string(),
                 "".to_string(),
                 "author".to_string(),
+                vec![],
+                None,
             )
             .await;
 
@@ -393,6 +1073,8 @@ mod default_admin_test {
                 "description".to_string(),
                 "content".to_string(),
                 "".to_string(),
+                vec![],
+                None,
             )
             .await;
 
@@ -415,6 +1097,11 @@ mod default_admin_test {
             created_at: Utc::now(),
             updated_at: Utc::now(),
             status: Status::Draft,
+            publish_at: None,
+            version: 1,
+            slug: "title".to_string(),
+            tags: vec![],
+            category: None,
         };
 
         let article2 = article.clone();
@@ -441,7 +1128,7 @@ mod default_admin_test {
         let mut repo = MockRepo::new();
         let id = Uuid::new_v4();
 
-        let id2 = id.clone();
+        let id2 = id;
         repo.expect_articles_get()
             .with(eq(id2))
             .returning(move |_| Err(Error::NotFound("article".to_string())));
@@ -465,6 +1152,11 @@ mod default_admin_test {
             created_at: Utc::now(),
             updated_at: Utc::now(),
             status: Status::Draft,
+            publish_at: None,
+            version: 1,
+            slug: "title1".to_string(),
+            tags: vec![],
+            category: None,
         };
 
         let article2 = Article {
@@ -476,6 +1168,11 @@ mod default_admin_test {
             created_at: Utc::now(),
             updated_at: Utc::now(),
             status: Status::Published,
+            publish_at: None,
+            version: 1,
+            slug: "title2".to_string(),
+            tags: vec![],
+            category: None,
         };
 
         let articles = vec![article1.clone(), article2.clone()];
@@ -486,7 +1183,7 @@ mod default_admin_test {
 
         let admin = DefaultAdmin::new(Arc::new(repo), 10);
 
-        let result = admin.list(ArticlesListOptions::All, 1).await;
+        let result = admin.list(ArticlesListOptions::default(), 1).await;
 
         assert!(result.is_ok());
         let listing = result.unwrap();
@@ -501,12 +1198,12 @@ mod default_admin_test {
         let mut repo = MockRepo::new();
 
         repo.expect_articles_list()
-            .with(eq(ArticlesListOptions::All), eq(10), eq(0))
+            .with(eq(ArticlesListOptions::default()), eq(10), eq(0))
             .returning(|_, _, _| Ok((vec![], 0)));
 
         let admin = DefaultAdmin::new(Arc::new(repo), 10);
 
-        let result = admin.list(ArticlesListOptions::All, 1).await;
+        let result = admin.list(ArticlesListOptions::default(), 1).await;
 
         assert!(result.is_ok());
         let listing = result.unwrap();
@@ -519,12 +1216,12 @@ mod default_admin_test {
         let mut repo = MockRepo::new();
 
         repo.expect_articles_list()
-            .with(eq(ArticlesListOptions::All), eq(10), eq(0))
+            .with(eq(ArticlesListOptions::default()), eq(10), eq(0))
             .returning(|_, _, _| Ok((vec![], 0)));
 
         let admin = DefaultAdmin::new(Arc::new(repo), 10);
 
-        let result = admin.list(ArticlesListOptions::All, -1).await;
+        let result = admin.list(ArticlesListOptions::default(), -1).await;
 
         assert!(result.is_ok());
         let listing = result.unwrap();
@@ -532,6 +1229,49 @@ mod default_admin_test {
         assert_eq!(listing.pages, 0);
     }
 
+    #[tokio::test]
+    async fn search_success() {
+        let mut repo = MockRepo::new();
+
+        repo.expect_articles_search()
+            .with(
+                eq("rust async".to_string()),
+                eq(ArticlesListOptions::from_str("published")),
+                eq(10),
+                eq(10),
+            )
+            .returning(|_, _, _, _| Ok((vec![], 15)));
+
+        let admin = DefaultAdmin::new(Arc::new(repo), 10);
+
+        let result = admin
+            .search(
+                " rust async ".to_string(),
+                ArticlesListOptions::from_str("published"),
+                2,
+            )
+            .await;
+
+        assert!(result.is_ok());
+        assert_eq!(result.unwrap().pages, 2);
+    }
+
+    #[tokio::test]
+    async fn search_empty_query() {
+        let repo = MockRepo::new();
+        let admin = DefaultAdmin::new(Arc::new(repo), 10);
+
+        let result = admin
+            .search("  ".to_string(), ArticlesListOptions::default(), 1)
+            .await;
+
+        assert!(result.is_err());
+        assert_eq!(
+            result.err().unwrap().to_string(),
+            "invalid input: search query cannot be empty"
+        );
+    }
+
     #[tokio::test]
     async fn test_update_success() {
         let mut repo = MockRepo::new();
@@ -544,6 +1284,11 @@ mod default_admin_test {
             created_at: Utc::now(),
             updated_at: Utc::now(),
             status: Status::Draft,
+            publish_at: None,
+            version: 1,
+            slug: "title".to_string(),
+            tags: vec![],
+            category: None,
         };
 
         let article2 = article.clone();
@@ -551,16 +1296,23 @@ mod default_admin_test {
         repo.expect_articles_get()
             .returning(move |_| Ok(article2.clone()));
 
-        repo.expect_article_update().returning(|_, _, _, _| Ok(()));
+        repo.expect_article_update().returning(|_, _| Ok(()));
 
         let admin = DefaultAdmin::new(Arc::new(repo), 10);
 
         let result = admin
             .update(
                 article.id,
-                "new title".to_string(),
-                "new description".to_string(),
-                "new content".to_string(),
+                ArticleChanges {
+                    title: "new title".to_string(),
+                    description: "new description".to_string(),
+                    content: "new content".to_string(),
+                    slug: None,
+                    tags: None,
+                    category: None,
+                    version: None,
+                },
+                "editor".to_string(),
             )
             .await;
 
@@ -579,6 +1331,11 @@ mod default_admin_test {
             created_at: Utc::now(),
             updated_at: Utc::now(),
             status: Status::Draft,
+            publish_at: None,
+            version: 1,
+            slug: "title".to_string(),
+            tags: vec![],
+            category: None,
         };
 
         let article2 = article.clone();
@@ -591,9 +1348,16 @@ mod default_admin_test {
         let result = admin
             .update(
                 article.id,
-                "".to_string(),
-                "new description".to_string(),
-                "new content".to_string(),
+                ArticleChanges {
+                    title: "".to_string(),
+                    description: "new description".to_string(),
+                    content: "new content".to_string(),
+                    slug: None,
+                    tags: None,
+                    category: None,
+                    version: None,
+                },
+                "editor".to_string(),
             )
             .await;
 
@@ -616,6 +1380,11 @@ mod default_admin_test {
             created_at: Utc::now(),
             updated_at: Utc::now(),
             status: Status::Draft,
+            publish_at: None,
+            version: 1,
+            slug: "title".to_string(),
+            tags: vec![],
+            category: None,
         };
 
         let article2 = article.clone();
@@ -628,9 +1397,16 @@ mod default_admin_test {
         let result = admin
             .update(
                 article.id,
-                "new title".to_string(),
-                "".to_string(),
-                "new content".to_string(),
+                ArticleChanges {
+                    title: "new title".to_string(),
+                    description: "".to_string(),
+                    content: "new content".to_string(),
+                    slug: None,
+                    tags: None,
+                    category: None,
+                    version: None,
+                },
+                "editor".to_string(),
             )
             .await;
 
@@ -653,6 +1429,11 @@ mod default_admin_test {
             created_at: Utc::now(),
             updated_at: Utc::now(),
             status: Status::Draft,
+            publish_at: None,
+            version: 1,
+            slug: "title".to_string(),
+            tags: vec![],
+            category: None,
         };
 
         let article2 = article.clone();
@@ -665,9 +1446,16 @@ mod default_admin_test {
         let result = admin
             .update(
                 article.id,
-                "new title".to_string(),
-                "new description".to_string(),
-                "".to_string(),
+                ArticleChanges {
+                    title: "new title".to_string(),
+                    description: "new description".to_string(),
+                    content: "".to_string(),
+                    slug: None,
+                    tags: None,
+                    category: None,
+                    version: None,
+                },
+                "editor".to_string(),
             )
             .await;
 
@@ -690,6 +1478,11 @@ mod default_admin_test {
             created_at: Utc::now(),
             updated_at: Utc::now(),
             status: Status::Draft,
+            publish_at: None,
+            version: 1,
+            slug: "title".to_string(),
+            tags: vec![],
+            category: None,
         };
 
         let article2 = article.clone();
@@ -698,22 +1491,33 @@ mod default_admin_test {
             .returning(move |_| Ok(article2.clone()));
 
         repo.expect_article_update()
-            .with(
-                eq(article.id),
-                eq("new title".to_string()),
-                eq("new description".to_string()),
-                eq("new content".to_string()),
-            )
-            .returning(|_, _, _, _| Ok(()));
+            .withf(move |updated, editor| {
+                updated.id == article.id
+                    && updated.title == "new title"
+                    && updated.description == "new description"
+                    && updated.content == "new content"
+                    && updated.slug == "title"
+                    && updated.tags.is_empty()
+                    && updated.author == "author"
+                    && editor == "editor"
+            })
+            .returning(|_, _| Ok(()));
 
         let admin = DefaultAdmin::new(Arc::new(repo), 10);
 
         let result = admin
             .update(
                 article.id,
-                "new title".to_string(),
-                "new description".to_string(),
-                "new content".to_string(),
+                ArticleChanges {
+                    title: "new title".to_string(),
+                    description: "new description".to_string(),
+                    content: "new content".to_string(),
+                    slug: None,
+                    tags: None,
+                    category: None,
+                    version: None,
+                },
+                "editor".to_string(),
             )
             .await;
 
@@ -732,9 +1536,16 @@ mod default_admin_test {
         let result = admin
             .update(
                 Uuid::new_v4(),
-                "new title".to_string(),
-                "new description".to_string(),
-                "new content".to_string(),
+                ArticleChanges {
+                    title: "new title".to_string(),
+                    description: "new description".to_string(),
+                    content: "new content".to_string(),
+                    slug: None,
+                    tags: None,
+                    category: None,
+                    version: None,
+                },
+                "editor".to_string(),
             )
             .await;
 
@@ -743,120 +1554,288 @@ mod default_admin_test {
     }
 
     #[tokio::test]
-    async fn publish_success() {
+    async fn test_update_version_mismatch() {
         let mut repo = MockRepo::new();
-        let article_id = Uuid::new_v4();
+        let article = Article {
+            version: 3,
+            ..draft()
+        };
+        let article_id = article.id;
 
-        repo.expect_articles_exists().returning(move |_| Ok(()));
-        repo.expect_article_set_status()
-            .with(eq(article_id), eq(Status::Published))
-            .returning(|_, _| Ok(()));
+        repo.expect_articles_get()
+            .returning(move |_| Ok(article.clone()));
 
         let admin = DefaultAdmin::new(Arc::new(repo), 10);
 
-        let result = admin.publish(article_id).await;
+        let result = admin
+            .update(
+                article_id,
+                ArticleChanges {
+                    title: "new title".to_string(),
+                    description: "new description".to_string(),
+                    content: "new content".to_string(),
+                    slug: None,
+                    tags: None,
+                    category: None,
+                    version: Some(2),
+                },
+                "editor".to_string(),
+            )
+            .await;
 
-        assert!(result.is_ok());
+        assert!(result.is_err());
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            format!(
+                "precondition failed: article {} is at version 3, not 2",
+                article_id
+            )
+        );
     }
 
     #[tokio::test]
-    async fn publish_notfound() {
+    async fn test_update_version_conflict() {
         let mut repo = MockRepo::new();
+        let article = Article {
+            version: 3,
+            ..draft()
+        };
+        let article_id = article.id;
 
-        repo.expect_articles_exists()
-            .returning(|_| Err(Error::NotFound("article".to_string())));
+        repo.expect_articles_get()
+            .returning(move |_| Ok(article.clone()));
+        repo.expect_article_update()
+            .withf(|updated, _| updated.version == 3)
+            .returning(|_, _| Err(Error::PreconditionFailed("modified".to_string())));
 
         let admin = DefaultAdmin::new(Arc::new(repo), 10);
 
-        let result = admin.publish(Uuid::new_v4()).await;
+        let result = admin
+            .update(
+                article_id,
+                ArticleChanges {
+                    title: "new title".to_string(),
+                    description: "new description".to_string(),
+                    content: "new content".to_string(),
+                    slug: None,
+                    tags: None,
+                    category: None,
+                    version: Some(3),
+                },
+                "editor".to_string(),
+            )
+            .await;
 
-        assert!(result.is_err());
-        assert_eq!(result.unwrap_err().to_string(), "article not found");
+        assert!(matches!(result, Err(Error::PreconditionFailed(_))));
     }
 
     #[tokio::test]
-    async fn move_to_draft_success() {
+    async fn test_create_unique_slug() {
         let mut repo = MockRepo::new();
-        let article_id = Uuid::new_v4();
 
-        repo.expect_articles_exists().returning(move |_| Ok(()));
-        repo.expect_article_set_status()
-            .with(eq(article_id), eq(Status::Draft))
-            .returning(|_, _| Ok(()));
+        repo.expect_articles_slug_owner()
+            .with(eq("hello-world".to_string()))
+            .returning(|_| Ok(Some(Uuid::new_v4())));
+        repo.expect_articles_slug_owner()
+            .with(eq("hello-world-2".to_string()))
+            .returning(|_| Ok(None));
+        repo.expect_article_create()
+            .withf(|article| article.slug == "hello-world-2")
+            .returning(Ok);
 
         let admin = DefaultAdmin::new(Arc::new(repo), 10);
 
-        let result = admin.move_to_draft(article_id).await;
+        let result = admin
+            .create(
+                "Hello, World!".to_string(),
+                "description".to_string(),
+                "content".to_string(),
+                "author".to_string(),
+                vec![],
+                None,
+            )
+            .await;
 
         assert!(result.is_ok());
+        assert_eq!(result.unwrap().slug, "hello-world-2");
     }
 
     #[tokio::test]
-    async fn move_to_draft_notfound() {
+    async fn test_update_slug() {
         let mut repo = MockRepo::new();
+        let article = Article {
+            id: Uuid::new_v4(),
+            title: "title".to_string(),
+            description: "description".to_string(),
+            content: "content".to_string(),
+            author: "author".to_string(),
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+            status: Status::Draft,
+            publish_at: None,
+            version: 1,
+            slug: "title".to_string(),
+            tags: vec![],
+            category: None,
+        };
 
-        repo.expect_articles_exists()
-            .returning(|_| Err(Error::NotFound("article".to_string())));
-
-        let admin = DefaultAdmin::new(Arc::new(repo), 10);
-
-        let result = admin.move_to_draft(Uuid::new_v4()).await;
-
-        assert!(result.is_err());
-        assert_eq!(result.unwrap_err().to_string(), "article not found");
-    }
-
-    #[tokio::test]
-    async fn move_to_trash_success() {
-        let mut repo = MockRepo::new();
-        let article_id = Uuid::new_v4();
+        let article2 = article.clone();
 
-        repo.expect_articles_exists().returning(move |_| Ok(()));
-        repo.expect_article_set_status()
-            .with(eq(article_id), eq(Status::Trash))
+        repo.expect_articles_get()
+            .returning(move |_| Ok(article2.clone()));
+        repo.expect_articles_slug_owner()
+            .with(eq("new-slug".to_string()))
+            .returning(|_| Ok(None));
+        repo.expect_article_update()
+            .withf(|updated, _| updated.slug == "new-slug")
             .returning(|_, _| Ok(()));
 
         let admin = DefaultAdmin::new(Arc::new(repo), 10);
 
-        let result = admin.move_to_trash(article_id).await;
+        let result = admin
+            .update(
+                article.id,
+                ArticleChanges {
+                    title: "title".to_string(),
+                    description: "description".to_string(),
+                    content: "content".to_string(),
+                    slug: Some("New Slug!".to_string()),
+                    tags: None,
+                    category: None,
+                    version: None,
+                },
+                "editor".to_string(),
+            )
+            .await;
 
         assert!(result.is_ok());
     }
 
     #[tokio::test]
-    async fn move_to_trash_notfound() {
+    async fn test_update_slug_taken() {
         let mut repo = MockRepo::new();
+        let article = Article {
+            id: Uuid::new_v4(),
+            title: "title".to_string(),
+            description: "description".to_string(),
+            content: "content".to_string(),
+            author: "author".to_string(),
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+            status: Status::Draft,
+            publish_at: None,
+            version: 1,
+            slug: "title".to_string(),
+            tags: vec![],
+            category: None,
+        };
 
-        repo.expect_articles_exists()
-            .returning(|_| Err(Error::NotFound("article".to_string())));
+        let article2 = article.clone();
+
+        repo.expect_articles_get()
+            .returning(move |_| Ok(article2.clone()));
+        repo.expect_articles_slug_owner()
+            .returning(|_| Ok(Some(Uuid::new_v4())));
 
         let admin = DefaultAdmin::new(Arc::new(repo), 10);
 
-        let result = admin.move_to_trash(Uuid::new_v4()).await;
+        let result = admin
+            .update(
+                article.id,
+                ArticleChanges {
+                    title: "title".to_string(),
+                    description: "description".to_string(),
+                    content: "content".to_string(),
+                    slug: Some("taken".to_string()),
+                    tags: None,
+                    category: None,
+                    version: None,
+                },
+                "editor".to_string(),
+            )
+            .await;
 
         assert!(result.is_err());
-        assert_eq!(result.unwrap_err().to_string(), "article not found");
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "invalid input: slug taken is already in use"
+        );
     }
 
     #[tokio::test]
-    async fn delete_success() {
+    async fn test_create_normalizes_tags_and_category() {
         let mut repo = MockRepo::new();
-        let article_id = Uuid::new_v4();
 
-        repo.expect_articles_exists().returning(move |_| Ok(()));
-        repo.expect_article_delete()
-            .with(eq(article_id))
-            .returning(|_| Ok(()));
+        repo.expect_articles_slug_owner().returning(|_| Ok(None));
+        repo.expect_article_create()
+            .withf(|article| {
+                article.tags == vec!["rust".to_string(), "web-dev".to_string()]
+                    && article.category == Some("languages".to_string())
+            })
+            .returning(Ok);
 
         let admin = DefaultAdmin::new(Arc::new(repo), 10);
 
-        let result = admin.delete(article_id).await;
+        let result = admin
+            .create(
+                "title".to_string(),
+                "description".to_string(),
+                "content".to_string(),
+                "author".to_string(),
+                vec![
+                    "Web Dev".to_string(),
+                    "rust".to_string(),
+                    "Rust".to_string(),
+                    " ".to_string(),
+                ],
+                Some("Languages".to_string()),
+            )
+            .await;
 
         assert!(result.is_ok());
     }
 
     #[tokio::test]
-    async fn delete_notfound() {
+    async fn tag_success() {
+        let mut repo = MockRepo::new();
+        let article_id = Uuid::new_v4();
+
+        repo.expect_articles_exists().returning(|_| Ok(()));
+        repo.expect_article_tag_attach()
+            .with(
+                eq(article_id),
+                eq("web-dev".to_string()),
+                eq("editor".to_string()),
+            )
+            .returning(|_, _, _| Ok(()));
+
+        let admin = DefaultAdmin::new(Arc::new(repo), 10);
+
+        let result = admin
+            .tag(article_id, "Web Dev".to_string(), "editor".to_string())
+            .await;
+
+        assert!(result.is_ok());
+    }
+
+    #[tokio::test]
+    async fn tag_empty() {
+        let repo = MockRepo::new();
+        let admin = DefaultAdmin::new(Arc::new(repo), 10);
+
+        let result = admin
+            .tag(Uuid::new_v4(), "  ".to_string(), "editor".to_string())
+            .await;
+
+        assert!(result.is_err());
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "invalid input: tag cannot be empty"
+        );
+    }
+
+    #[tokio::test]
+    async fn tag_notfound() {
         let mut repo = MockRepo::new();
 
         repo.expect_articles_exists()
@@ -864,9 +1843,946 @@ mod default_admin_test {
 
         let admin = DefaultAdmin::new(Arc::new(repo), 10);
 
-        let result = admin.delete(Uuid::new_v4()).await;
+        let result = admin
+            .tag(Uuid::new_v4(), "rust".to_string(), "editor".to_string())
+            .await;
+
+        assert!(result.is_err());
+        assert_eq!(result.unwrap_err().to_string(), "article not found");
+    }
+
+    #[tokio::test]
+    async fn untag_success() {
+        let mut repo = MockRepo::new();
+        let article_id = Uuid::new_v4();
+
+        repo.expect_articles_exists().returning(|_| Ok(()));
+        repo.expect_article_tag_detach()
+            .with(
+                eq(article_id),
+                eq("rust".to_string()),
+                eq("editor".to_string()),
+            )
+            .returning(|_, _, _| Ok(()));
+
+        let admin = DefaultAdmin::new(Arc::new(repo), 10);
+
+        let result = admin
+            .untag(article_id, "rust".to_string(), "editor".to_string())
+            .await;
+
+        assert!(result.is_ok());
+    }
+
+    #[tokio::test]
+    async fn categorize_success() {
+        let mut repo = MockRepo::new();
+        let article_id = Uuid::new_v4();
+
+        repo.expect_articles_exists().returning(|_| Ok(()));
+        repo.expect_article_category_set()
+            .with(
+                eq(article_id),
+                eq(Some("web-dev".to_string())),
+                eq("editor".to_string()),
+            )
+            .returning(|_, _, _| Ok(()));
+
+        let admin = DefaultAdmin::new(Arc::new(repo), 10);
+
+        let result = admin
+            .categorize(article_id, "Web Dev".to_string(), "editor".to_string())
+            .await;
+
+        assert!(result.is_ok());
+    }
+
+    #[tokio::test]
+    async fn categorize_empty() {
+        let repo = MockRepo::new();
+        let admin = DefaultAdmin::new(Arc::new(repo), 10);
+
+        let result = admin
+            .categorize(Uuid::new_v4(), "  ".to_string(), "editor".to_string())
+            .await;
+
+        assert!(result.is_err());
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "invalid input: category cannot be empty"
+        );
+    }
+
+    #[tokio::test]
+    async fn uncategorize_success() {
+        let mut repo = MockRepo::new();
+        let article_id = Uuid::new_v4();
+
+        repo.expect_articles_exists().returning(|_| Ok(()));
+        repo.expect_article_category_set()
+            .with(eq(article_id), eq(None), eq("editor".to_string()))
+            .returning(|_, _, _| Ok(()));
+
+        let admin = DefaultAdmin::new(Arc::new(repo), 10);
+
+        let result = admin.uncategorize(article_id, "editor".to_string()).await;
+
+        assert!(result.is_ok());
+    }
+
+    #[tokio::test]
+    async fn publish_success() {
+        let mut repo = MockRepo::new();
+        let article_id = Uuid::new_v4();
+
+        repo.expect_articles_exists().returning(move |_| Ok(()));
+        repo.expect_article_set_status()
+            .with(eq(article_id), eq(Status::Published))
+            .returning(|_, _| Ok(()));
+
+        let admin = DefaultAdmin::new(Arc::new(repo), 10);
+
+        let result = admin.publish(article_id).await;
+
+        assert!(result.is_ok());
+    }
+
+    #[tokio::test]
+    async fn publish_notfound() {
+        let mut repo = MockRepo::new();
+
+        repo.expect_articles_exists()
+            .returning(|_| Err(Error::NotFound("article".to_string())));
+
+        let admin = DefaultAdmin::new(Arc::new(repo), 10);
+
+        let result = admin.publish(Uuid::new_v4()).await;
+
+        assert!(result.is_err());
+        assert_eq!(result.unwrap_err().to_string(), "article not found");
+    }
+
+    fn draft() -> Article {
+        Article {
+            id: Uuid::new_v4(),
+            title: "title".to_string(),
+            description: "description".to_string(),
+            content: "content".to_string(),
+            author: "author".to_string(),
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+            status: Status::Draft,
+            publish_at: None,
+            version: 1,
+            slug: "title".to_string(),
+            tags: vec![],
+            category: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn schedule_success() {
+        let mut repo = MockRepo::new();
+        let article_id = Uuid::new_v4();
+        let publish_at = Utc::now() + chrono::Duration::hours(1);
+
+        repo.expect_article_schedule()
+            .with(eq(article_id), eq(publish_at))
+            .returning(|_, _| Ok(true));
+
+        let admin = DefaultAdmin::new(Arc::new(repo), 10);
+
+        let result = admin.schedule(article_id, publish_at).await;
+
+        assert!(result.is_ok());
+    }
+
+    #[tokio::test]
+    async fn schedule_in_past() {
+        let repo = MockRepo::new();
+        let admin = DefaultAdmin::new(Arc::new(repo), 10);
+
+        let result = admin
+            .schedule(Uuid::new_v4(), Utc::now() - chrono::Duration::minutes(1))
+            .await;
+
+        assert!(result.is_err());
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "invalid input: publish_at must be in the future"
+        );
+    }
+
+    #[tokio::test]
+    async fn schedule_already_published() {
+        let mut repo = MockRepo::new();
+        let article = Article {
+            status: Status::Published,
+            ..draft()
+        };
+
+        repo.expect_article_schedule().returning(|_, _| Ok(false));
+        repo.expect_articles_get()
+            .returning(move |_| Ok(article.clone()));
+
+        let admin = DefaultAdmin::new(Arc::new(repo), 10);
+
+        let result = admin
+            .schedule(Uuid::new_v4(), Utc::now() + chrono::Duration::hours(1))
+            .await;
+
+        assert!(result.is_err());
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "invalid input: article is already published"
+        );
+    }
+
+    #[tokio::test]
+    async fn schedule_trashed() {
+        let mut repo = MockRepo::new();
+        let article = Article {
+            status: Status::Trash,
+            ..draft()
+        };
+
+        repo.expect_article_schedule().returning(|_, _| Ok(false));
+        repo.expect_articles_get()
+            .returning(move |_| Ok(article.clone()));
+
+        let admin = DefaultAdmin::new(Arc::new(repo), 10);
+
+        let result = admin
+            .schedule(Uuid::new_v4(), Utc::now() + chrono::Duration::hours(1))
+            .await;
+
+        assert!(result.is_err());
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "invalid input: article is in the trash"
+        );
+    }
+
+    #[tokio::test]
+    async fn schedule_notfound() {
+        let mut repo = MockRepo::new();
+
+        repo.expect_article_schedule().returning(|_, _| Ok(false));
+        repo.expect_articles_get()
+            .returning(|_| Err(Error::NotFound("article".to_string())));
+
+        let admin = DefaultAdmin::new(Arc::new(repo), 10);
+
+        let result = admin
+            .schedule(Uuid::new_v4(), Utc::now() + chrono::Duration::hours(1))
+            .await;
 
         assert!(result.is_err());
         assert_eq!(result.unwrap_err().to_string(), "article not found");
     }
+
+    #[tokio::test]
+    async fn unschedule_success() {
+        let mut repo = MockRepo::new();
+        let article = Article {
+            status: Status::Scheduled,
+            publish_at: Some(Utc::now() + chrono::Duration::hours(1)),
+            ..draft()
+        };
+        let article_id = article.id;
+
+        repo.expect_articles_get()
+            .returning(move |_| Ok(article.clone()));
+        repo.expect_article_set_status()
+            .with(eq(article_id), eq(Status::Draft))
+            .returning(|_, _| Ok(()));
+
+        let admin = DefaultAdmin::new(Arc::new(repo), 10);
+
+        let result = admin.unschedule(article_id).await;
+
+        assert!(result.is_ok());
+    }
+
+    #[tokio::test]
+    async fn unschedule_not_scheduled() {
+        let mut repo = MockRepo::new();
+        let article = draft();
+
+        repo.expect_articles_get()
+            .returning(move |_| Ok(article.clone()));
+
+        let admin = DefaultAdmin::new(Arc::new(repo), 10);
+
+        let result = admin.unschedule(Uuid::new_v4()).await;
+
+        assert!(result.is_err());
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "invalid input: article is not scheduled"
+        );
+    }
+
+    #[tokio::test]
+    async fn publish_due_success() {
+        let mut repo = MockRepo::new();
+        let article_id = Uuid::new_v4();
+
+        repo.expect_articles_publish_due()
+            .withf(|now| *now <= Utc::now())
+            .returning(move |_| Ok(vec![article_id]));
+
+        let admin = DefaultAdmin::new(Arc::new(repo), 10);
+
+        let result = admin.publish_due().await;
+
+        assert_eq!(result.unwrap(), vec![article_id]);
+    }
+
+    #[tokio::test]
+    async fn move_to_draft_success() {
+        let mut repo = MockRepo::new();
+        let article_id = Uuid::new_v4();
+
+        repo.expect_articles_exists().returning(move |_| Ok(()));
+        repo.expect_article_set_status()
+            .with(eq(article_id), eq(Status::Draft))
+            .returning(|_, _| Ok(()));
+
+        let admin = DefaultAdmin::new(Arc::new(repo), 10);
+
+        let result = admin.move_to_draft(article_id).await;
+
+        assert!(result.is_ok());
+    }
+
+    #[tokio::test]
+    async fn move_to_draft_notfound() {
+        let mut repo = MockRepo::new();
+
+        repo.expect_articles_exists()
+            .returning(|_| Err(Error::NotFound("article".to_string())));
+
+        let admin = DefaultAdmin::new(Arc::new(repo), 10);
+
+        let result = admin.move_to_draft(Uuid::new_v4()).await;
+
+        assert!(result.is_err());
+        assert_eq!(result.unwrap_err().to_string(), "article not found");
+    }
+
+    #[tokio::test]
+    async fn move_to_trash_success() {
+        let mut repo = MockRepo::new();
+        let article_id = Uuid::new_v4();
+
+        repo.expect_articles_exists().returning(move |_| Ok(()));
+        repo.expect_article_set_status()
+            .with(eq(article_id), eq(Status::Trash))
+            .returning(|_, _| Ok(()));
+
+        let admin = DefaultAdmin::new(Arc::new(repo), 10);
+
+        let result = admin.move_to_trash(article_id).await;
+
+        assert!(result.is_ok());
+    }
+
+    #[tokio::test]
+    async fn move_to_trash_notfound() {
+        let mut repo = MockRepo::new();
+
+        repo.expect_articles_exists()
+            .returning(|_| Err(Error::NotFound("article".to_string())));
+
+        let admin = DefaultAdmin::new(Arc::new(repo), 10);
+
+        let result = admin.move_to_trash(Uuid::new_v4()).await;
+
+        assert!(result.is_err());
+        assert_eq!(result.unwrap_err().to_string(), "article not found");
+    }
+
+    #[tokio::test]
+    async fn delete_success() {
+        let mut repo = MockRepo::new();
+        let article_id = Uuid::new_v4();
+
+        repo.expect_articles_exists().returning(move |_| Ok(()));
+        repo.expect_article_delete()
+            .with(eq(article_id))
+            .returning(|_| Ok(()));
+
+        let admin = DefaultAdmin::new(Arc::new(repo), 10);
+
+        let result = admin.delete(article_id).await;
+
+        assert!(result.is_ok());
+    }
+
+    #[tokio::test]
+    async fn delete_notfound() {
+        let mut repo = MockRepo::new();
+
+        repo.expect_articles_exists()
+            .returning(|_| Err(Error::NotFound("article".to_string())));
+
+        let admin = DefaultAdmin::new(Arc::new(repo), 10);
+
+        let result = admin.delete(Uuid::new_v4()).await;
+
+        assert!(result.is_err());
+        assert_eq!(result.unwrap_err().to_string(), "article not found");
+    }
+
+    fn revision(article_id: Uuid, content: &str) -> Revision {
+        Revision {
+            id: Uuid::new_v4(),
+            article_id,
+            title: "title".to_string(),
+            description: "description".to_string(),
+            content: content.to_string(),
+            slug: "old-slug".to_string(),
+            tags: vec!["rust".to_string()],
+            category: Some("tooling".to_string()),
+            author: "author".to_string(),
+            created_at: Utc::now(),
+        }
+    }
+
+    #[tokio::test]
+    async fn revisions_notfound() {
+        let mut repo = MockRepo::new();
+
+        repo.expect_articles_exists()
+            .returning(|_| Err(Error::NotFound("article".to_string())));
+
+        let admin = DefaultAdmin::new(Arc::new(repo), 10);
+
+        let result = admin.revisions(Uuid::new_v4()).await;
+
+        assert!(result.is_err());
+        assert_eq!(result.unwrap_err().to_string(), "article not found");
+    }
+
+    #[tokio::test]
+    async fn diff_success() {
+        let mut repo = MockRepo::new();
+        let article_id = Uuid::new_v4();
+        let older = revision(article_id, "one\ntwo\n");
+        let newer = revision(article_id, "one\nthree\n");
+        let (from, to) = (older.id, newer.id);
+
+        repo.expect_revisions_get()
+            .with(eq(article_id), eq(from))
+            .returning(move |_, _| Ok(older.clone()));
+        repo.expect_revisions_get()
+            .with(eq(article_id), eq(to))
+            .returning(move |_, _| Ok(newer.clone()));
+
+        let admin = DefaultAdmin::new(Arc::new(repo), 10);
+
+        let diff = admin.diff(article_id, from, to).await.unwrap();
+
+        assert_eq!(diff.from, from);
+        assert_eq!(diff.to, to);
+        assert!(diff.title.is_empty());
+        assert!(diff.description.is_empty());
+        assert!(diff.content.contains("-two"));
+        assert!(diff.content.contains("+three"));
+    }
+
+    #[tokio::test]
+    async fn diff_revision_notfound() {
+        let mut repo = MockRepo::new();
+
+        repo.expect_revisions_get()
+            .returning(|_, id| Err(Error::NotFound(format!("revision {}", id))));
+
+        let admin = DefaultAdmin::new(Arc::new(repo), 10);
+
+        let from = Uuid::new_v4();
+        let result = admin.diff(Uuid::new_v4(), from, Uuid::new_v4()).await;
+
+        assert!(result.is_err());
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            format!("revision {} not found", from)
+        );
+    }
+
+    #[tokio::test]
+    async fn restore_success() {
+        let mut repo = MockRepo::new();
+        let article = Article {
+            id: Uuid::new_v4(),
+            title: "new title".to_string(),
+            description: "new description".to_string(),
+            content: "new content".to_string(),
+            author: "author".to_string(),
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+            status: Status::Published,
+            publish_at: None,
+            version: 1,
+            slug: "new-title".to_string(),
+            tags: vec![],
+            category: Some("languages".to_string()),
+        };
+        let old = revision(article.id, "old content");
+        let revision_id = old.id;
+        let article_id = article.id;
+
+        repo.expect_revisions_get()
+            .with(eq(article_id), eq(revision_id))
+            .returning(move |_, _| Ok(old.clone()));
+        repo.expect_articles_get()
+            .returning(move |_| Ok(article.clone()));
+        repo.expect_article_update()
+            .withf(move |updated, editor| {
+                updated.id == article_id
+                    && updated.content == "old content"
+                    && updated.slug == "new-title"
+                    && updated.tags == vec!["rust".to_string()]
+                    && updated.category == Some("tooling".to_string())
+                    && updated.status == Status::Published
+                    && editor == "editor"
+            })
+            .returning(|_, _| Ok(()));
+
+        let admin = DefaultAdmin::new(Arc::new(repo), 10);
+
+        let result = admin
+            .restore(article_id, revision_id, "editor".to_string())
+            .await;
+
+        assert!(result.is_ok());
+    }
+
+    fn with_role(role: Option<Role>) -> MockRepo {
+        let mut repo = MockRepo::new();
+        repo.expect_roles_get().returning(move |_| Ok(role));
+        repo
+    }
+
+    #[tokio::test]
+    async fn role_bootstrap_admin() {
+        let repo = MockRepo::new();
+        let admin = DefaultAdmin::new(Arc::new(repo), 10).with_admins(vec!["owner".to_string()]);
+
+        let role = admin.role("owner".to_string()).await.unwrap();
+
+        assert_eq!(role, Role::Admin);
+    }
+
+    #[tokio::test]
+    async fn role_missing() {
+        let admin = DefaultAdmin::new(Arc::new(with_role(None)), 10);
+
+        let result = admin.authorize("bob".to_string(), Action::Read).await;
+
+        assert!(result.is_err());
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "permission denied: no role assigned to bob"
+        );
+    }
+
+    #[tokio::test]
+    async fn authorize_viewer() {
+        let admin = DefaultAdmin::new(Arc::new(with_role(Some(Role::Viewer))), 10);
+
+        assert!(
+            admin
+                .authorize("bob".to_string(), Action::Read)
+                .await
+                .is_ok()
+        );
+        assert!(
+            admin
+                .authorize("bob".to_string(), Action::Create)
+                .await
+                .is_err()
+        );
+        assert!(
+            admin
+                .authorize("bob".to_string(), Action::Publish)
+                .await
+                .is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn authorize_author_own_draft() {
+        let mut repo = with_role(Some(Role::Author));
+        let article = Article {
+            author: "bob".to_string(),
+            ..draft()
+        };
+        let article_id = article.id;
+
+        repo.expect_articles_get()
+            .with(eq(article_id))
+            .returning(move |_| Ok(article.clone()));
+
+        let admin = DefaultAdmin::new(Arc::new(repo), 10);
+
+        assert!(
+            admin
+                .authorize("bob".to_string(), Action::Edit(article_id))
+                .await
+                .is_ok()
+        );
+        assert!(
+            admin
+                .authorize("bob".to_string(), Action::Create)
+                .await
+                .is_ok()
+        );
+        assert!(
+            admin
+                .authorize("bob".to_string(), Action::Publish)
+                .await
+                .is_err()
+        );
+        assert!(
+            admin
+                .authorize("bob".to_string(), Action::Delete)
+                .await
+                .is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn authorize_author_other_article() {
+        let mut repo = with_role(Some(Role::Author));
+        let article = Article {
+            author: "alice".to_string(),
+            ..draft()
+        };
+
+        repo.expect_articles_get()
+            .returning(move |_| Ok(article.clone()));
+
+        let admin = DefaultAdmin::new(Arc::new(repo), 10);
+
+        let result = admin
+            .authorize("bob".to_string(), Action::Edit(Uuid::new_v4()))
+            .await;
+
+        assert!(matches!(result, Err(Error::PermissionDenied(_))));
+    }
+
+    #[tokio::test]
+    async fn authorize_author_published_article() {
+        let mut repo = with_role(Some(Role::Author));
+        let article = Article {
+            author: "bob".to_string(),
+            status: Status::Published,
+            ..draft()
+        };
+
+        repo.expect_articles_get()
+            .returning(move |_| Ok(article.clone()));
+
+        let admin = DefaultAdmin::new(Arc::new(repo), 10);
+
+        let result = admin
+            .authorize("bob".to_string(), Action::Edit(Uuid::new_v4()))
+            .await;
+
+        assert!(matches!(result, Err(Error::PermissionDenied(_))));
+    }
+
+    #[tokio::test]
+    async fn authorize_editor() {
+        let admin = DefaultAdmin::new(Arc::new(with_role(Some(Role::Editor))), 10);
+
+        assert!(
+            admin
+                .authorize("bob".to_string(), Action::Edit(Uuid::new_v4()))
+                .await
+                .is_ok()
+        );
+        assert!(
+            admin
+                .authorize("bob".to_string(), Action::Delete)
+                .await
+                .is_ok()
+        );
+        assert!(
+            admin
+                .authorize("bob".to_string(), Action::ManageRoles)
+                .await
+                .is_err()
+        );
+        assert!(
+            admin
+                .authorize("bob".to_string(), Action::ManageUsers)
+                .await
+                .is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn authorize_admin() {
+        let admin = DefaultAdmin::new(Arc::new(with_role(Some(Role::Admin))), 10);
+
+        let result = admin
+            .authorize("bob".to_string(), Action::ManageRoles)
+            .await;
+
+        assert_eq!(result.unwrap(), Role::Admin);
+    }
+
+    #[tokio::test]
+    async fn assign_role_success() {
+        let mut repo = MockRepo::new();
+
+        repo.expect_roles_set()
+            .with(eq("bob".to_string()), eq(Role::Editor))
+            .returning(|_, _| Ok(()));
+
+        let admin = DefaultAdmin::new(Arc::new(repo), 10);
+
+        let result = admin.assign_role("bob".to_string(), Role::Editor).await;
+
+        assert!(result.is_ok());
+    }
+}
+
+#[cfg(test)]
+mod default_reader_test {
+    use super::*;
+
+    fn article(status: Status) -> Article {
+        Article {
+            id: Uuid::new_v4(),
+            title: "title".to_string(),
+            description: "description".to_string(),
+            content: "content".to_string(),
+            author: "author".to_string(),
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+            status,
+            slug: "title".to_string(),
+            tags: vec![],
+            category: None,
+            publish_at: None,
+            version: 1,
+        }
+    }
+
+    #[tokio::test]
+    async fn get_published() {
+        let mut repo = MockRepo::new();
+        let article = article(Status::Published);
+        let article2 = article.clone();
+
+        repo.expect_articles_get()
+            .with(eq(article.id))
+            .returning(move |_| Ok(article2.clone()));
+
+        let reader = DefaultReader::new(Arc::new(repo), 10);
+
+        let result = reader.get(article.id).await;
+
+        assert!(result.is_ok());
+        assert_eq!(result.unwrap().id, article.id);
+    }
+
+    #[tokio::test]
+    async fn get_draft_is_not_found() {
+        let mut repo = MockRepo::new();
+        let article = article(Status::Draft);
+        let article2 = article.clone();
+
+        repo.expect_articles_get()
+            .returning(move |_| Ok(article2.clone()));
+
+        let reader = DefaultReader::new(Arc::new(repo), 10);
+
+        let result = reader.get(article.id).await;
+
+        assert!(result.is_err());
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            format!("article {} not found", article.id)
+        );
+    }
+
+    #[tokio::test]
+    async fn get_trash_is_not_found() {
+        let mut repo = MockRepo::new();
+        let article = article(Status::Trash);
+        let article2 = article.clone();
+
+        repo.expect_articles_get()
+            .returning(move |_| Ok(article2.clone()));
+
+        let reader = DefaultReader::new(Arc::new(repo), 10);
+
+        let result = reader.get(article.id).await;
+
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn get_by_slug_published() {
+        let mut repo = MockRepo::new();
+        let article = article(Status::Published);
+        let article2 = article.clone();
+
+        repo.expect_articles_get_by_slug()
+            .with(eq("title".to_string()))
+            .returning(move |_| Ok(article2.clone()));
+
+        let reader = DefaultReader::new(Arc::new(repo), 10);
+
+        let result = reader.get_by_slug("title".to_string()).await;
+
+        match result {
+            Ok(SlugLookup::Found(found)) => assert_eq!(found.id, article.id),
+            _ => panic!("expected article to be found"),
+        }
+    }
+
+    #[tokio::test]
+    async fn get_by_slug_moved() {
+        let mut repo = MockRepo::new();
+        let article = article(Status::Published);
+
+        repo.expect_articles_get_by_slug()
+            .with(eq("old".to_string()))
+            .returning(|slug| Err(Error::NotFound(format!("article {}", slug))));
+        repo.expect_articles_slug_redirect()
+            .with(eq("old".to_string()))
+            .returning(|_| Ok("title".to_string()));
+        repo.expect_articles_get_by_slug()
+            .with(eq("title".to_string()))
+            .returning(move |_| Ok(article.clone()));
+
+        let reader = DefaultReader::new(Arc::new(repo), 10);
+
+        let result = reader.get_by_slug("old".to_string()).await;
+
+        match result {
+            Ok(SlugLookup::Moved(slug)) => assert_eq!(slug, "title"),
+            _ => panic!("expected article to have moved"),
+        }
+    }
+
+    #[tokio::test]
+    async fn get_by_slug_moved_draft_is_not_found() {
+        let mut repo = MockRepo::new();
+        let article = article(Status::Draft);
+
+        repo.expect_articles_get_by_slug()
+            .with(eq("old".to_string()))
+            .returning(|slug| Err(Error::NotFound(format!("article {}", slug))));
+        repo.expect_articles_slug_redirect()
+            .returning(|_| Ok("title".to_string()));
+        repo.expect_articles_get_by_slug()
+            .with(eq("title".to_string()))
+            .returning(move |_| Ok(article.clone()));
+
+        let reader = DefaultReader::new(Arc::new(repo), 10);
+
+        let result = reader.get_by_slug("old".to_string()).await;
+
+        assert!(result.is_err());
+        assert_eq!(result.err().unwrap().to_string(), "article old not found");
+    }
+
+    #[tokio::test]
+    async fn get_by_slug_unknown() {
+        let mut repo = MockRepo::new();
+
+        repo.expect_articles_get_by_slug()
+            .returning(|slug| Err(Error::NotFound(format!("article {}", slug))));
+        repo.expect_articles_slug_redirect()
+            .returning(|slug| Err(Error::NotFound(format!("slug {}", slug))));
+
+        let reader = DefaultReader::new(Arc::new(repo), 10);
+
+        let result = reader.get_by_slug("nope".to_string()).await;
+
+        assert!(result.is_err());
+        assert_eq!(result.err().unwrap().to_string(), "article nope not found");
+    }
+
+    #[tokio::test]
+    async fn list_only_published() {
+        let mut repo = MockRepo::new();
+        let articles = vec![article(Status::Published), article(Status::Published)];
+
+        repo.expect_articles_list()
+            .with(
+                eq(ArticlesListOptions {
+                    status: Some(Status::Published),
+                    tag: None,
+                    category: None,
+                }),
+                eq(10),
+                eq(10),
+            )
+            .returning(move |_, _, _| Ok((articles.clone(), 12)));
+
+        let reader = DefaultReader::new(Arc::new(repo), 10);
+
+        let result = reader.list(ArticlesListOptions::from_str("draft"), 2).await;
+
+        assert!(result.is_ok());
+        let listing = result.unwrap();
+        assert_eq!(listing.items.len(), 2);
+        assert_eq!(listing.pages, 2);
+    }
+
+    #[tokio::test]
+    async fn list_by_tag() {
+        let mut repo = MockRepo::new();
+
+        repo.expect_articles_list()
+            .with(
+                eq(ArticlesListOptions {
+                    status: Some(Status::Published),
+                    tag: Some("rust".to_string()),
+                    category: None,
+                }),
+                eq(10),
+                eq(0),
+            )
+            .returning(|_, _, _| Ok((vec![], 0)));
+
+        let reader = DefaultReader::new(Arc::new(repo), 10);
+
+        let opts = ArticlesListOptions::default().with_tag(Some("Rust".to_string()));
+        let result = reader.list(opts, 1).await;
+
+        assert!(result.is_ok());
+    }
+
+    #[tokio::test]
+    async fn list_by_category() {
+        let mut repo = MockRepo::new();
+
+        repo.expect_articles_list()
+            .with(
+                eq(ArticlesListOptions {
+                    status: Some(Status::Published),
+                    tag: None,
+                    category: Some("web-dev".to_string()),
+                }),
+                eq(10),
+                eq(0),
+            )
+            .returning(|_, _, _| Ok((vec![], 0)));
+
+        let reader = DefaultReader::new(Arc::new(repo), 10);
+
+        let opts = ArticlesListOptions::default().with_category(Some("Web Dev".to_string()));
+        let result = reader.list(opts, 1).await;
+
+        assert!(result.is_ok());
+    }
 }
@@ -1,11 +1,16 @@
-use super::Admin;
-use crate::auth::{SessionManager, http::load_user};
+use super::{Action, Admin, ArticleChanges, Role, RoleAssignment, markdown};
+use crate::auth::tokens::Scope;
+use crate::auth::{Directory, SessionManager, User, http::load_user};
 use crate::blog::ArticlesListOptions;
-use crate::web::Listing;
+use crate::errors::Error;
+use crate::web::{Listing, csrf};
+use actix_web::http::header::{self, ETag, EntityTag, Header, IfMatch};
+use actix_web::middleware::from_fn;
 use actix_web::{
     App, HttpRequest, HttpResponse, HttpServer, Responder, delete, get, patch, post, put, web,
 };
-use serde::Deserialize;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
 use std::sync::Arc;
 use tracing_actix_web::TracingLogger;
 use utoipa::ToSchema;
@@ -14,14 +19,51 @@ use uuid::Uuid;
 struct State {
     admin: Arc<dyn Admin>,
     sessions: Arc<dyn SessionManager>,
+    directory: Arc<dyn Directory>,
     cookie_name: String,
 }
 
+/// API token scope needed for `action`.
+fn scope(action: &Action) -> Scope {
+    match action {
+        Action::Read => Scope::Read,
+        Action::Create | Action::Edit(_) => Scope::Write,
+        Action::Publish | Action::Delete => Scope::Publish,
+        Action::ManageRoles | Action::ManageUsers => Scope::Admin,
+    }
+}
+
+/// Loads the caller and checks that their role, and token scopes if any, allow `action`.
+async fn authorize(state: &State, req: HttpRequest, action: Action) -> Result<User, Error> {
+    let caller = load_user(req, &state.sessions, state.cookie_name.as_str()).await?;
+
+    let scope = scope(&action);
+    if !caller.allows(scope) {
+        return Err(Error::PermissionDenied(format!(
+            "api token lacks the {} scope",
+            scope
+        )));
+    }
+
+    state
+        .admin
+        .authorize(caller.user.login.clone(), action)
+        .await?;
+
+    Ok(caller.user)
+}
+
 #[derive(Deserialize, ToSchema)]
 pub struct ArticleRequest {
     title: String,
     description: String,
     content: String,
+    /// Only used on update, new articles get a slug generated from the title.
+    slug: Option<String>,
+    /// Replaces all tags of the article, omit to leave them unchanged on update.
+    tags: Option<Vec<String>>,
+    /// Category to file the article under, omit to leave it unchanged on update.
+    category: Option<String>,
 }
 
 // This is to allow openapi schema to be derived using utoipa
@@ -38,6 +80,11 @@ pub struct ArticleResponse {
     created_at: String,
     status: String,
     author: String,
+    slug: String,
+    tags: Vec<String>,
+    category: Option<String>,
+    publish_at: Option<String>,
+    version: i32,
 }
 
 #[utoipa::path(
@@ -56,7 +103,7 @@ pub async fn create_article(
     req: HttpRequest,
     body: web::Json<ArticleRequest>,
 ) -> impl Responder {
-    let user = match load_user(req, &state.sessions, state.cookie_name.as_str()).await {
+    let user = match authorize(&state, req, Action::Create).await {
         Ok(user) => user,
         Err(err) => return err.to_http_response(),
     };
@@ -65,7 +112,14 @@ pub async fn create_article(
 
     match state
         .admin
-        .create(data.title, data.description, data.content, user.login)
+        .create(
+            data.title,
+            data.description,
+            data.content,
+            user.login,
+            data.tags.unwrap_or_default(),
+            data.category,
+        )
         .await
     {
         Ok(article) => HttpResponse::Accepted().json(article),
@@ -76,6 +130,8 @@ pub async fn create_article(
 #[derive(Deserialize, ToSchema)]
 struct ArticlesListRequest {
     status: Option<String>,
+    tag: Option<String>,
+    category: Option<String>,
     page: Option<i64>,
 }
 
@@ -88,9 +144,9 @@ struct ArticlesListRequest {
         (status = 200, description = "Articles listing", body = Listing<ArticleResponse>),
     ),
     params(
-        ("id" = String, Path, description = "List articles"),
-        ("status" = String, Query, description = "Filter by status"),
         ("status" = Option<String>, Query, description = "Filter by status"),
+        ("tag" = Option<String>, Query, description = "Filter by tag"),
+        ("category" = Option<String>, Query, description = "Filter by category"),
         ("page" = Option<i64>, Query, description = "Page number"),
     )
 )]
@@ -100,14 +156,16 @@ pub async fn list_articles(
     req: HttpRequest,
     query: web::Query<ArticlesListRequest>,
 ) -> impl Responder {
-    if let Err(err) = load_user(req, &state.sessions, state.cookie_name.as_str()).await {
+    if let Err(err) = authorize(&state, req, Action::Read).await {
         return err.to_http_response();
     }
 
     let opts = match &query.status {
         Some(s) => ArticlesListOptions::from_str(s.as_str()),
-        None => ArticlesListOptions::All,
-    };
+        None => ArticlesListOptions::default(),
+    }
+    .with_tag(query.tag.clone())
+    .with_category(query.category.clone());
 
     let page = match query.page {
         Some(p) => {
@@ -126,13 +184,86 @@ pub async fn list_articles(
     }
 }
 
+#[derive(Deserialize, ToSchema)]
+struct ArticlesSearchRequest {
+    q: String,
+    status: Option<String>,
+    tag: Option<String>,
+    category: Option<String>,
+    page: Option<i64>,
+}
+
+// Matches SearchResult, see ArticleResponse.
+#[allow(dead_code)]
+#[derive(ToSchema)]
+pub struct SearchResultResponse {
+    id: String,
+    title: String,
+    description: String,
+    content: String,
+    updated_at: String,
+    created_at: String,
+    status: String,
+    author: String,
+    slug: String,
+    tags: Vec<String>,
+    category: Option<String>,
+    rank: f32,
+    snippet: String,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/articles/search",
+    description = "Search articles by title, description and content",
+    tag = "blog",
+    responses(
+        (status = 200, description = "Search results, most relevant first", body = Listing<SearchResultResponse>),
+    ),
+    params(
+        ("q" = String, Query, description = "Search terms, supports quotes, `or` and `-`"),
+        ("status" = Option<String>, Query, description = "Filter by status"),
+        ("tag" = Option<String>, Query, description = "Filter by tag"),
+        ("category" = Option<String>, Query, description = "Filter by category"),
+        ("page" = Option<i64>, Query, description = "Page number"),
+    )
+)]
+#[get("/api/articles/search")]
+pub async fn search_articles(
+    state: web::Data<State>,
+    req: HttpRequest,
+    query: web::Query<ArticlesSearchRequest>,
+) -> impl Responder {
+    if let Err(err) = authorize(&state, req, Action::Read).await {
+        return err.to_http_response();
+    }
+
+    let query = query.into_inner();
+
+    let opts = match &query.status {
+        Some(s) => ArticlesListOptions::from_str(s.as_str()),
+        None => ArticlesListOptions::default(),
+    }
+    .with_tag(query.tag)
+    .with_category(query.category);
+
+    match state
+        .admin
+        .search(query.q, opts, query.page.unwrap_or(1))
+        .await
+    {
+        Ok(listing) => HttpResponse::Ok().json(listing),
+        Err(err) => err.to_http_response(),
+    }
+}
+
 #[utoipa::path(
     get,
     path = "/api/articles/{id}",
     description = "Get a specific article",
     tag = "blog",
     responses(
-        (status = 200, description = "Article", body = ArticleResponse),
+        (status = 200, description = "Article, with its version as ETag", body = ArticleResponse),
     ),
     params(
         ("id" = String, Path, description = "Article id"),
@@ -144,18 +275,47 @@ pub async fn get_article(
     req: HttpRequest,
     path: web::Path<(Uuid,)>,
 ) -> impl Responder {
-    if let Err(err) = load_user(req, &state.sessions, state.cookie_name.as_str()).await {
+    if let Err(err) = authorize(&state, req, Action::Read).await {
         return err.to_http_response();
     }
 
     let id = path.into_inner().0;
 
     match state.admin.get(id).await {
-        Ok(article) => HttpResponse::Ok().json(article),
+        Ok(article) => HttpResponse::Ok()
+            .insert_header(ETag(EntityTag::new_strong(article.version.to_string())))
+            .json(article),
         Err(err) => err.to_http_response(),
     }
 }
 
+/// Reads the version an update was made against from the `If-Match` header.
+/// No header, or `*`, means any version.
+fn if_match_version(req: &HttpRequest) -> Result<Option<i32>, Error> {
+    if !req.headers().contains_key(header::IF_MATCH) {
+        return Ok(None);
+    }
+
+    let tags = match IfMatch::parse(req) {
+        Ok(IfMatch::Any) => return Ok(None),
+        Ok(IfMatch::Items(tags)) => tags,
+        Err(_) => {
+            return Err(Error::InvalidInput("malformed If-Match header".to_string()));
+        }
+    };
+
+    match tags.as_slice() {
+        [tag] if !tag.weak => tag
+            .tag()
+            .parse()
+            .map(Some)
+            .map_err(|_| Error::PreconditionFailed(format!("unknown version {}", tag))),
+        _ => Err(Error::PreconditionFailed(
+            "If-Match must be a single strong ETag".to_string(),
+        )),
+    }
+}
+
 #[utoipa::path(
     patch,
     path = "/api/articles/{id}",
@@ -163,9 +323,11 @@ pub async fn get_article(
     tag = "blog",
     responses(
         (status = 202, description = "Article updated"),
+        (status = 412, description = "Article was modified since the version in If-Match"),
     ),
     params(
         ("id" = String, Path, description = "Article id"),
+        ("If-Match" = Option<String>, Header, description = "ETag returned when the article was fetched"),
     ),
     request_body(content=ArticleRequest, content_type = "application/json")
 )]
@@ -176,18 +338,31 @@ pub async fn update_article(
     path: web::Path<(Uuid,)>,
     body: web::Json<ArticleRequest>,
 ) -> impl Responder {
-    if let Err(err) = load_user(req, &state.sessions, state.cookie_name.as_str()).await {
-        return err.to_http_response();
-    }
+    let version = match if_match_version(&req) {
+        Ok(version) => version,
+        Err(err) => return err.to_http_response(),
+    };
 
     let id = path.into_inner().0;
+
+    let user = match authorize(&state, req, Action::Edit(id)).await {
+        Ok(user) => user,
+        Err(err) => return err.to_http_response(),
+    };
+
     let data = body.into_inner();
 
-    match state
-        .admin
-        .update(id, data.title, data.description, data.content)
-        .await
-    {
+    let changes = ArticleChanges {
+        title: data.title,
+        description: data.description,
+        content: data.content,
+        slug: data.slug,
+        tags: data.tags,
+        category: data.category.map(Some),
+        version,
+    };
+
+    match state.admin.update(id, changes, user.login).await {
         Ok(_) => HttpResponse::Accepted().finish(),
         Err(err) => err.to_http_response(),
     }
@@ -211,7 +386,7 @@ pub async fn publish_article(
     req: HttpRequest,
     path: web::Path<(Uuid,)>,
 ) -> impl Responder {
-    if let Err(err) = load_user(req, &state.sessions, state.cookie_name.as_str()).await {
+    if let Err(err) = authorize(&state, req, Action::Publish).await {
         return err.to_http_response();
     }
 
@@ -223,6 +398,75 @@ pub async fn publish_article(
     }
 }
 
+#[derive(Deserialize, ToSchema)]
+pub struct ScheduleRequest {
+    /// RFC 3339 timestamp, must be in the future.
+    #[schema(value_type = String)]
+    publish_at: DateTime<Utc>,
+}
+
+#[utoipa::path(
+    put,
+    path = "/api/articles/{id}/status/schedule",
+    description = "Schedule article to be published later, or reschedule it",
+    tag = "blog",
+    responses(
+        (status = 202, description = "Article scheduled"),
+    ),
+    params(
+        ("id" = String, Path, description = "Article id"),
+    ),
+    request_body(content=ScheduleRequest, content_type = "application/json")
+)]
+#[put("/api/articles/{id}/status/schedule")]
+pub async fn schedule_article(
+    state: web::Data<State>,
+    req: HttpRequest,
+    path: web::Path<(Uuid,)>,
+    body: web::Json<ScheduleRequest>,
+) -> impl Responder {
+    if let Err(err) = authorize(&state, req, Action::Publish).await {
+        return err.to_http_response();
+    }
+
+    let id = path.into_inner().0;
+
+    match state.admin.schedule(id, body.publish_at).await {
+        Ok(_) => HttpResponse::Accepted().finish(),
+        Err(err) => err.to_http_response(),
+    }
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/articles/{id}/status/schedule",
+    description = "Cancel a scheduled publication, the article goes back to draft",
+    tag = "blog",
+    responses(
+        (status = 202, description = "Schedule cancelled"),
+    ),
+    params(
+        ("id" = String, Path, description = "Article id"),
+    ),
+)]
+#[delete("/api/articles/{id}/status/schedule")]
+pub async fn unschedule_article(
+    state: web::Data<State>,
+    req: HttpRequest,
+    path: web::Path<(Uuid,)>,
+) -> impl Responder {
+    if let Err(err) = authorize(&state, req, Action::Publish).await {
+        return err.to_http_response();
+    }
+
+    let id = path.into_inner().0;
+
+    match state.admin.unschedule(id).await {
+        Ok(_) => HttpResponse::Accepted().finish(),
+        Err(err) => err.to_http_response(),
+    }
+}
+
 #[utoipa::path(
     put,
     path = "/api/articles/{id}/status/trash",
@@ -241,7 +485,7 @@ pub async fn move_article_to_trash(
     req: HttpRequest,
     path: web::Path<(Uuid,)>,
 ) -> impl Responder {
-    if let Err(err) = load_user(req, &state.sessions, state.cookie_name.as_str()).await {
+    if let Err(err) = authorize(&state, req, Action::Publish).await {
         return err.to_http_response();
     }
 
@@ -271,7 +515,7 @@ pub async fn move_article_to_draft(
     req: HttpRequest,
     path: web::Path<(Uuid,)>,
 ) -> impl Responder {
-    if let Err(err) = load_user(req, &state.sessions, state.cookie_name.as_str()).await {
+    if let Err(err) = authorize(&state, req, Action::Publish).await {
         return err.to_http_response();
     }
 
@@ -301,7 +545,7 @@ pub async fn delete_article(
     req: HttpRequest,
     path: web::Path<(Uuid,)>,
 ) -> impl Responder {
-    if let Err(err) = load_user(req, &state.sessions, state.cookie_name.as_str()).await {
+    if let Err(err) = authorize(&state, req, Action::Delete).await {
         return err.to_http_response();
     }
 
@@ -313,32 +557,821 @@ pub async fn delete_article(
     }
 }
 
-pub async fn server(
-    admin: Arc<dyn Admin>,
-    sessions: Arc<dyn SessionManager>,
-    cookie_name: String,
-    listen_addr: String,
-) -> Result<(), std::io::Error> {
-    let data = web::Data::new(State {
-        admin,
-        sessions,
-        cookie_name,
-    });
+#[utoipa::path(
+    get,
+    path = "/api/tags",
+    description = "List tags in use",
+    tag = "blog",
+    responses(
+        (status = 200, description = "Tags", body = Vec<String>),
+    ),
+)]
+#[get("/api/tags")]
+pub async fn list_tags(state: web::Data<State>, req: HttpRequest) -> impl Responder {
+    if let Err(err) = authorize(&state, req, Action::Read).await {
+        return err.to_http_response();
+    }
 
-    HttpServer::new(move || {
-        App::new()
-            .app_data(data.clone())
-            .wrap(TracingLogger::default())
-            .service(create_article)
-            .service(list_articles)
-            .service(get_article)
-            .service(update_article)
-            .service(publish_article)
-            .service(move_article_to_trash)
-            .service(move_article_to_draft)
-            .service(delete_article)
-    })
-    .bind(listen_addr)?
-    .run()
-    .await
+    match state.admin.tags().await {
+        Ok(tags) => HttpResponse::Ok().json(tags),
+        Err(err) => err.to_http_response(),
+    }
+}
+
+#[utoipa::path(
+    put,
+    path = "/api/articles/{id}/tags/{tag}",
+    description = "Attach a tag to an article",
+    tag = "blog",
+    responses(
+        (status = 202, description = "Tag attached"),
+    ),
+    params(
+        ("id" = String, Path, description = "Article id"),
+        ("tag" = String, Path, description = "Tag"),
+    ),
+)]
+#[put("/api/articles/{id}/tags/{tag}")]
+pub async fn tag_article(
+    state: web::Data<State>,
+    req: HttpRequest,
+    path: web::Path<(Uuid, String)>,
+) -> impl Responder {
+    let (id, tag) = path.into_inner();
+
+    let user = match authorize(&state, req, Action::Edit(id)).await {
+        Ok(user) => user,
+        Err(err) => return err.to_http_response(),
+    };
+
+    match state.admin.tag(id, tag, user.login).await {
+        Ok(_) => HttpResponse::Accepted().finish(),
+        Err(err) => err.to_http_response(),
+    }
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/articles/{id}/tags/{tag}",
+    description = "Detach a tag from an article",
+    tag = "blog",
+    responses(
+        (status = 202, description = "Tag detached"),
+    ),
+    params(
+        ("id" = String, Path, description = "Article id"),
+        ("tag" = String, Path, description = "Tag"),
+    ),
+)]
+#[delete("/api/articles/{id}/tags/{tag}")]
+pub async fn untag_article(
+    state: web::Data<State>,
+    req: HttpRequest,
+    path: web::Path<(Uuid, String)>,
+) -> impl Responder {
+    let (id, tag) = path.into_inner();
+
+    let user = match authorize(&state, req, Action::Edit(id)).await {
+        Ok(user) => user,
+        Err(err) => return err.to_http_response(),
+    };
+
+    match state.admin.untag(id, tag, user.login).await {
+        Ok(_) => HttpResponse::Accepted().finish(),
+        Err(err) => err.to_http_response(),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/categories",
+    description = "List categories in use",
+    tag = "blog",
+    responses(
+        (status = 200, description = "Categories", body = Vec<String>),
+    ),
+)]
+#[get("/api/categories")]
+pub async fn list_categories(state: web::Data<State>, req: HttpRequest) -> impl Responder {
+    if let Err(err) = authorize(&state, req, Action::Read).await {
+        return err.to_http_response();
+    }
+
+    match state.admin.categories().await {
+        Ok(categories) => HttpResponse::Ok().json(categories),
+        Err(err) => err.to_http_response(),
+    }
+}
+
+#[utoipa::path(
+    put,
+    path = "/api/articles/{id}/category/{category}",
+    description = "File an article under a category, replacing the previous one",
+    tag = "blog",
+    responses(
+        (status = 202, description = "Category set"),
+    ),
+    params(
+        ("id" = String, Path, description = "Article id"),
+        ("category" = String, Path, description = "Category"),
+    ),
+)]
+#[put("/api/articles/{id}/category/{category}")]
+pub async fn categorize_article(
+    state: web::Data<State>,
+    req: HttpRequest,
+    path: web::Path<(Uuid, String)>,
+) -> impl Responder {
+    let (id, category) = path.into_inner();
+
+    let user = match authorize(&state, req, Action::Edit(id)).await {
+        Ok(user) => user,
+        Err(err) => return err.to_http_response(),
+    };
+
+    match state.admin.categorize(id, category, user.login).await {
+        Ok(_) => HttpResponse::Accepted().finish(),
+        Err(err) => err.to_http_response(),
+    }
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/articles/{id}/category",
+    description = "Remove an article from its category",
+    tag = "blog",
+    responses(
+        (status = 202, description = "Category cleared"),
+    ),
+    params(
+        ("id" = String, Path, description = "Article id"),
+    ),
+)]
+#[delete("/api/articles/{id}/category")]
+pub async fn uncategorize_article(
+    state: web::Data<State>,
+    req: HttpRequest,
+    path: web::Path<(Uuid,)>,
+) -> impl Responder {
+    let id = path.into_inner().0;
+
+    let user = match authorize(&state, req, Action::Edit(id)).await {
+        Ok(user) => user,
+        Err(err) => return err.to_http_response(),
+    };
+
+    match state.admin.uncategorize(id, user.login).await {
+        Ok(_) => HttpResponse::Accepted().finish(),
+        Err(err) => err.to_http_response(),
+    }
+}
+
+// Same as Revision, see ArticleResponse.
+#[allow(dead_code)]
+#[derive(ToSchema)]
+pub struct RevisionResponse {
+    id: String,
+    article_id: String,
+    title: String,
+    description: String,
+    content: String,
+    slug: String,
+    tags: Vec<String>,
+    category: Option<String>,
+    author: String,
+    created_at: String,
+}
+
+// Same as RevisionDiff, see ArticleResponse.
+#[allow(dead_code)]
+#[derive(ToSchema)]
+pub struct RevisionDiffResponse {
+    from: String,
+    to: String,
+    title: String,
+    description: String,
+    content: String,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/articles/{id}/revisions",
+    description = "List revisions of an article, newest first",
+    tag = "blog",
+    responses(
+        (status = 200, description = "Revisions", body = Vec<RevisionResponse>),
+    ),
+    params(
+        ("id" = String, Path, description = "Article id"),
+    ),
+)]
+#[get("/api/articles/{id}/revisions")]
+pub async fn list_revisions(
+    state: web::Data<State>,
+    req: HttpRequest,
+    path: web::Path<(Uuid,)>,
+) -> impl Responder {
+    if let Err(err) = authorize(&state, req, Action::Read).await {
+        return err.to_http_response();
+    }
+
+    let id = path.into_inner().0;
+
+    match state.admin.revisions(id).await {
+        Ok(revisions) => HttpResponse::Ok().json(revisions),
+        Err(err) => err.to_http_response(),
+    }
+}
+
+#[derive(Deserialize)]
+struct RevisionDiffRequest {
+    from: Uuid,
+    to: Uuid,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/articles/{id}/revisions/diff",
+    description = "Compare two revisions of an article",
+    tag = "blog",
+    responses(
+        (status = 200, description = "Unified diff of each field", body = RevisionDiffResponse),
+    ),
+    params(
+        ("id" = String, Path, description = "Article id"),
+        ("from" = String, Query, description = "Older revision id"),
+        ("to" = String, Query, description = "Newer revision id"),
+    ),
+)]
+#[get("/api/articles/{id}/revisions/diff")]
+pub async fn diff_revisions(
+    state: web::Data<State>,
+    req: HttpRequest,
+    path: web::Path<(Uuid,)>,
+    query: web::Query<RevisionDiffRequest>,
+) -> impl Responder {
+    if let Err(err) = authorize(&state, req, Action::Read).await {
+        return err.to_http_response();
+    }
+
+    let id = path.into_inner().0;
+
+    match state.admin.diff(id, query.from, query.to).await {
+        Ok(diff) => HttpResponse::Ok().json(diff),
+        Err(err) => err.to_http_response(),
+    }
+}
+
+#[utoipa::path(
+    put,
+    path = "/api/articles/{id}/revisions/{revision}/restore",
+    description = "Restore an article to a previous revision",
+    tag = "blog",
+    responses(
+        (status = 202, description = "Article restored"),
+    ),
+    params(
+        ("id" = String, Path, description = "Article id"),
+        ("revision" = String, Path, description = "Revision id"),
+    ),
+)]
+#[put("/api/articles/{id}/revisions/{revision}/restore")]
+pub async fn restore_revision(
+    state: web::Data<State>,
+    req: HttpRequest,
+    path: web::Path<(Uuid, Uuid)>,
+) -> impl Responder {
+    let (id, revision) = path.into_inner();
+
+    let user = match authorize(&state, req, Action::Edit(id)).await {
+        Ok(user) => user,
+        Err(err) => return err.to_http_response(),
+    };
+
+    match state.admin.restore(id, revision, user.login).await {
+        Ok(_) => HttpResponse::Accepted().finish(),
+        Err(err) => err.to_http_response(),
+    }
+}
+
+// Same as RoleAssignment, see ArticleResponse.
+#[allow(dead_code)]
+#[derive(ToSchema)]
+pub struct RoleResponse {
+    login: String,
+    /// One of admin, editor, author, viewer.
+    role: String,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct RoleRequest {
+    /// One of admin, editor, author, viewer.
+    #[schema(value_type = String)]
+    role: Role,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/roles/me",
+    description = "Get the role of the current user",
+    tag = "blog",
+    responses(
+        (status = 200, description = "Role of the current user", body = RoleResponse),
+    ),
+)]
+#[get("/api/roles/me")]
+pub async fn my_role(state: web::Data<State>, req: HttpRequest) -> impl Responder {
+    let user = match load_user(req, &state.sessions, state.cookie_name.as_str()).await {
+        Ok(caller) => caller.user,
+        Err(err) => return err.to_http_response(),
+    };
+
+    match state.admin.role(user.login.clone()).await {
+        Ok(role) => HttpResponse::Ok().json(RoleAssignment {
+            login: user.login,
+            role,
+        }),
+        Err(err) => err.to_http_response(),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/roles",
+    description = "List role assignments",
+    tag = "blog",
+    responses(
+        (status = 200, description = "Role assignments", body = Vec<RoleResponse>),
+    ),
+)]
+#[get("/api/roles")]
+pub async fn list_roles(state: web::Data<State>, req: HttpRequest) -> impl Responder {
+    if let Err(err) = authorize(&state, req, Action::ManageRoles).await {
+        return err.to_http_response();
+    }
+
+    match state.admin.roles().await {
+        Ok(roles) => HttpResponse::Ok().json(roles),
+        Err(err) => err.to_http_response(),
+    }
+}
+
+#[utoipa::path(
+    put,
+    path = "/api/roles/{login}",
+    description = "Assign a role to a user",
+    tag = "blog",
+    responses(
+        (status = 202, description = "Role assigned"),
+    ),
+    params(
+        ("login" = String, Path, description = "User login"),
+    ),
+    request_body(content=RoleRequest, content_type = "application/json")
+)]
+#[put("/api/roles/{login}")]
+pub async fn assign_role(
+    state: web::Data<State>,
+    req: HttpRequest,
+    path: web::Path<(String,)>,
+    body: web::Json<RoleRequest>,
+) -> impl Responder {
+    if let Err(err) = authorize(&state, req, Action::ManageRoles).await {
+        return err.to_http_response();
+    }
+
+    let login = path.into_inner().0;
+
+    match state.admin.assign_role(login, body.role).await {
+        Ok(_) => HttpResponse::Accepted().finish(),
+        Err(err) => err.to_http_response(),
+    }
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/roles/{login}",
+    description = "Remove the role of a user, revoking their access",
+    tag = "blog",
+    responses(
+        (status = 202, description = "Role removed"),
+    ),
+    params(
+        ("login" = String, Path, description = "User login"),
+    ),
+)]
+#[delete("/api/roles/{login}")]
+pub async fn revoke_role(
+    state: web::Data<State>,
+    req: HttpRequest,
+    path: web::Path<(String,)>,
+) -> impl Responder {
+    if let Err(err) = authorize(&state, req, Action::ManageRoles).await {
+        return err.to_http_response();
+    }
+
+    let login = path.into_inner().0;
+
+    match state.admin.revoke_role(login).await {
+        Ok(_) => HttpResponse::Accepted().finish(),
+        Err(err) => err.to_http_response(),
+    }
+}
+
+// Same as auth::UserRecord, see ArticleResponse.
+#[allow(dead_code)]
+#[derive(ToSchema)]
+pub struct UserResponse {
+    id: u64,
+    login: String,
+    name: String,
+    avatar_url: String,
+    first_login_at: String,
+    last_login_at: String,
+    disabled: bool,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/users",
+    description = "List users who logged in at least once, most recent login first",
+    tag = "blog",
+    responses(
+        (status = 200, description = "Users", body = Vec<UserResponse>),
+    ),
+)]
+#[get("/api/users")]
+pub async fn list_users(state: web::Data<State>, req: HttpRequest) -> impl Responder {
+    if let Err(err) = authorize(&state, req, Action::ManageUsers).await {
+        return err.to_http_response();
+    }
+
+    match state.directory.users_list().await {
+        Ok(users) => HttpResponse::Ok().json(users),
+        Err(err) => err.to_http_response(),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/users/{login}",
+    description = "Get a user",
+    tag = "blog",
+    responses(
+        (status = 200, description = "User", body = UserResponse),
+    ),
+    params(
+        ("login" = String, Path, description = "User login"),
+    ),
+)]
+#[get("/api/users/{login}")]
+pub async fn get_user(
+    state: web::Data<State>,
+    req: HttpRequest,
+    path: web::Path<(String,)>,
+) -> impl Responder {
+    if let Err(err) = authorize(&state, req, Action::ManageUsers).await {
+        return err.to_http_response();
+    }
+
+    match state.directory.users_get(path.into_inner().0).await {
+        Ok(user) => HttpResponse::Ok().json(user),
+        Err(err) => err.to_http_response(),
+    }
+}
+
+#[utoipa::path(
+    put,
+    path = "/api/users/{login}/status/disabled",
+    description = "Disable a user, their sessions stop working and they cannot log in",
+    tag = "blog",
+    responses(
+        (status = 202, description = "User disabled"),
+    ),
+    params(
+        ("login" = String, Path, description = "User login"),
+    ),
+)]
+#[put("/api/users/{login}/status/disabled")]
+pub async fn disable_user(
+    state: web::Data<State>,
+    req: HttpRequest,
+    path: web::Path<(String,)>,
+) -> impl Responder {
+    let user = match authorize(&state, req, Action::ManageUsers).await {
+        Ok(user) => user,
+        Err(err) => return err.to_http_response(),
+    };
+
+    let login = path.into_inner().0;
+
+    if login == user.login {
+        return Error::InvalidInput("cannot disable yourself".to_string()).to_http_response();
+    }
+
+    if let Err(err) = state
+        .directory
+        .users_set_disabled(login.clone(), true)
+        .await
+    {
+        return err.to_http_response();
+    }
+
+    match state.sessions.revoke_all(login, None).await {
+        Ok(_) => HttpResponse::Accepted().finish(),
+        Err(err) => err.to_http_response(),
+    }
+}
+
+#[utoipa::path(
+    put,
+    path = "/api/users/{login}/status/enabled",
+    description = "Enable a disabled user",
+    tag = "blog",
+    responses(
+        (status = 202, description = "User enabled"),
+    ),
+    params(
+        ("login" = String, Path, description = "User login"),
+    ),
+)]
+#[put("/api/users/{login}/status/enabled")]
+pub async fn enable_user(
+    state: web::Data<State>,
+    req: HttpRequest,
+    path: web::Path<(String,)>,
+) -> impl Responder {
+    if let Err(err) = authorize(&state, req, Action::ManageUsers).await {
+        return err.to_http_response();
+    }
+
+    match state
+        .directory
+        .users_set_disabled(path.into_inner().0, false)
+        .await
+    {
+        Ok(_) => HttpResponse::Accepted().finish(),
+        Err(err) => err.to_http_response(),
+    }
+}
+
+// Same as auth::SessionInfo, see ArticleResponse.
+#[allow(dead_code)]
+#[derive(ToSchema)]
+pub struct UserSessionResponse {
+    id: String,
+    created_at: String,
+    last_seen_at: String,
+    ip: Option<String>,
+    user_agent: Option<String>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/users/{login}/sessions",
+    description = "List the active sessions of a user",
+    tag = "blog",
+    responses(
+        (status = 200, description = "Active sessions", body = Vec<UserSessionResponse>),
+    ),
+    params(
+        ("login" = String, Path, description = "User login"),
+    ),
+)]
+#[get("/api/users/{login}/sessions")]
+pub async fn list_user_sessions(
+    state: web::Data<State>,
+    req: HttpRequest,
+    path: web::Path<(String,)>,
+) -> impl Responder {
+    if let Err(err) = authorize(&state, req, Action::ManageUsers).await {
+        return err.to_http_response();
+    }
+
+    match state.sessions.sessions(path.into_inner().0).await {
+        Ok(sessions) => HttpResponse::Ok().json(sessions),
+        Err(err) => err.to_http_response(),
+    }
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/users/{login}/sessions/{id}",
+    description = "Log out a session of a user",
+    tag = "blog",
+    responses(
+        (status = 202, description = "Session revoked"),
+    ),
+    params(
+        ("login" = String, Path, description = "User login"),
+        ("id" = String, Path, description = "Session id"),
+    ),
+)]
+#[delete("/api/users/{login}/sessions/{id}")]
+pub async fn revoke_user_session(
+    state: web::Data<State>,
+    req: HttpRequest,
+    path: web::Path<(String, String)>,
+) -> impl Responder {
+    if let Err(err) = authorize(&state, req, Action::ManageUsers).await {
+        return err.to_http_response();
+    }
+
+    let (login, id) = path.into_inner();
+
+    match state.sessions.revoke(login, id).await {
+        Ok(_) => HttpResponse::Accepted().finish(),
+        Err(err) => err.to_http_response(),
+    }
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/users/{login}/sessions",
+    description = "Log out every session of a user",
+    tag = "blog",
+    responses(
+        (status = 202, description = "Sessions revoked"),
+    ),
+    params(
+        ("login" = String, Path, description = "User login"),
+    ),
+)]
+#[delete("/api/users/{login}/sessions")]
+pub async fn revoke_user_sessions(
+    state: web::Data<State>,
+    req: HttpRequest,
+    path: web::Path<(String,)>,
+) -> impl Responder {
+    if let Err(err) = authorize(&state, req, Action::ManageUsers).await {
+        return err.to_http_response();
+    }
+
+    match state.sessions.revoke_all(path.into_inner().0, None).await {
+        Ok(_) => HttpResponse::Accepted().finish(),
+        Err(err) => err.to_http_response(),
+    }
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct PreviewRequest {
+    content: String,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct PreviewResponse {
+    content_html: String,
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/articles/preview",
+    description = "Render article content as it will be shown to readers",
+    tag = "blog",
+    responses(
+        (status = 200, description = "Rendered content", body = PreviewResponse),
+    ),
+    request_body(content=PreviewRequest, content_type = "application/json")
+)]
+#[post("/api/articles/preview")]
+pub async fn preview_article(
+    state: web::Data<State>,
+    req: HttpRequest,
+    body: web::Json<PreviewRequest>,
+) -> impl Responder {
+    if let Err(err) = authorize(&state, req, Action::Read).await {
+        return err.to_http_response();
+    }
+
+    HttpResponse::Ok().json(PreviewResponse {
+        content_html: markdown::render(&body.content),
+    })
+}
+
+pub async fn server(
+    admin: Arc<dyn Admin>,
+    sessions: Arc<dyn SessionManager>,
+    directory: Arc<dyn Directory>,
+    cookie_name: String,
+    listen_addr: String,
+) -> Result<(), std::io::Error> {
+    let csrf_cookie = cookie_name.clone();
+    let csrf_sessions = sessions.clone();
+    let data = web::Data::new(State {
+        admin,
+        sessions,
+        directory,
+        cookie_name,
+    });
+
+    HttpServer::new(move || {
+        let csrf_cookie = csrf_cookie.clone();
+        let csrf_sessions = csrf_sessions.clone();
+
+        App::new()
+            .app_data(data.clone())
+            .wrap(from_fn(move |req, next| {
+                csrf::protect(csrf_cookie.clone(), csrf_sessions.clone(), req, next)
+            }))
+            .wrap(TracingLogger::default())
+            .service(create_article)
+            .service(preview_article)
+            .service(list_articles)
+            .service(search_articles)
+            .service(get_article)
+            .service(update_article)
+            .service(publish_article)
+            .service(schedule_article)
+            .service(unschedule_article)
+            .service(move_article_to_trash)
+            .service(move_article_to_draft)
+            .service(delete_article)
+            .service(list_tags)
+            .service(tag_article)
+            .service(untag_article)
+            .service(list_categories)
+            .service(categorize_article)
+            .service(uncategorize_article)
+            .service(list_revisions)
+            .service(diff_revisions)
+            .service(restore_revision)
+            .service(my_role)
+            .service(list_roles)
+            .service(assign_role)
+            .service(revoke_role)
+            .service(list_users)
+            .service(get_user)
+            .service(disable_user)
+            .service(enable_user)
+            .service(list_user_sessions)
+            .service(revoke_user_session)
+            .service(revoke_user_sessions)
+    })
+    .bind(listen_addr)?
+    .run()
+    .await
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use actix_web::test::TestRequest;
+
+    #[test]
+    fn test_if_match_missing() {
+        let req = TestRequest::default().to_http_request();
+
+        assert_eq!(if_match_version(&req).unwrap(), None);
+    }
+
+    #[test]
+    fn test_if_match_any() {
+        let req = TestRequest::default()
+            .insert_header((header::IF_MATCH, "*"))
+            .to_http_request();
+
+        assert_eq!(if_match_version(&req).unwrap(), None);
+    }
+
+    #[test]
+    fn test_if_match_version() {
+        let req = TestRequest::default()
+            .insert_header((header::IF_MATCH, "\"3\""))
+            .to_http_request();
+
+        assert_eq!(if_match_version(&req).unwrap(), Some(3));
+    }
+
+    #[test]
+    fn test_if_match_weak() {
+        let req = TestRequest::default()
+            .insert_header((header::IF_MATCH, "W/\"3\""))
+            .to_http_request();
+
+        let err = if_match_version(&req).unwrap_err();
+        assert_eq!(
+            err.to_http_response().status(),
+            actix_web::http::StatusCode::PRECONDITION_FAILED
+        );
+    }
+
+    #[test]
+    fn test_if_match_unknown() {
+        let req = TestRequest::default()
+            .insert_header((header::IF_MATCH, "\"abc\""))
+            .to_http_request();
+
+        assert!(matches!(
+            if_match_version(&req),
+            Err(Error::PreconditionFailed(_))
+        ));
+    }
+
+    #[test]
+    fn test_scope() {
+        assert_eq!(scope(&Action::Read), Scope::Read);
+        assert_eq!(scope(&Action::Edit(Uuid::new_v4())), Scope::Write);
+        assert_eq!(scope(&Action::Delete), Scope::Publish);
+        assert_eq!(scope(&Action::ManageUsers), Scope::Admin);
+    }
 }
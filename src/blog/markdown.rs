@@ -0,0 +1,136 @@
+use pulldown_cmark::{Options, Parser, html};
+
+/// Prefix of every id in rendered content, so that articles cannot clobber ids and globals of the page.
+const ID_PREFIX: &str = "user-content-";
+
+/// Renders markdown content to sanitized html.
+///
+/// Raw html written inside the markdown goes through the same sanitizer,
+/// so the output is safe to embed as-is in a page.
+pub fn render(content: &str) -> String {
+    let options = Options::ENABLE_TABLES
+        | Options::ENABLE_FOOTNOTES
+        | Options::ENABLE_STRIKETHROUGH
+        | Options::ENABLE_TASKLISTS
+        | Options::ENABLE_HEADING_ATTRIBUTES;
+
+    let parser = Parser::new_ext(content, options);
+
+    let mut unsafe_html = String::new();
+    html::push_html(&mut unsafe_html, parser);
+
+    sanitize(&unsafe_html)
+}
+
+fn sanitize(unsafe_html: &str) -> String {
+    let mut builder = ammonia::Builder::default();
+
+    builder
+        .id_prefix(Some(ID_PREFIX))
+        .add_tags(["input"])
+        .add_tag_attributes("input", ["type", "checked", "disabled"])
+        .add_tag_attributes("code", ["class"])
+        .add_tag_attributes("sup", ["class"])
+        .add_tag_attributes("div", ["class", "id"])
+        .attribute_filter(|element, attribute, value| match (element, attribute) {
+            // only keep the classes generated by the renderer
+            ("code", "class") if is_language_class(value) => Some(value.into()),
+            ("sup", "class") if value == "footnote-reference" => Some(value.into()),
+            ("div", "class") if value == "footnote-definition" => Some(value.into()),
+            (_, "class") => None,
+            ("input", "type") if value == "checkbox" => Some(value.into()),
+            ("input", "type") => None,
+            // in-page links follow the prefixed ids of headings and footnotes
+            ("a", "href") if value.starts_with('#') && !value[1..].starts_with(ID_PREFIX) => {
+                Some(format!("#{}{}", ID_PREFIX, &value[1..]).into())
+            }
+            _ => Some(value.into()),
+        });
+
+    for heading in ["h1", "h2", "h3", "h4", "h5", "h6"] {
+        builder.add_tag_attributes(heading, ["id"]);
+    }
+
+    builder.clean(unsafe_html).to_string()
+}
+
+fn is_language_class(value: &str) -> bool {
+    match value.strip_prefix("language-") {
+        Some(lang) => {
+            !lang.is_empty()
+                && lang
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '+' | '#'))
+        }
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_headings() {
+        let html = render("# Title\n\n## Section {#intro}");
+
+        assert!(html.contains("<h1>Title</h1>"));
+        assert!(html.contains(r#"<h2 id="user-content-intro">Section</h2>"#));
+    }
+
+    #[test]
+    fn test_code_block_language() {
+        let html = render("```rust\nfn main() {}\n```");
+
+        assert!(html.contains(r#"<pre><code class="language-rust">fn main() {}"#));
+    }
+
+    #[test]
+    fn test_code_block_invalid_language() {
+        let html = render("```rust\" onclick=\"alert(1)\nfn main() {}\n```");
+
+        assert!(!html.contains("onclick"));
+        assert!(html.contains("<pre><code>"));
+    }
+
+    #[test]
+    fn test_tables() {
+        let html = render("| a | b |\n| - | - |\n| 1 | 2 |");
+
+        assert!(html.contains("<table>"));
+        assert!(html.contains("<th>a</th>"));
+        assert!(html.contains("<td>2</td>"));
+    }
+
+    #[test]
+    fn test_footnotes() {
+        let html = render("Text[^1]\n\n[^1]: The note.");
+
+        assert!(html.contains(r##"<sup class="footnote-reference"><a href="#user-content-1""##));
+        assert!(html.contains(r#"<div class="footnote-definition" id="user-content-1">"#));
+    }
+
+    #[test]
+    fn test_strips_scripts() {
+        let html = render("hello <script>alert(1)</script> <a href=\"javascript:alert(1)\">x</a>");
+
+        assert!(!html.contains("<script>"));
+        assert!(!html.contains("javascript:"));
+    }
+
+    #[test]
+    fn test_prefixes_ids() {
+        let html = render("<div id=\"csrf\">x</div>\n\n# Top {#top}\n\n[back](#top)");
+
+        assert!(html.contains(r#"<div id="user-content-csrf">"#));
+        assert!(html.contains(r##"<a href="#user-content-top""##));
+        assert!(!html.contains(r#"id="csrf""#));
+    }
+
+    #[test]
+    fn test_strips_classes() {
+        let html = render("<p class=\"evil\">text</p>");
+
+        assert!(!html.contains("evil"));
+    }
+}
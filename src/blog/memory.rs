@@ -0,0 +1,753 @@
+use super::search::{MATCH_START, MATCH_STOP, highlight};
+use super::{
+    Article, ArticlesListOptions, Repo, Revision, Role, RoleAssignment, SearchResult, Status,
+};
+use crate::errors::Error;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::sync::RwLock;
+use uuid::Uuid;
+
+/// Words of a search snippet, as ts_headline with `MaxWords=30`.
+const SNIPPET_WORDS: usize = 30;
+
+#[derive(Default)]
+struct Data {
+    articles: HashMap<Uuid, Article>,
+    /// Previous slugs, to the article that used them.
+    slugs: HashMap<String, Uuid>,
+    revisions: Vec<Revision>,
+    roles: BTreeMap<String, Role>,
+}
+
+impl Data {
+    fn get(&self, id: Uuid) -> Result<&Article, Error> {
+        self.articles
+            .get(&id)
+            .ok_or_else(|| Error::NotFound(format!("article {}", id)))
+    }
+
+    fn get_mut(&mut self, id: Uuid) -> Result<&mut Article, Error> {
+        self.articles
+            .get_mut(&id)
+            .ok_or_else(|| Error::NotFound(format!("article {}", id)))
+    }
+
+    /// Fails when another article already uses `slug`, as the unique index does.
+    fn check_slug(&self, id: Uuid, slug: &str) -> Result<(), Error> {
+        if self
+            .articles
+            .values()
+            .any(|article| article.id != id && article.slug == slug)
+        {
+            return Err(Error::InvalidInput(format!(
+                "slug {} is already in use",
+                slug
+            )));
+        }
+
+        Ok(())
+    }
+
+    /// Records the current state of an article as a revision made by `author`.
+    fn add_revision(&mut self, article: &Article, author: &str) {
+        self.revisions.push(Revision {
+            id: Uuid::new_v4(),
+            article_id: article.id,
+            title: article.title.clone(),
+            description: article.description.clone(),
+            content: article.content.clone(),
+            slug: article.slug.clone(),
+            tags: article.tags.clone(),
+            category: article.category.clone(),
+            author: author.to_string(),
+            created_at: article.updated_at,
+        });
+    }
+
+    /// Bumps `updated_at` and the version of the article `id`, so that edits based on the
+    /// previous version become stale, and records the change as a revision made by `editor`.
+    fn touch(&mut self, id: Uuid, editor: &str) -> Result<(), Error> {
+        let article = self.get_mut(id)?;
+        article.updated_at = Utc::now();
+        article.version += 1;
+
+        let article = article.clone();
+        self.add_revision(&article, editor);
+
+        Ok(())
+    }
+
+    /// Articles matching `opts`, most recent first.
+    fn filter(&self, opts: &ArticlesListOptions) -> Vec<&Article> {
+        let mut articles: Vec<&Article> = self
+            .articles
+            .values()
+            .filter(|article| {
+                opts.status
+                    .as_ref()
+                    .is_none_or(|status| article.status == *status)
+                    && opts
+                        .tag
+                        .as_ref()
+                        .is_none_or(|tag| article.tags.contains(tag))
+                    && opts
+                        .category
+                        .as_ref()
+                        .is_none_or(|category| article.category.as_ref() == Some(category))
+            })
+            .collect();
+
+        articles.sort_by_key(|article| std::cmp::Reverse(article.created_at));
+        articles
+    }
+}
+
+/// Tags as the postgres repo returns them, sorted and without duplicates.
+fn normalize_tags(tags: &[String]) -> Vec<String> {
+    tags.iter()
+        .cloned()
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .collect()
+}
+
+fn page<T>(items: Vec<T>, limit: i64, offset: i64) -> Vec<T> {
+    items
+        .into_iter()
+        .skip(offset.max(0) as usize)
+        .take(limit.max(0) as usize)
+        .collect()
+}
+
+/// Lowercased words of a search query.
+fn terms(query: &str) -> Vec<String> {
+    query
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|term| !term.is_empty())
+        .map(|term| term.to_lowercase())
+        .collect()
+}
+
+fn matches(word: &str, terms: &[String]) -> bool {
+    let word = word.to_lowercase();
+    terms.iter().any(|term| word.contains(term.as_str()))
+}
+
+/// Excerpt of `content` around the first match, highlighted as the postgres repo does.
+fn snippet(content: &str, terms: &[String]) -> String {
+    // markers already in the content would be turned into unbalanced <mark> tags
+    let content = content.replace([MATCH_START, MATCH_STOP], "");
+    let words: Vec<&str> = content.split_whitespace().collect();
+
+    let first = words
+        .iter()
+        .position(|word| matches(word, terms))
+        .unwrap_or(0);
+    let start = first.saturating_sub(SNIPPET_WORDS / 6);
+
+    let excerpt: Vec<String> = words
+        .iter()
+        .skip(start)
+        .take(SNIPPET_WORDS)
+        .map(|word| match matches(word, terms) {
+            true => format!("{}{}{}", MATCH_START, word, MATCH_STOP),
+            false => word.to_string(),
+        })
+        .collect();
+
+    highlight(&excerpt.join(" "))
+}
+
+/// Keeps everything in memory, for development and tests, it is lost when the process exits.
+///
+/// Follows the postgres repo: listings are ordered by `created_at` desc, missing articles fail
+/// with `NotFound` where it does, and slugs are unique. Search matches articles containing every
+/// word of the query, ranked by how often they appear.
+#[derive(Default)]
+pub struct MemoryRepo {
+    data: RwLock<Data>,
+}
+
+impl MemoryRepo {
+    pub fn new() -> Self {
+        MemoryRepo::default()
+    }
+}
+
+#[async_trait]
+impl Repo for MemoryRepo {
+    async fn article_create(&self, mut article: Article) -> Result<Article, Error> {
+        let mut data = self.data.write().unwrap();
+
+        data.check_slug(article.id, &article.slug)?;
+
+        article.tags = normalize_tags(&article.tags);
+        data.add_revision(&article, &article.author);
+        data.articles.insert(article.id, article.clone());
+
+        Ok(article)
+    }
+
+    async fn articles_get(&self, id: Uuid) -> Result<Article, Error> {
+        self.data.read().unwrap().get(id).cloned()
+    }
+
+    async fn articles_get_by_slug(&self, slug: String) -> Result<Article, Error> {
+        self.data
+            .read()
+            .unwrap()
+            .articles
+            .values()
+            .find(|article| article.slug == slug)
+            .cloned()
+            .ok_or_else(|| Error::NotFound(format!("article {}", slug)))
+    }
+
+    async fn articles_slug_redirect(&self, slug: String) -> Result<String, Error> {
+        let data = self.data.read().unwrap();
+
+        data.slugs
+            .get(&slug)
+            .and_then(|id| data.articles.get(id))
+            .map(|article| article.slug.clone())
+            .ok_or_else(|| Error::NotFound(format!("slug {}", slug)))
+    }
+
+    async fn articles_slug_owner(&self, slug: String) -> Result<Option<Uuid>, Error> {
+        let data = self.data.read().unwrap();
+
+        let current = data
+            .articles
+            .values()
+            .find(|article| article.slug == slug)
+            .map(|article| article.id);
+
+        Ok(current.or_else(|| data.slugs.get(&slug).copied()))
+    }
+
+    async fn revisions_list(&self, article_id: Uuid) -> Result<Vec<Revision>, Error> {
+        let mut revisions: Vec<Revision> = self
+            .data
+            .read()
+            .unwrap()
+            .revisions
+            .iter()
+            .filter(|revision| revision.article_id == article_id)
+            .cloned()
+            .collect();
+
+        // revisions are recorded in order, so ties keep the latest first
+        revisions.reverse();
+        revisions.sort_by_key(|revision| std::cmp::Reverse(revision.created_at));
+
+        Ok(revisions)
+    }
+
+    async fn revisions_get(&self, article_id: Uuid, id: Uuid) -> Result<Revision, Error> {
+        self.data
+            .read()
+            .unwrap()
+            .revisions
+            .iter()
+            .find(|revision| revision.article_id == article_id && revision.id == id)
+            .cloned()
+            .ok_or_else(|| Error::NotFound(format!("revision {}", id)))
+    }
+
+    async fn tags_list(&self) -> Result<Vec<String>, Error> {
+        let data = self.data.read().unwrap();
+
+        let tags: BTreeSet<&String> = data
+            .articles
+            .values()
+            .flat_map(|article| &article.tags)
+            .collect();
+
+        Ok(tags.into_iter().cloned().collect())
+    }
+
+    async fn categories_list(&self) -> Result<Vec<String>, Error> {
+        let data = self.data.read().unwrap();
+
+        let categories: BTreeSet<&String> = data
+            .articles
+            .values()
+            .filter_map(|article| article.category.as_ref())
+            .collect();
+
+        Ok(categories.into_iter().cloned().collect())
+    }
+
+    async fn articles_list(
+        &self,
+        opts: ArticlesListOptions,
+        limit: i64,
+        offset: i64,
+    ) -> Result<(Vec<Article>, i64), Error> {
+        let data = self.data.read().unwrap();
+
+        let articles = data.filter(&opts);
+        let count = articles.len() as i64;
+
+        Ok((
+            page(articles, limit, offset).into_iter().cloned().collect(),
+            count,
+        ))
+    }
+
+    async fn articles_search(
+        &self,
+        search: String,
+        opts: ArticlesListOptions,
+        limit: i64,
+        offset: i64,
+    ) -> Result<(Vec<SearchResult>, i64), Error> {
+        let terms = terms(&search);
+        if terms.is_empty() {
+            return Ok((Vec::new(), 0));
+        }
+
+        let data = self.data.read().unwrap();
+
+        let mut results: Vec<SearchResult> = data
+            .filter(&opts)
+            .into_iter()
+            .filter_map(|article| {
+                let text = format!(
+                    "{} {} {}",
+                    article.title, article.description, article.content
+                );
+
+                let lowercase = text.to_lowercase();
+                if !terms.iter().all(|term| lowercase.contains(term.as_str())) {
+                    return None;
+                }
+
+                let rank = text
+                    .split_whitespace()
+                    .filter(|word| matches(word, &terms))
+                    .count() as f32;
+
+                Some(SearchResult {
+                    article: article.clone(),
+                    rank,
+                    snippet: snippet(&article.content, &terms),
+                })
+            })
+            .collect();
+
+        // already ordered by created_at desc, which the stable sort keeps among equal ranks
+        results.sort_by(|a, b| b.rank.total_cmp(&a.rank));
+
+        let count = results.len() as i64;
+
+        Ok((page(results, limit, offset), count))
+    }
+
+    async fn articles_exists(&self, id: Uuid) -> Result<(), Error> {
+        self.data.read().unwrap().get(id).map(|_| ())
+    }
+
+    async fn article_update(&self, mut article: Article, editor: String) -> Result<(), Error> {
+        let id = article.id;
+        let mut data = self.data.write().unwrap();
+
+        let current = data.get(id)?;
+        if current.version != article.version {
+            return Err(Error::PreconditionFailed(format!(
+                "article {} was modified concurrently",
+                id
+            )));
+        }
+
+        let previous_slug = current.slug.clone();
+        data.check_slug(id, &article.slug)?;
+
+        if previous_slug != article.slug {
+            // keep the previous slug around so that old links can be redirected
+            data.slugs.insert(previous_slug, id);
+
+            if data.slugs.get(&article.slug) == Some(&id) {
+                data.slugs.remove(&article.slug);
+            }
+        }
+
+        article.tags = normalize_tags(&article.tags);
+        data.add_revision(&article, &editor);
+
+        let current = data.get_mut(id)?;
+        current.title = article.title;
+        current.description = article.description;
+        current.content = article.content;
+        current.slug = article.slug;
+        current.updated_at = article.updated_at;
+        current.category = article.category;
+        current.tags = article.tags;
+        current.version += 1;
+
+        Ok(())
+    }
+
+    async fn article_set_status(&self, id: Uuid, status: Status) -> Result<(), Error> {
+        if let Some(article) = self.data.write().unwrap().articles.get_mut(&id) {
+            article.status = status;
+            article.publish_at = None;
+            article.updated_at = Utc::now();
+        }
+
+        Ok(())
+    }
+
+    async fn article_schedule(&self, id: Uuid, publish_at: DateTime<Utc>) -> Result<bool, Error> {
+        let mut data = self.data.write().unwrap();
+
+        match data.articles.get_mut(&id) {
+            Some(article) if matches!(article.status, Status::Draft | Status::Scheduled) => {
+                article.status = Status::Scheduled;
+                article.publish_at = Some(publish_at);
+                article.updated_at = Utc::now();
+
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    async fn articles_publish_due(&self, now: DateTime<Utc>) -> Result<Vec<Uuid>, Error> {
+        let mut ids = Vec::new();
+
+        for article in self.data.write().unwrap().articles.values_mut() {
+            if article.status == Status::Scheduled
+                && article
+                    .publish_at
+                    .is_some_and(|publish_at| publish_at <= now)
+            {
+                article.status = Status::Published;
+                article.publish_at = None;
+                article.updated_at = now;
+
+                ids.push(article.id);
+            }
+        }
+
+        Ok(ids)
+    }
+
+    async fn article_tag_attach(&self, id: Uuid, tag: String, editor: String) -> Result<(), Error> {
+        let mut data = self.data.write().unwrap();
+        let article = data.get_mut(id)?;
+
+        if !article.tags.contains(&tag) {
+            article.tags.push(tag);
+            article.tags.sort();
+            data.touch(id, &editor)?;
+        }
+
+        Ok(())
+    }
+
+    async fn article_tag_detach(&self, id: Uuid, tag: String, editor: String) -> Result<(), Error> {
+        let mut data = self.data.write().unwrap();
+
+        if let Some(article) = data.articles.get_mut(&id) {
+            let before = article.tags.len();
+            article.tags.retain(|t| *t != tag);
+
+            if article.tags.len() != before {
+                data.touch(id, &editor)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn article_category_set(
+        &self,
+        id: Uuid,
+        category: Option<String>,
+        editor: String,
+    ) -> Result<(), Error> {
+        let mut data = self.data.write().unwrap();
+
+        if let Some(article) = data.articles.get_mut(&id)
+            && article.category != category
+        {
+            article.category = category;
+            data.touch(id, &editor)?;
+        }
+
+        Ok(())
+    }
+
+    async fn article_delete(&self, id: Uuid) -> Result<(), Error> {
+        let mut data = self.data.write().unwrap();
+
+        data.articles.remove(&id);
+        data.slugs.retain(|_, article_id| *article_id != id);
+        data.revisions.retain(|revision| revision.article_id != id);
+
+        Ok(())
+    }
+
+    async fn roles_get(&self, login: String) -> Result<Option<Role>, Error> {
+        Ok(self.data.read().unwrap().roles.get(&login).copied())
+    }
+
+    async fn roles_list(&self) -> Result<Vec<RoleAssignment>, Error> {
+        Ok(self
+            .data
+            .read()
+            .unwrap()
+            .roles
+            .iter()
+            .map(|(login, role)| RoleAssignment {
+                login: login.clone(),
+                role: *role,
+            })
+            .collect())
+    }
+
+    async fn roles_set(&self, login: String, role: Role) -> Result<(), Error> {
+        self.data.write().unwrap().roles.insert(login, role);
+
+        Ok(())
+    }
+
+    async fn roles_delete(&self, login: String) -> Result<(), Error> {
+        self.data.write().unwrap().roles.remove(&login);
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use chrono::Duration;
+
+    fn article(slug: &str, created_at: DateTime<Utc>) -> Article {
+        Article {
+            id: Uuid::new_v4(),
+            title: format!("About {}", slug),
+            description: "description".to_string(),
+            content: "content".to_string(),
+            updated_at: created_at,
+            created_at,
+            status: Status::Draft,
+            author: "john_doe".to_string(),
+            slug: slug.to_string(),
+            tags: vec!["rust".to_string()],
+            category: None,
+            publish_at: None,
+            version: 1,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_list_order_and_count() {
+        let repo = MemoryRepo::new();
+        let now = Utc::now();
+
+        for (i, slug) in ["first", "second", "third"].iter().enumerate() {
+            let mut article = article(slug, now + Duration::minutes(i as i64));
+            if *slug == "second" {
+                article.status = Status::Published;
+            }
+
+            repo.article_create(article).await.unwrap();
+        }
+
+        let (articles, count) = repo
+            .articles_list(ArticlesListOptions::default(), 2, 0)
+            .await
+            .unwrap();
+
+        assert_eq!(count, 3);
+        assert_eq!(
+            articles.iter().map(|a| a.slug.as_str()).collect::<Vec<_>>(),
+            vec!["third", "second"]
+        );
+
+        let (articles, count) = repo
+            .articles_list(ArticlesListOptions::from_str("published"), 10, 0)
+            .await
+            .unwrap();
+
+        assert_eq!((articles.len(), count), (1, 1));
+    }
+
+    #[tokio::test]
+    async fn test_not_found() {
+        let repo = MemoryRepo::new();
+        let id = Uuid::new_v4();
+
+        assert!(matches!(
+            repo.articles_get(id).await,
+            Err(Error::NotFound(_))
+        ));
+        assert!(matches!(
+            repo.articles_exists(id).await,
+            Err(Error::NotFound(_))
+        ));
+        assert!(matches!(
+            repo.articles_get_by_slug("missing".to_string()).await,
+            Err(Error::NotFound(_))
+        ));
+        assert!(matches!(
+            repo.revisions_get(id, id).await,
+            Err(Error::NotFound(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_update() {
+        let repo = MemoryRepo::new();
+        let mut created = repo
+            .article_create(article("old", Utc::now()))
+            .await
+            .unwrap();
+        repo.article_create(article("taken", Utc::now()))
+            .await
+            .unwrap();
+
+        created.slug = "taken".to_string();
+        assert!(matches!(
+            repo.article_update(created.clone(), "jane_doe".to_string())
+                .await,
+            Err(Error::InvalidInput(_))
+        ));
+
+        created.slug = "new".to_string();
+        repo.article_update(created.clone(), "jane_doe".to_string())
+            .await
+            .unwrap();
+
+        // the version the update was based on is now stale
+        assert!(matches!(
+            repo.article_update(created.clone(), "jane_doe".to_string())
+                .await,
+            Err(Error::PreconditionFailed(_))
+        ));
+
+        assert_eq!(repo.articles_get(created.id).await.unwrap().version, 2);
+        assert_eq!(
+            repo.articles_slug_redirect("old".to_string())
+                .await
+                .unwrap(),
+            "new"
+        );
+        assert_eq!(
+            repo.articles_slug_owner("old".to_string()).await.unwrap(),
+            Some(created.id)
+        );
+
+        let revisions = repo.revisions_list(created.id).await.unwrap();
+        assert_eq!(
+            revisions
+                .iter()
+                .map(|r| r.author.as_str())
+                .collect::<Vec<_>>(),
+            vec!["jane_doe", "john_doe"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_tags_and_category_revised() {
+        let repo = MemoryRepo::new();
+        let created = repo
+            .article_create(article("slug", Utc::now()))
+            .await
+            .unwrap();
+
+        for _ in 0..2 {
+            repo.article_tag_attach(created.id, "tokio".to_string(), "jane_doe".to_string())
+                .await
+                .unwrap();
+        }
+        repo.article_category_set(created.id, Some("news".to_string()), "jane_doe".to_string())
+            .await
+            .unwrap();
+        repo.article_tag_detach(created.id, "tokio".to_string(), "jane_doe".to_string())
+            .await
+            .unwrap();
+        // unchanged, nothing recorded
+        repo.article_category_set(created.id, Some("news".to_string()), "jane_doe".to_string())
+            .await
+            .unwrap();
+
+        let article = repo.articles_get(created.id).await.unwrap();
+        assert_eq!(article.version, created.version + 3);
+        assert!(article.updated_at > created.updated_at);
+
+        let revisions = repo.revisions_list(created.id).await.unwrap();
+        assert_eq!(
+            revisions
+                .iter()
+                .map(|r| (
+                    r.author.as_str(),
+                    r.tags.iter().map(String::as_str).collect(),
+                    r.category.as_deref()
+                ))
+                .collect::<Vec<(&str, Vec<&str>, Option<&str>)>>(),
+            vec![
+                ("jane_doe", vec!["rust"], Some("news")),
+                ("jane_doe", vec!["rust", "tokio"], Some("news")),
+                ("jane_doe", vec!["rust", "tokio"], None),
+                ("john_doe", vec!["rust"], None),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_publish_due() {
+        let repo = MemoryRepo::new();
+        let created = repo
+            .article_create(article("later", Utc::now()))
+            .await
+            .unwrap();
+        let now = Utc::now();
+
+        assert!(
+            repo.article_schedule(created.id, now + Duration::hours(1))
+                .await
+                .unwrap()
+        );
+        assert!(repo.articles_publish_due(now).await.unwrap().is_empty());
+        assert_eq!(
+            repo.articles_publish_due(now + Duration::hours(2))
+                .await
+                .unwrap(),
+            vec![created.id]
+        );
+        assert!(
+            !repo
+                .article_schedule(created.id, now + Duration::hours(1))
+                .await
+                .unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_search() {
+        let repo = MemoryRepo::new();
+        let mut a = article("tokio", Utc::now());
+        a.content = "async <b>rust</b> with tokio and more tokio".to_string();
+        repo.article_create(a).await.unwrap();
+        repo.article_create(article("other", Utc::now()))
+            .await
+            .unwrap();
+
+        let (results, count) = repo
+            .articles_search("Tokio".to_string(), ArticlesListOptions::default(), 10, 0)
+            .await
+            .unwrap();
+
+        assert_eq!(count, 1);
+        assert_eq!(
+            results[0].snippet,
+            "async &lt;b&gt;rust&lt;/b&gt; with <mark>tokio</mark> and more <mark>tokio</mark>"
+        );
+    }
+}
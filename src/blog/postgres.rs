@@ -1,9 +1,12 @@
-use super::{Article, ArticlesListOptions, Repo, Status};
+use super::search::{MATCH_START, MATCH_STOP, highlight};
+use super::{
+    Article, ArticlesListOptions, Repo, Revision, Role, RoleAssignment, SearchResult, Status,
+};
 use crate::errors::Error;
 use async_trait::async_trait;
 use chrono::{DateTime, Utc};
 use futures::stream::StreamExt;
-use sqlx::{postgres::PgPool, query_builder::QueryBuilder};
+use sqlx::{PgConnection, Postgres, postgres::PgPool, query_builder::QueryBuilder};
 use std::sync::Arc;
 use uuid::Uuid;
 
@@ -16,10 +19,7 @@ impl PostgresRepo {
         let db = match PgPool::connect(&dsn).await {
             Ok(pool) => Arc::new(pool),
             Err(err) => {
-                return Err(Error::ConnectionError(format!(
-                    "connecting to db: {}",
-                    err.to_string()
-                )));
+                return Err(Error::ConnectionError(format!("connecting to db: {}", err)));
             }
         };
 
@@ -27,12 +27,137 @@ impl PostgresRepo {
     }
 }
 
+fn push_filters(query: &mut QueryBuilder<Postgres>, opts: &ArticlesListOptions) {
+    if let Some(status) = &opts.status {
+        query.push(" AND status = ");
+        query.push_bind(status.to_string());
+    }
+
+    if let Some(tag) = &opts.tag {
+        query.push(
+            " AND EXISTS (SELECT 1 FROM blog.article_tags t WHERE t.article_id = a.id AND t.tag = ",
+        );
+        query.push_bind(tag.clone());
+        query.push(")");
+    }
+
+    if let Some(category) = &opts.category {
+        query.push(" AND category = ");
+        query.push_bind(category.clone());
+    }
+}
+
+/// A concurrent create or update can take the slug between the check and the write, the unique
+/// index then rejects it as any other slug in use.
+fn write_error(err: sqlx::Error, slug: &str, doing: &str) -> Error {
+    match &err {
+        sqlx::Error::Database(db) if db.constraint() == Some("articles_slug_idx") => {
+            Error::InvalidInput(format!("slug {} is already in use", slug))
+        }
+        _ => Error::ConnectionError(format!("{}: {}", doing, err)),
+    }
+}
+
+/// Records the current state of an article as a revision made by `author`.
+async fn add_revision(
+    con: &mut PgConnection,
+    article: &Article,
+    author: &str,
+) -> Result<(), Error> {
+    sqlx::query!(
+        r#"INSERT INTO blog.article_revisions (id, article_id, title, description, content, slug, tags, category, author, created_at)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)"#,
+        Uuid::new_v4(),
+        article.id,
+        article.title,
+        article.description,
+        article.content,
+        article.slug,
+        &article.tags,
+        article.category,
+        author,
+        article.updated_at,
+    )
+    .execute(&mut *con)
+    .await
+    .map_err(|err| Error::ConnectionError(format!("inserting revision: {}", err)))?;
+
+    Ok(())
+}
+
+/// Records the stored state of the article `id` as a revision made by `author`.
+async fn add_current_revision(con: &mut PgConnection, id: Uuid, author: &str) -> Result<(), Error> {
+    sqlx::query!(
+        r#"INSERT INTO blog.article_revisions (id, article_id, title, description, content, slug, tags, category, author, created_at)
+        SELECT $1, a.id, a.title, a.description, a.content, a.slug,
+            ARRAY(SELECT tag FROM blog.article_tags t WHERE t.article_id = a.id ORDER BY tag), a.category, $2, a.updated_at
+        FROM blog.articles a WHERE a.id = $3"#,
+        Uuid::new_v4(),
+        author,
+        id,
+    )
+    .execute(&mut *con)
+    .await
+    .map_err(|err| Error::ConnectionError(format!("inserting revision: {}", err)))?;
+
+    Ok(())
+}
+
+/// Bumps `updated_at` and the version of the article `id`, so that edits based on the previous
+/// version become stale, and records the change as a revision made by `editor`.
+async fn touch(con: &mut PgConnection, id: Uuid, editor: &str) -> Result<(), Error> {
+    sqlx::query!(
+        r#"UPDATE blog.articles SET updated_at = $1, version = version + 1 WHERE id = $2"#,
+        Utc::now(),
+        id,
+    )
+    .execute(&mut *con)
+    .await
+    .map_err(|err| Error::ConnectionError(format!("updating data: {}", err)))?;
+
+    add_current_revision(con, id, editor).await
+}
+
+/// Replaces the tags of an article, creating the ones which do not exist yet.
+async fn set_tags(con: &mut PgConnection, id: Uuid, tags: &[String]) -> Result<(), Error> {
+    sqlx::query!(
+        r#"INSERT INTO blog.tags (name, created_at) SELECT unnest($1::text[]), $2 ON CONFLICT DO NOTHING"#,
+        tags,
+        Utc::now(),
+    )
+    .execute(&mut *con)
+    .await
+    .map_err(|err| Error::ConnectionError(format!("inserting tags: {}", err)))?;
+
+    sqlx::query!(r#"DELETE FROM blog.article_tags WHERE article_id = $1"#, id)
+        .execute(&mut *con)
+        .await
+        .map_err(|err| Error::ConnectionError(format!("deleting tags: {}", err)))?;
+
+    sqlx::query!(
+        r#"INSERT INTO blog.article_tags (article_id, tag) SELECT $1, unnest($2::text[])"#,
+        id,
+        tags,
+    )
+    .execute(&mut *con)
+    .await
+    .map_err(|err| Error::ConnectionError(format!("inserting tags: {}", err)))?;
+
+    Ok(())
+}
+
 #[async_trait]
 impl Repo for PostgresRepo {
     async fn article_create(&self, article: Article) -> Result<Article, Error> {
+        let mut tx = self
+            .db
+            .begin()
+            .await
+            .map_err(|err| Error::ConnectionError(format!("starting transaction: {}", err)))?;
+
         let err = sqlx::query!(
-            r#"INSERT INTO blog.articles (id, title, description, content, updated_at, created_at, status, author) 
-            VALUES ($1, $2, $3, $4, $5, $6, $7, $8)"#,
+            r#"INSERT INTO blog.articles (id, title, description, content, updated_at, created_at, status, author, slug, publish_at, version, category)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12)"#,
             article.id,
             article.title,
             article.description,
@@ -41,22 +166,34 @@ impl Repo for PostgresRepo {
             article.created_at,
             article.status.to_string(),
             article.author,
+            article.slug,
+            article.publish_at,
+            article.version,
+            article.category,
         )
-        .execute(&*self.db)
+        .execute(&mut *tx)
         .await;
 
         if let Err(err) = err {
-            return Err(Error::ConnectionError(format!("inserting data: {}", err)));
+            return Err(write_error(err, &article.slug, "inserting data"));
         }
 
+        set_tags(&mut tx, article.id, &article.tags).await?;
+        add_revision(&mut tx, &article, &article.author).await?;
+
+        tx.commit()
+            .await
+            .map_err(|err| Error::ConnectionError(format!("committing transaction: {}", err)))?;
+
         Ok(article)
     }
 
     async fn articles_get(&self, id: Uuid) -> Result<Article, Error> {
         let row = match sqlx::query!(
             r#"
-            SELECT id, title, description, content, updated_at, created_at, status, author
-            FROM blog.articles WHERE id = $1
+            SELECT id, title, description, content, updated_at, created_at, status, author, slug, publish_at, version, category,
+            ARRAY(SELECT tag FROM blog.article_tags t WHERE t.article_id = a.id ORDER BY tag) AS "tags!"
+            FROM blog.articles a WHERE id = $1
             "#,
             id
         )
@@ -79,11 +216,146 @@ impl Repo for PostgresRepo {
             created_at: row.created_at,
             status: Status::from_string(row.status),
             author: row.author,
+            slug: row.slug,
+            tags: row.tags,
+            category: row.category,
+            publish_at: row.publish_at,
+            version: row.version,
         };
 
         Ok(article)
     }
 
+    async fn articles_get_by_slug(&self, slug: String) -> Result<Article, Error> {
+        let row = match sqlx::query!(
+            r#"
+            SELECT id, title, description, content, updated_at, created_at, status, author, slug, publish_at, version, category,
+            ARRAY(SELECT tag FROM blog.article_tags t WHERE t.article_id = a.id ORDER BY tag) AS "tags!"
+            FROM blog.articles a WHERE slug = $1
+            "#,
+            slug
+        )
+        .fetch_one(&*self.db)
+        .await
+        {
+            Ok(row) => row,
+            Err(sqlx::Error::RowNotFound) => {
+                return Err(Error::NotFound(format!("article {}", slug)));
+            }
+            Err(err) => return Err(Error::ConnectionError(format!("fetching data: {}", err))),
+        };
+
+        let article = Article {
+            id: row.id,
+            title: row.title,
+            description: row.description,
+            content: row.content,
+            updated_at: row.updated_at,
+            created_at: row.created_at,
+            status: Status::from_string(row.status),
+            author: row.author,
+            slug: row.slug,
+            tags: row.tags,
+            category: row.category,
+            publish_at: row.publish_at,
+            version: row.version,
+        };
+
+        Ok(article)
+    }
+
+    async fn articles_slug_redirect(&self, slug: String) -> Result<String, Error> {
+        let row = sqlx::query!(
+            r#"
+            SELECT a.slug FROM blog.article_slugs s
+            JOIN blog.articles a ON a.id = s.article_id
+            WHERE s.slug = $1
+            "#,
+            slug
+        )
+        .fetch_optional(&*self.db)
+        .await
+        .map_err(|err| Error::ConnectionError(format!("fetching data: {}", err)))?;
+
+        match row {
+            Some(row) => Ok(row.slug),
+            None => Err(Error::NotFound(format!("slug {}", slug))),
+        }
+    }
+
+    async fn articles_slug_owner(&self, slug: String) -> Result<Option<Uuid>, Error> {
+        let row = sqlx::query!(
+            r#"
+            SELECT id AS "id!" FROM blog.articles WHERE slug = $1
+            UNION ALL
+            SELECT article_id FROM blog.article_slugs WHERE slug = $1
+            LIMIT 1
+            "#,
+            slug
+        )
+        .fetch_optional(&*self.db)
+        .await
+        .map_err(|err| Error::ConnectionError(format!("fetching data: {}", err)))?;
+
+        Ok(row.map(|row| row.id))
+    }
+
+    async fn revisions_list(&self, article_id: Uuid) -> Result<Vec<Revision>, Error> {
+        sqlx::query_as!(
+            Revision,
+            r#"
+            SELECT id, article_id, title, description, content, slug, tags, category, author, created_at
+            FROM blog.article_revisions WHERE article_id = $1 ORDER BY created_at DESC
+            "#,
+            article_id
+        )
+        .fetch_all(&*self.db)
+        .await
+        .map_err(|err| Error::ConnectionError(format!("fetching revisions: {}", err)))
+    }
+
+    async fn revisions_get(&self, article_id: Uuid, id: Uuid) -> Result<Revision, Error> {
+        match sqlx::query_as!(
+            Revision,
+            r#"
+            SELECT id, article_id, title, description, content, slug, tags, category, author, created_at
+            FROM blog.article_revisions WHERE article_id = $1 AND id = $2
+            "#,
+            article_id,
+            id
+        )
+        .fetch_one(&*self.db)
+        .await
+        {
+            Ok(revision) => Ok(revision),
+            Err(sqlx::Error::RowNotFound) => Err(Error::NotFound(format!("revision {}", id))),
+            Err(err) => Err(Error::ConnectionError(format!(
+                "fetching revision: {}",
+                err
+            ))),
+        }
+    }
+
+    async fn tags_list(&self) -> Result<Vec<String>, Error> {
+        let rows = sqlx::query!(r#"SELECT DISTINCT tag FROM blog.article_tags ORDER BY tag"#)
+            .fetch_all(&*self.db)
+            .await
+            .map_err(|err| Error::ConnectionError(format!("fetching tags: {}", err)))?;
+
+        Ok(rows.into_iter().map(|row| row.tag).collect())
+    }
+
+    async fn categories_list(&self) -> Result<Vec<String>, Error> {
+        let rows = sqlx::query!(
+            r#"SELECT DISTINCT category AS "category!" FROM blog.articles WHERE category IS NOT NULL ORDER BY 1"#
+        )
+        .fetch_all(&*self.db)
+        .await
+        .map_err(|err| Error::ConnectionError(format!("fetching categories: {}", err)))?;
+
+        Ok(rows.into_iter().map(|row| row.category).collect())
+    }
+
     async fn articles_list(
         &self,
         opts: ArticlesListOptions,
@@ -92,34 +364,136 @@ impl Repo for PostgresRepo {
     ) -> Result<(Vec<Article>, i64), Error> {
         let mut query = QueryBuilder::new(
             r#"
-        SELECT id, title, description, content, updated_at, created_at, status, author FROM blog.articles
+        SELECT id, title, description, content, updated_at, created_at, status, author, slug, publish_at, version, category,
+        ARRAY(SELECT tag FROM blog.article_tags t WHERE t.article_id = a.id ORDER BY tag) AS tags
+        FROM blog.articles a
         "#,
         );
 
         let mut count = QueryBuilder::new(
             r#"
-        SELECT COUNT(*) FROM blog.articles
+        SELECT COUNT(*) FROM blog.articles a
         "#,
         );
 
-        if let ArticlesListOptions::Filtered(status) = opts {
-            query.push(" WHERE status = ");
-            query.push_bind(status.to_string());
+        query.push(" WHERE TRUE");
+        push_filters(&mut query, &opts);
 
-            count.push(" WHERE status = ");
-            count.push_bind(status.to_string());
+        count.push(" WHERE TRUE");
+        push_filters(&mut count, &opts);
+
+        query.push(" ORDER BY created_at DESC");
+        query.push(" LIMIT ");
+        query.push_bind(limit);
+        query.push(" OFFSET ");
+        query.push_bind(offset);
+
+        let mut items = Vec::new();
+        let mut rows = query
+            .build_query_as::<(
+                Uuid,
+                String,
+                String,
+                String,
+                DateTime<Utc>,
+                DateTime<Utc>,
+                String,
+                String,
+                String,
+                Option<DateTime<Utc>>,
+                i32,
+                Option<String>,
+                Vec<String>,
+            )>()
+            .fetch(&*self.db);
+
+        while let Some(row) = rows.next().await {
+            let article = match row {
+                Ok((
+                    id,
+                    title,
+                    description,
+                    content,
+                    updated_at,
+                    created_at,
+                    status,
+                    author,
+                    slug,
+                    publish_at,
+                    version,
+                    category,
+                    tags,
+                )) => Article {
+                    id,
+                    title,
+                    description,
+                    content,
+                    updated_at,
+                    created_at,
+                    status: Status::from_string(status),
+                    author,
+                    slug,
+                    tags,
+                    category,
+                    publish_at,
+                    version,
+                },
+
+                Err(err) => {
+                    return Err(Error::ConnectionError(format!("fetching data: {}", err)));
+                }
+            };
+
+            items.push(article);
         }
 
-        query.push("ORDER BY created_at DESC");
+        let count: i64 = count
+            .build_query_scalar()
+            .fetch_one(&*self.db)
+            .await
+            .map_err(|err| Error::ConnectionError(format!("fetching count: {}", err)))?;
+
+        Ok((items, count))
+    }
+
+    async fn articles_search(
+        &self,
+        search: String,
+        opts: ArticlesListOptions,
+        limit: i64,
+        offset: i64,
+    ) -> Result<(Vec<SearchResult>, i64), Error> {
+        let mut query = QueryBuilder::new(
+            r#"
+        SELECT id, title, description, content, updated_at, created_at, status, author, slug, publish_at, version, category,
+        ARRAY(SELECT tag FROM blog.article_tags t WHERE t.article_id = a.id ORDER BY tag) AS tags,
+        ts_rank(a.search, q) AS rank,
+        ts_headline('english', translate(a.content, "#,
+        );
+        // markers already in the content would be turned into unbalanced <mark> tags
+        query.push_bind(format!("{}{}", MATCH_START, MATCH_STOP));
+        query.push(", ''), q, ");
+        query.push_bind(format!(
+            "StartSel={}, StopSel={}, MaxFragments=2, MaxWords=30, MinWords=10",
+            MATCH_START, MATCH_STOP
+        ));
+        query.push(") AS snippet FROM blog.articles a, websearch_to_tsquery('english', ");
+        query.push_bind(search.clone());
+        query.push(") q WHERE a.search @@ q");
+        push_filters(&mut query, &opts);
+        query.push(" ORDER BY rank DESC, created_at DESC");
         query.push(" LIMIT ");
         query.push_bind(limit);
         query.push(" OFFSET ");
         query.push_bind(offset);
 
-        count.push(" LIMIT ");
-        count.push_bind(limit);
-        count.push(" OFFSET ");
-        count.push_bind(offset);
+        let mut count = QueryBuilder::new(
+            r#"
+        SELECT COUNT(*) FROM blog.articles a, websearch_to_tsquery('english', "#,
+        );
+        count.push_bind(search);
+        count.push(") q WHERE a.search @@ q");
+        push_filters(&mut count, &opts);
 
         let mut items = Vec::new();
         let mut rows = query
@@ -132,13 +506,36 @@ impl Repo for PostgresRepo {
                 DateTime<Utc>,
                 String,
                 String,
+                String,
+                Option<DateTime<Utc>>,
+                i32,
+                Option<String>,
+                Vec<String>,
+                f32,
+                String,
             )>()
             .fetch(&*self.db);
 
         while let Some(row) = rows.next().await {
-            let article = match row {
-                Ok((id, title, description, content, updated_at, created_at, status, author)) => {
-                    Article {
+            let result = match row {
+                Ok((
+                    id,
+                    title,
+                    description,
+                    content,
+                    updated_at,
+                    created_at,
+                    status,
+                    author,
+                    slug,
+                    publish_at,
+                    version,
+                    category,
+                    tags,
+                    rank,
+                    snippet,
+                )) => SearchResult {
+                    article: Article {
                         id,
                         title,
                         description,
@@ -147,27 +544,29 @@ impl Repo for PostgresRepo {
                         created_at,
                         status: Status::from_string(status),
                         author,
-                    }
-                }
+                        slug,
+                        tags,
+                        category,
+                        publish_at,
+                        version,
+                    },
+                    rank,
+                    snippet: highlight(&snippet),
+                },
 
                 Err(err) => {
-                    return Err(Error::ConnectionError(format!(
-                        "fetching data: {}",
-                        err.to_string()
-                    )));
+                    return Err(Error::ConnectionError(format!("searching data: {}", err)));
                 }
             };
 
-            items.push(article);
+            items.push(result);
         }
 
         let count: i64 = count
             .build_query_scalar()
             .fetch_one(&*self.db)
             .await
-            .map_err(|err| {
-                Error::ConnectionError(format!("fetching count: {}", err.to_string()))
-            })?;
+            .map_err(|err| Error::ConnectionError(format!("fetching count: {}", err)))?;
 
         Ok((items, count))
     }
@@ -188,30 +587,74 @@ impl Repo for PostgresRepo {
         }
     }
 
-    async fn article_update(
-        &self,
-        id: Uuid,
-        title: String,
-        description: String,
-        content: String,
-    ) -> Result<(), Error> {
-        let result = sqlx::query!(
-            r#"UPDATE blog.articles SET title = $1, description = $2, content = $3, updated_at = $4 WHERE id = $5"#,
-            title, description, content, Utc::now(), id,
+    async fn article_update(&self, article: Article, editor: String) -> Result<(), Error> {
+        let id = article.id;
+
+        let mut tx = self
+            .db
+            .begin()
+            .await
+            .map_err(|err| Error::ConnectionError(format!("starting transaction: {}", err)))?;
+
+        let current = sqlx::query!(
+            r#"SELECT slug FROM blog.articles WHERE id = $1 FOR UPDATE"#,
+            id
         )
-        .execute(&*self.db)
-        .await;
+        .fetch_optional(&mut *tx)
+        .await
+        .map_err(|err| Error::ConnectionError(format!("fetching data: {}", err)))?
+        .ok_or_else(|| Error::NotFound(format!("article {}", id)))?;
 
-        if let Err(err) = result {
-            return Err(Error::ConnectionError(format!("updating data: {}", err)));
+        let updated = sqlx::query!(
+            r#"UPDATE blog.articles SET title = $1, description = $2, content = $3, slug = $4, updated_at = $5, category = $6, version = version + 1
+            WHERE id = $7 AND version = $8"#,
+            article.title, article.description, article.content, article.slug, article.updated_at, article.category, id, article.version,
+        )
+        .execute(&mut *tx)
+        .await
+        .map_err(|err| write_error(err, &article.slug, "updating data"))?;
+
+        if updated.rows_affected() == 0 {
+            return Err(Error::PreconditionFailed(format!(
+                "article {} was modified concurrently",
+                id
+            )));
         }
 
-        Ok(())
+        if current.slug != article.slug {
+            // keep the previous slug around so that old links can be redirected
+            sqlx::query!(
+                r#"INSERT INTO blog.article_slugs (slug, article_id, created_at) VALUES ($1, $2, $3)
+                ON CONFLICT (slug) DO UPDATE SET article_id = EXCLUDED.article_id, created_at = EXCLUDED.created_at"#,
+                current.slug,
+                id,
+                Utc::now(),
+            )
+            .execute(&mut *tx)
+            .await
+            .map_err(|err| Error::ConnectionError(format!("updating data: {}", err)))?;
+
+            sqlx::query!(
+                r#"DELETE FROM blog.article_slugs WHERE slug = $1 AND article_id = $2"#,
+                article.slug,
+                id,
+            )
+            .execute(&mut *tx)
+            .await
+            .map_err(|err| Error::ConnectionError(format!("updating data: {}", err)))?;
+        }
+
+        set_tags(&mut tx, id, &article.tags).await?;
+        add_revision(&mut tx, &article, &editor).await?;
+
+        tx.commit()
+            .await
+            .map_err(|err| Error::ConnectionError(format!("committing transaction: {}", err)))
     }
 
     async fn article_set_status(&self, id: Uuid, status: Status) -> Result<(), Error> {
         let result = sqlx::query!(
-            r#"UPDATE blog.articles SET status = $1, updated_at = $2 WHERE id = $3"#,
+            r#"UPDATE blog.articles SET status = $1, publish_at = NULL, updated_at = $2 WHERE id = $3"#,
             status.to_string(),
             Utc::now(),
             id,
@@ -226,6 +669,132 @@ impl Repo for PostgresRepo {
         Ok(())
     }
 
+    async fn article_schedule(&self, id: Uuid, publish_at: DateTime<Utc>) -> Result<bool, Error> {
+        let result = sqlx::query!(
+            r#"UPDATE blog.articles SET status = $1, publish_at = $2, updated_at = $3
+            WHERE id = $4 AND status IN ($1, $5)"#,
+            Status::Scheduled.to_string(),
+            publish_at,
+            Utc::now(),
+            id,
+            Status::Draft.to_string(),
+        )
+        .execute(&*self.db)
+        .await
+        .map_err(|err| Error::ConnectionError(format!("updating data: {}", err)))?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn articles_publish_due(&self, now: DateTime<Utc>) -> Result<Vec<Uuid>, Error> {
+        let rows = sqlx::query!(
+            r#"
+            UPDATE blog.articles SET status = $1, publish_at = NULL, updated_at = $2
+            WHERE status = $3 AND publish_at <= $2
+            RETURNING id
+            "#,
+            Status::Published.to_string(),
+            now,
+            Status::Scheduled.to_string(),
+        )
+        .fetch_all(&*self.db)
+        .await
+        .map_err(|err| Error::ConnectionError(format!("publishing scheduled: {}", err)))?;
+
+        Ok(rows.into_iter().map(|row| row.id).collect())
+    }
+
+    async fn article_tag_attach(&self, id: Uuid, tag: String, editor: String) -> Result<(), Error> {
+        let mut tx = self
+            .db
+            .begin()
+            .await
+            .map_err(|err| Error::ConnectionError(format!("starting transaction: {}", err)))?;
+
+        sqlx::query!(
+            r#"INSERT INTO blog.tags (name, created_at) VALUES ($1, $2) ON CONFLICT DO NOTHING"#,
+            tag,
+            Utc::now(),
+        )
+        .execute(&mut *tx)
+        .await
+        .map_err(|err| Error::ConnectionError(format!("inserting tag: {}", err)))?;
+
+        let result = sqlx::query!(
+            r#"INSERT INTO blog.article_tags (article_id, tag) VALUES ($1, $2) ON CONFLICT DO NOTHING"#,
+            id,
+            tag,
+        )
+        .execute(&mut *tx)
+        .await
+        .map_err(|err| Error::ConnectionError(format!("inserting tag: {}", err)))?;
+
+        if result.rows_affected() > 0 {
+            touch(&mut tx, id, &editor).await?;
+        }
+
+        tx.commit()
+            .await
+            .map_err(|err| Error::ConnectionError(format!("committing transaction: {}", err)))
+    }
+
+    async fn article_tag_detach(&self, id: Uuid, tag: String, editor: String) -> Result<(), Error> {
+        let mut tx = self
+            .db
+            .begin()
+            .await
+            .map_err(|err| Error::ConnectionError(format!("starting transaction: {}", err)))?;
+
+        let result = sqlx::query!(
+            r#"DELETE FROM blog.article_tags WHERE article_id = $1 AND tag = $2"#,
+            id,
+            tag,
+        )
+        .execute(&mut *tx)
+        .await
+        .map_err(|err| Error::ConnectionError(format!("deleting tag: {}", err)))?;
+
+        if result.rows_affected() > 0 {
+            touch(&mut tx, id, &editor).await?;
+        }
+
+        tx.commit()
+            .await
+            .map_err(|err| Error::ConnectionError(format!("committing transaction: {}", err)))
+    }
+
+    async fn article_category_set(
+        &self,
+        id: Uuid,
+        category: Option<String>,
+        editor: String,
+    ) -> Result<(), Error> {
+        let mut tx = self
+            .db
+            .begin()
+            .await
+            .map_err(|err| Error::ConnectionError(format!("starting transaction: {}", err)))?;
+
+        let result = sqlx::query!(
+            r#"UPDATE blog.articles SET category = $1, updated_at = $2, version = version + 1
+            WHERE id = $3 AND category IS DISTINCT FROM $1"#,
+            category,
+            Utc::now(),
+            id,
+        )
+        .execute(&mut *tx)
+        .await
+        .map_err(|err| Error::ConnectionError(format!("updating category: {}", err)))?;
+
+        if result.rows_affected() > 0 {
+            add_current_revision(&mut tx, id, &editor).await?;
+        }
+
+        tx.commit()
+            .await
+            .map_err(|err| Error::ConnectionError(format!("committing transaction: {}", err)))
+    }
+
     async fn article_delete(&self, id: Uuid) -> Result<(), Error> {
         let result = sqlx::query!(r#"DELETE FROM blog.articles WHERE id = $1"#, id,)
             .execute(&*self.db)
@@ -237,4 +806,52 @@ impl Repo for PostgresRepo {
 
         Ok(())
     }
+
+    async fn roles_get(&self, login: String) -> Result<Option<Role>, Error> {
+        let row = sqlx::query!(r#"SELECT role FROM blog.roles WHERE login = $1"#, login)
+            .fetch_optional(&*self.db)
+            .await
+            .map_err(|err| Error::ConnectionError(format!("fetching role: {}", err)))?;
+
+        Ok(row.map(|row| Role::from_string(row.role)))
+    }
+
+    async fn roles_list(&self) -> Result<Vec<RoleAssignment>, Error> {
+        let rows = sqlx::query!(r#"SELECT login, role FROM blog.roles ORDER BY login"#)
+            .fetch_all(&*self.db)
+            .await
+            .map_err(|err| Error::ConnectionError(format!("fetching roles: {}", err)))?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| RoleAssignment {
+                login: row.login,
+                role: Role::from_string(row.role),
+            })
+            .collect())
+    }
+
+    async fn roles_set(&self, login: String, role: Role) -> Result<(), Error> {
+        sqlx::query!(
+            r#"INSERT INTO blog.roles (login, role, updated_at) VALUES ($1, $2, $3)
+            ON CONFLICT (login) DO UPDATE SET role = EXCLUDED.role, updated_at = EXCLUDED.updated_at"#,
+            login,
+            role.to_string(),
+            Utc::now(),
+        )
+        .execute(&*self.db)
+        .await
+        .map_err(|err| Error::ConnectionError(format!("updating role: {}", err)))?;
+
+        Ok(())
+    }
+
+    async fn roles_delete(&self, login: String) -> Result<(), Error> {
+        sqlx::query!(r#"DELETE FROM blog.roles WHERE login = $1"#, login)
+            .execute(&*self.db)
+            .await
+            .map_err(|err| Error::ConnectionError(format!("deleting role: {}", err)))?;
+
+        Ok(())
+    }
 }
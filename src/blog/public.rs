@@ -0,0 +1,459 @@
+use super::{Article, ArticlesListOptions, Reader, SlugLookup, markdown};
+use crate::errors::Error;
+use crate::web::Listing;
+use actix_web::{App, HttpResponse, HttpServer, Responder, get, web};
+use askama::Template;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tracing_actix_web::TracingLogger;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+struct State {
+    reader: Arc<dyn Reader>,
+}
+
+#[derive(Serialize)]
+struct PublishedArticle {
+    #[serde(flatten)]
+    article: Article,
+    content_html: String,
+}
+
+impl From<Article> for PublishedArticle {
+    fn from(article: Article) -> Self {
+        let content_html = markdown::render(&article.content);
+
+        PublishedArticle {
+            article,
+            content_html,
+        }
+    }
+}
+
+// Same as ArticleResponse in blog::http, with the rendered content added.
+#[allow(dead_code)]
+#[derive(ToSchema)]
+pub struct PublishedArticleResponse {
+    id: String,
+    title: String,
+    description: String,
+    content: String,
+    content_html: String,
+    updated_at: String,
+    created_at: String,
+    status: String,
+    author: String,
+    slug: String,
+    tags: Vec<String>,
+    category: Option<String>,
+    publish_at: Option<String>,
+    version: i32,
+}
+
+#[derive(Deserialize, ToSchema)]
+struct PublishedArticlesListRequest {
+    tag: Option<String>,
+    category: Option<String>,
+    page: Option<i64>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/public/articles",
+    description = "List published articles",
+    tag = "public",
+    responses(
+        (status = 200, description = "Published articles listing", body = Listing<PublishedArticleResponse>),
+    ),
+    params(
+        ("tag" = Option<String>, Query, description = "Filter by tag"),
+        ("category" = Option<String>, Query, description = "Filter by category"),
+        ("page" = Option<i64>, Query, description = "Page number"),
+    )
+)]
+#[get("/api/public/articles")]
+pub async fn list_published_articles(
+    state: web::Data<State>,
+    query: web::Query<PublishedArticlesListRequest>,
+) -> impl Responder {
+    let page = query.page.unwrap_or(1);
+    let opts = ArticlesListOptions::default()
+        .with_tag(query.tag.clone())
+        .with_category(query.category.clone());
+
+    match state.reader.list(opts, page).await {
+        Ok(listing) => HttpResponse::Ok().json(Listing {
+            items: listing
+                .items
+                .into_iter()
+                .map(PublishedArticle::from)
+                .collect(),
+            pages: listing.pages,
+        }),
+        Err(err) => json_error(err),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/public/articles/{id}",
+    description = "Get a published article",
+    tag = "public",
+    responses(
+        (status = 200, description = "Article", body = PublishedArticleResponse),
+    ),
+    params(
+        ("id" = String, Path, description = "Article id"),
+    )
+)]
+#[get("/api/public/articles/{id}")]
+pub async fn get_published_article(
+    state: web::Data<State>,
+    path: web::Path<(Uuid,)>,
+) -> impl Responder {
+    let id = path.into_inner().0;
+
+    match state.reader.get(id).await {
+        Ok(article) => HttpResponse::Ok().json(PublishedArticle::from(article)),
+        Err(err) => json_error(err),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/public/articles/slug/{slug}",
+    description = "Get a published article by slug, renamed articles redirect to their current slug",
+    tag = "public",
+    responses(
+        (status = 200, description = "Article", body = PublishedArticleResponse),
+        (status = 301, description = "Article has a new slug"),
+    ),
+    params(
+        ("slug" = String, Path, description = "Article slug"),
+    )
+)]
+#[get("/api/public/articles/slug/{slug}")]
+pub async fn get_published_article_by_slug(
+    state: web::Data<State>,
+    path: web::Path<(String,)>,
+) -> impl Responder {
+    let slug = path.into_inner().0;
+
+    match state.reader.get_by_slug(slug).await {
+        Ok(SlugLookup::Found(article)) => HttpResponse::Ok().json(PublishedArticle::from(*article)),
+        Ok(SlugLookup::Moved(slug)) => HttpResponse::MovedPermanently()
+            .append_header(("Location", format!("/api/public/articles/slug/{}", slug)))
+            .finish(),
+        Err(err) => json_error(err),
+    }
+}
+
+#[derive(Template)]
+#[template(path = "index.html")]
+struct IndexPage {
+    articles: Vec<Article>,
+    tag: Option<String>,
+    category: Option<String>,
+    page: i64,
+    pages: i64,
+}
+
+impl IndexPage {
+    fn page_url(&self, page: i64) -> String {
+        let mut query = url::form_urlencoded::Serializer::new(String::new());
+
+        if let Some(tag) = &self.tag {
+            query.append_pair("tag", tag);
+        }
+
+        if let Some(category) = &self.category {
+            query.append_pair("category", category);
+        }
+
+        query.append_pair("page", &page.to_string());
+
+        format!("/blog?{}", query.finish())
+    }
+}
+
+#[derive(Template)]
+#[template(path = "article.html")]
+struct ArticlePage {
+    article: Article,
+    content_html: String,
+}
+
+#[derive(Template)]
+#[template(path = "error.html")]
+struct ErrorPage {
+    status: String,
+    message: String,
+}
+
+fn html<T: Template>(page: T) -> HttpResponse {
+    match page.render() {
+        Ok(body) => HttpResponse::Ok()
+            .content_type("text/html; charset=utf-8")
+            .body(body),
+        Err(err) => HttpResponse::InternalServerError().body(server_error(&err)),
+    }
+}
+
+/// Message shown instead of server errors, which can carry database details.
+const SERVER_ERROR_MESSAGE: &str = "something went wrong, please try again later";
+
+/// Logs a server error and returns the message readers get instead.
+fn server_error(err: &dyn std::fmt::Display) -> String {
+    tracing::error!(error = %err, "serving public request");
+    SERVER_ERROR_MESSAGE.to_string()
+}
+
+fn json_error(err: Error) -> HttpResponse {
+    let response = err.to_http_response();
+
+    if response.status().is_server_error() {
+        HttpResponse::build(response.status()).body(server_error(&err))
+    } else {
+        response
+    }
+}
+
+fn html_error(err: Error) -> HttpResponse {
+    let status = err.to_http_response().status();
+
+    let message = if status.is_server_error() {
+        server_error(&err)
+    } else {
+        err.to_string()
+    };
+
+    let page = ErrorPage {
+        status: status.to_string(),
+        message,
+    };
+
+    let mut response = html(page);
+    *response.status_mut() = status;
+
+    response
+}
+
+#[get("/blog")]
+async fn index_page(
+    state: web::Data<State>,
+    query: web::Query<PublishedArticlesListRequest>,
+) -> impl Responder {
+    let page = query.page.unwrap_or(1).max(1);
+    let opts = ArticlesListOptions::default()
+        .with_tag(query.tag.clone())
+        .with_category(query.category.clone());
+
+    match state.reader.list(opts.clone(), page).await {
+        Ok(listing) => html(IndexPage {
+            articles: listing.items,
+            tag: opts.tag,
+            category: opts.category,
+            page,
+            pages: listing.pages,
+        }),
+        Err(err) => html_error(err),
+    }
+}
+
+#[get("/blog/{slug}")]
+async fn article_page(state: web::Data<State>, path: web::Path<(String,)>) -> impl Responder {
+    let slug = path.into_inner().0;
+
+    match state.reader.get_by_slug(slug).await {
+        Ok(SlugLookup::Found(article)) => html(ArticlePage {
+            content_html: markdown::render(&article.content),
+            article: *article,
+        }),
+        Ok(SlugLookup::Moved(slug)) => HttpResponse::MovedPermanently()
+            .append_header(("Location", format!("/blog/{}", slug)))
+            .finish(),
+        Err(err) => html_error(err),
+    }
+}
+
+pub async fn server(reader: Arc<dyn Reader>, listen_addr: String) -> Result<(), std::io::Error> {
+    let data = web::Data::new(State { reader });
+
+    HttpServer::new(move || {
+        App::new()
+            .app_data(data.clone())
+            .wrap(TracingLogger::default())
+            .service(list_published_articles)
+            .service(get_published_article)
+            .service(get_published_article_by_slug)
+            .service(index_page)
+            .service(article_page)
+    })
+    .bind(listen_addr)?
+    .run()
+    .await
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::blog::{DefaultReader, MockRepo, Status};
+    use actix_web::test::{TestRequest, call_service, init_service, read_body};
+    use chrono::{TimeZone, Utc};
+
+    fn article() -> Article {
+        Article {
+            id: Uuid::new_v4(),
+            title: "Hello <world>".to_string(),
+            description: "description".to_string(),
+            content: "content".to_string(),
+            author: "author".to_string(),
+            created_at: Utc.with_ymd_and_hms(2025, 3, 22, 10, 0, 0).unwrap(),
+            updated_at: Utc::now(),
+            status: Status::Published,
+            slug: "hello-world".to_string(),
+            tags: vec!["rust".to_string()],
+            category: Some("languages".to_string()),
+            publish_at: None,
+            version: 1,
+        }
+    }
+
+    #[actix_web::test]
+    async fn html_error_hides_server_errors() {
+        let response = html_error(Error::ConnectionError(
+            "connecting to db: password authentication failed".to_string(),
+        ));
+        assert_eq!(response.status(), 500);
+
+        let body = actix_web::body::to_bytes(response.into_body())
+            .await
+            .unwrap();
+        let body = String::from_utf8(body.to_vec()).unwrap();
+        assert!(body.contains(SERVER_ERROR_MESSAGE));
+        assert!(!body.contains("password"));
+
+        let response = html_error(Error::NotFound("article hello".to_string()));
+        assert_eq!(response.status(), 404);
+
+        let body = actix_web::body::to_bytes(response.into_body())
+            .await
+            .unwrap();
+        assert!(
+            String::from_utf8(body.to_vec())
+                .unwrap()
+                .contains("article hello")
+        );
+    }
+
+    #[actix_web::test]
+    async fn json_error_hides_server_errors() {
+        let mut repo = MockRepo::new();
+        repo.expect_articles_get().returning(|_| {
+            Err(Error::ConnectionError(
+                "connecting to db: password authentication failed".to_string(),
+            ))
+        });
+
+        let data = web::Data::new(State {
+            reader: Arc::new(DefaultReader::new(Arc::new(repo), 10)),
+        });
+        let app = init_service(App::new().app_data(data).service(get_published_article)).await;
+
+        let req = TestRequest::get()
+            .uri(&format!("/api/public/articles/{}", Uuid::new_v4()))
+            .to_request();
+        let resp = call_service(&app, req).await;
+        assert_eq!(resp.status(), 500);
+
+        let body = String::from_utf8(read_body(resp).await.to_vec()).unwrap();
+        assert_eq!(body, SERVER_ERROR_MESSAGE);
+    }
+
+    #[test]
+    fn render_article_page() {
+        let body = ArticlePage {
+            article: article(),
+            content_html: markdown::render("## Heading"),
+        }
+        .render()
+        .unwrap();
+
+        assert!(body.contains("<title>Hello &#60;world&#62;</title>"));
+        assert!(body.contains("March 22, 2025"));
+        assert!(body.contains("<h2>Heading</h2>"));
+    }
+
+    #[test]
+    fn render_index_page() {
+        let article = article();
+        let body = IndexPage {
+            articles: vec![article.clone()],
+            tag: None,
+            category: None,
+            page: 2,
+            pages: 3,
+        }
+        .render()
+        .unwrap();
+
+        assert!(body.contains("/blog/hello-world"));
+        assert!(body.contains("/blog?page=1"));
+        assert!(body.contains("/blog?page=3"));
+        assert!(body.contains("/blog?tag=rust"));
+        assert!(body.contains("/blog?category=languages"));
+    }
+
+    #[test]
+    fn render_index_page_tagged() {
+        let body = IndexPage {
+            articles: vec![article()],
+            tag: Some("rust".to_string()),
+            category: None,
+            page: 1,
+            pages: 2,
+        }
+        .render()
+        .unwrap();
+
+        assert!(body.contains("/blog?tag=rust&#38;page=2"));
+    }
+
+    #[test]
+    fn render_index_page_categorized() {
+        let body = IndexPage {
+            articles: vec![article()],
+            tag: Some("rust".to_string()),
+            category: Some("languages".to_string()),
+            page: 1,
+            pages: 2,
+        }
+        .render()
+        .unwrap();
+
+        assert!(body.contains("Articles in <strong>languages</strong>"));
+        assert!(body.contains("/blog?tag=rust&#38;category=languages&#38;page=2"));
+    }
+
+    #[test]
+    fn render_index_page_encodes_filters() {
+        let mut article = article();
+        article.tags = vec!["c&c++ #1".to_string()];
+        article.category = Some("a b".to_string());
+
+        let body = IndexPage {
+            articles: vec![article],
+            tag: Some("c&c++ #1".to_string()),
+            category: Some("a b".to_string()),
+            page: 1,
+            pages: 2,
+        }
+        .render()
+        .unwrap();
+
+        assert!(body.contains("/blog?tag=c%26c%2B%2B+%231&#38;category=a+b&#38;page=2"));
+        assert!(body.contains("/blog?tag=c%26c%2B%2B%20%231"));
+        assert!(body.contains("/blog?category=a%20b"));
+    }
+}
@@ -0,0 +1,23 @@
+use super::Admin;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Publishes scheduled articles as they become due, checking every `interval`.
+///
+/// Runs until the process exits; failures are logged and retried on the next tick.
+pub async fn run(admin: Arc<dyn Admin>, interval: Duration) {
+    let mut ticker = tokio::time::interval(interval);
+
+    loop {
+        ticker.tick().await;
+
+        match admin.publish_due().await {
+            Ok(ids) => {
+                for id in ids {
+                    tracing::info!(article = %id, "published scheduled article");
+                }
+            }
+            Err(err) => tracing::error!(error = %err, "publishing scheduled articles"),
+        }
+    }
+}
@@ -0,0 +1,38 @@
+// markers around matches in search snippets, replaced once the snippet has been escaped
+pub(super) const MATCH_START: char = '\u{2}';
+pub(super) const MATCH_STOP: char = '\u{3}';
+
+/// Escapes a search snippet and highlights the matches between markers with `<mark>`.
+pub(super) fn highlight(snippet: &str) -> String {
+    let mut html = String::with_capacity(snippet.len());
+
+    for c in snippet.chars() {
+        match c {
+            MATCH_START => html.push_str("<mark>"),
+            MATCH_STOP => html.push_str("</mark>"),
+            '&' => html.push_str("&amp;"),
+            '<' => html.push_str("&lt;"),
+            '>' => html.push_str("&gt;"),
+            '"' => html.push_str("&quot;"),
+            '\'' => html.push_str("&#39;"),
+            c => html.push(c),
+        }
+    }
+
+    html
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_highlight() {
+        let snippet = format!("use {}tokio{} <script>&\"'", MATCH_START, MATCH_STOP);
+
+        assert_eq!(
+            highlight(&snippet),
+            "use <mark>tokio</mark> &lt;script&gt;&amp;&quot;&#39;"
+        );
+    }
+}
@@ -1,6 +1,7 @@
 use actix_web::HttpResponse;
 use thiserror::Error;
 
+#[allow(clippy::enum_variant_names)]
 #[derive(Debug, Error)]
 pub enum Error {
     #[error("initialization error: {0}")]
@@ -20,6 +21,9 @@ pub enum Error {
 
     #[error("invalid input: {0}")]
     InvalidInput(String),
+
+    #[error("precondition failed: {0}")]
+    PreconditionFailed(String),
 }
 
 impl Error {
@@ -33,6 +37,7 @@ impl Error {
             Error::PermissionDenied(msg) => HttpResponse::Forbidden().body(msg.clone()),
             Error::NotFound(msg) => HttpResponse::NotFound().body(msg.clone()),
             Error::InvalidInput(msg) => HttpResponse::BadRequest().body(msg.clone()),
+            Error::PreconditionFailed(msg) => HttpResponse::PreconditionFailed().body(msg.clone()),
         }
     }
 }
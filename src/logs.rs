@@ -1,8 +1,8 @@
-use hostname;
 use tracing_loki::BackgroundTask;
 use tracing_subscriber::layer::SubscriberExt;
 use url::Url;
 
+#[allow(dead_code)]
 pub fn loki(url: String) -> BackgroundTask {
     let loki_url = Url::parse(format!("{}/loki/api/v1/push", url).as_str()).unwrap();
     let host = hostname::get().unwrap().to_str().unwrap().to_string();
@@ -6,33 +6,217 @@ mod web;
 
 use clap::{Parser, Subcommand};
 use serde::Deserialize;
+use std::num::NonZeroU64;
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::fs;
 use utoipa::OpenApi;
 #[derive(Deserialize)]
 struct Config {
     base_url: String,
+    /// Where articles, roles, users and API tokens are kept.
+    #[serde(default)]
+    store: Store,
+    #[serde(default)]
     dsn: String,
+    #[allow(dead_code)]
     loki: String,
     auth: AuthConfig,
     admin: AdminConfig,
+    public: PublicConfig,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+enum Store {
+    #[default]
+    Postgres,
+    /// Nothing outlives the process, the services must run together with `blog all`.
+    Memory,
 }
 
 #[derive(Deserialize)]
 struct AuthConfig {
     listen_addr: String,
+    /// Where sessions are kept.
+    #[serde(default)]
+    store: SessionStore,
+    /// Address of keydb, with the redis store.
+    #[serde(default)]
     redis: String,
+    /// Seconds between deletions of expired sessions, with the postgres store.
+    #[serde(default = "default_sweep_interval")]
+    sweep_interval: NonZeroU64,
+    /// Seconds a session lives without being used, each use extends it.
     ttl: i64,
+    /// Seconds a session lives at most after login, however much it is used.
+    #[serde(default = "default_max_lifetime")]
+    max_lifetime: i64,
 
     gh_client_id: String,
     gh_client_secret: String,
+    /// Restricts login to members of this organization, when not empty.
+    #[serde(default)]
+    gh_org: String,
+    /// Further restricts login to a team of `gh_org`, by slug.
+    #[serde(default)]
+    gh_team: String,
+
+    /// Login providers besides Github, each available at `/api/auth/login/{name}`.
+    #[serde(default)]
+    providers: Vec<ProviderConfig>,
+
+    /// Enables local accounts, stored in postgres, when present.
+    local: Option<LocalConfig>,
+
+    /// Hands out sessions as signed tokens, verified without keydb, when present.
+    signed: Option<SignedConfig>,
+
+    /// Keeps recently seen sessions in memory, when present.
+    cache: Option<CacheConfig>,
 
     cookie: String,
+    /// Domain of the session cookie, the host of the auth service when empty.
+    #[serde(default)]
+    cookie_domain: String,
+    /// Only send the session cookie over https, required when `base_url` is https.
+    #[serde(default)]
+    cookie_secure: bool,
+    #[serde(default = "default_true")]
+    cookie_http_only: bool,
+    /// One of strict, lax or none.
+    #[serde(default = "default_same_site")]
+    cookie_same_site: String,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+enum SessionStore {
+    #[default]
+    Redis,
+    Postgres,
+    /// Nothing outlives the process, the services must run together with `blog all`.
+    Memory,
+}
+
+fn default_sweep_interval() -> NonZeroU64 {
+    NonZeroU64::new(300).unwrap()
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_same_site() -> String {
+    "lax".to_string()
+}
+
+#[derive(Deserialize)]
+struct LocalConfig {
+    /// Lets anyone register, otherwise an invite is needed.
+    #[serde(default)]
+    open_registration: bool,
+}
+
+#[derive(Deserialize)]
+struct SignedConfig {
+    /// Seconds a signed token is trusted without checking its session in keydb.
+    #[serde(default = "default_signed_ttl")]
+    ttl: i64,
+    /// Seconds between reloads of the denylist, how long a revocation can take to reach other services.
+    #[serde(default = "default_denylist_refresh")]
+    denylist_refresh: NonZeroU64,
+    /// Signing keys, the first one signs and the others only verify, to rotate keys.
+    keys: Vec<SigningKeyConfig>,
+}
+
+fn default_signed_ttl() -> i64 {
+    900
+}
+
+fn default_denylist_refresh() -> NonZeroU64 {
+    NonZeroU64::new(10).unwrap()
+}
+
+#[derive(Deserialize)]
+struct CacheConfig {
+    /// Sessions kept at most.
+    #[serde(default = "default_cache_size")]
+    size: usize,
+    /// Seconds a session is kept, it is checked again at most every minute.
+    #[serde(default = "default_cache_ttl")]
+    ttl: i64,
+    /// Seconds between reloads of the denylist, how long a revocation can take to drop sessions cached elsewhere.
+    #[serde(default = "default_denylist_refresh")]
+    denylist_refresh: NonZeroU64,
+    /// Seconds between logs of the hit rate.
+    #[serde(default = "default_cache_report_interval")]
+    report_interval: NonZeroU64,
+    /// Serves the hits, misses and evictions at `/metrics` for Prometheus, when set.
+    metrics_listen_addr: Option<String>,
+}
+
+fn default_cache_size() -> usize {
+    10000
+}
+
+fn default_cache_ttl() -> i64 {
+    10
+}
+
+fn default_cache_report_interval() -> NonZeroU64 {
+    NonZeroU64::new(300).unwrap()
+}
+
+#[derive(Deserialize)]
+struct SigningKeyConfig {
+    id: String,
+    secret: String,
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+enum ProviderConfig {
+    Gitlab {
+        name: String,
+        /// Address of the GitLab instance.
+        url: String,
+        client_id: String,
+        client_secret: String,
+    },
+    Oidc {
+        name: String,
+        /// Issuer url, where `/.well-known/openid-configuration` is served.
+        issuer: String,
+        client_id: String,
+        client_secret: String,
+    },
 }
 
 #[derive(Deserialize)]
 struct AdminConfig {
     listen_addr: String,
+
+    /// How often, in seconds, to look for scheduled articles that are due.
+    #[serde(default = "default_schedule_interval")]
+    schedule_interval: NonZeroU64,
+
+    /// Logins that always have the admin role.
+    #[serde(default)]
+    admins: Vec<String>,
+}
+
+fn default_max_lifetime() -> i64 {
+    7 * 86400
+}
+
+fn default_schedule_interval() -> NonZeroU64 {
+    NonZeroU64::new(60).unwrap()
+}
+
+#[derive(Deserialize)]
+struct PublicConfig {
+    listen_addr: String,
 }
 
 async fn read_config(path: &str) -> Result<Config, ()> {
@@ -54,6 +238,18 @@ struct Cli {
 enum Commands {
     Auth,
     Admin,
+    Public,
+    /// Run the auth, admin and public services in one process, sharing their stores
+    All,
+
+    /// Invite someone to register a local account
+    Invite {
+        login: String,
+    },
+    /// Create a password reset link for a local account
+    ResetPassword {
+        login: String,
+    },
 
     OpenApi {
         /// write to file
@@ -75,80 +271,468 @@ async fn main() -> std::io::Result<()> {
     // tokio::spawn(logtask);
 
     let cli = Cli::parse();
+
+    if !matches!(cli.command, Commands::All | Commands::OpenApi { .. }) {
+        check_stores_shared(&config)?;
+    }
+
     match &cli.command {
         Commands::Auth => auth_service(&config).await.unwrap(),
         Commands::Admin => admin_service(&config).await.unwrap(),
+        Commands::Public => public_service(&config).await.unwrap(),
+        Commands::All => all_services(&config).await.unwrap(),
+        Commands::Invite { login } => invite(&config, login.clone()).await.unwrap(),
+        Commands::ResetPassword { login } => reset_password(&config, login.clone()).await.unwrap(),
         Commands::OpenApi { write } => openapi(write.clone()).await.unwrap(),
     }
 
     Ok(())
 }
 
+/// Memory stores are private to the process, so services using them cannot run on their own.
+fn check_stores_shared(config: &Config) -> std::io::Result<()> {
+    if matches!(config.store, Store::Memory) || matches!(config.auth.store, SessionStore::Memory) {
+        return Err(std::io::Error::other(
+            "memory stores are not shared between processes, run the services with `blog all`",
+        ));
+    }
+
+    Ok(())
+}
+
+/// Stores of the auth and admin services, built once so that services in one process share them.
+struct AuthStores {
+    repo: Arc<dyn auth::Repo>,
+    denylist: Arc<dyn auth::signed::Denylist>,
+    directory: Arc<dyn auth::Directory>,
+    tokens: Arc<auth::tokens::ApiTokens>,
+    /// Local accounts, when enabled.
+    accounts: Option<Arc<dyn auth::local::AccountRepo>>,
+}
+
+async fn auth_stores(config: &Config) -> std::io::Result<AuthStores> {
+    let (repo, denylist) = session_repo(config).await?;
+
+    Ok(AuthStores {
+        repo,
+        denylist,
+        directory: directory(config).await?,
+        tokens: api_tokens(config).await?,
+        accounts: match config.auth.local {
+            Some(_) => Some(accounts(config).await?),
+            None => None,
+        },
+    })
+}
+
 async fn auth_service(config: &Config) -> std::io::Result<()> {
-    let repo = Arc::new(
-        auth::redis::RedisRepo::new(&config.auth.redis, config.auth.ttl)
-            .await
-            .unwrap(),
-    );
-    let sessions = Arc::new(auth::DefaultSessionManager::new(repo.clone()));
-    let authenticator = Arc::new(
-        auth::github::GithubAuthenticator::new(
+    let stores = auth_stores(config).await?;
+    let sessions = session_manager(config, &stores)?;
+
+    serve_auth(config, &stores, sessions).await
+}
+
+async fn admin_service(config: &Config) -> std::io::Result<()> {
+    let stores = auth_stores(config).await?;
+    let sessions = session_manager(config, &stores)?;
+
+    serve_admin(config, blog_repo(config).await?, &stores, sessions).await
+}
+
+async fn public_service(config: &Config) -> std::io::Result<()> {
+    serve_public(config, blog_repo(config).await?).await
+}
+
+/// Runs the three services in this process, on the same stores and session manager.
+async fn all_services(config: &Config) -> std::io::Result<()> {
+    let stores = auth_stores(config).await?;
+    let sessions = session_manager(config, &stores)?;
+    let blog = blog_repo(config).await?;
+
+    tokio::try_join!(
+        serve_auth(config, &stores, sessions.clone()),
+        serve_admin(config, blog.clone(), &stores, sessions),
+        serve_public(config, blog),
+    )?;
+
+    Ok(())
+}
+
+async fn serve_auth(
+    config: &Config,
+    stores: &AuthStores,
+    sessions: Arc<dyn auth::SessionManager>,
+) -> std::io::Result<()> {
+    let repo = stores.repo.clone();
+
+    let mut providers = auth::Providers::new();
+
+    // Github is optional, but stays the default provider when configured
+    if !config.auth.gh_client_id.is_empty() {
+        let github = auth::github::GithubAuthenticator::new(
             repo.clone(),
             config.auth.gh_client_id.clone(),
             config.auth.gh_client_secret.clone(),
             config.base_url.clone(),
         )
-        .unwrap(),
-    );
+        .unwrap()
+        .with_org(config.auth.gh_org.clone(), config.auth.gh_team.clone());
+
+        providers = providers.with("github".to_string(), Arc::new(github));
+    }
+
+    for provider in &config.auth.providers {
+        let (name, authenticator): (&String, Arc<dyn auth::Authenticator>) = match provider {
+            ProviderConfig::Gitlab {
+                name,
+                url,
+                client_id,
+                client_secret,
+            } => (
+                name,
+                Arc::new(
+                    auth::gitlab::GitlabAuthenticator::new(
+                        repo.clone(),
+                        name.clone(),
+                        url.clone(),
+                        client_id.clone(),
+                        client_secret.clone(),
+                        config.base_url.clone(),
+                    )
+                    .unwrap(),
+                ),
+            ),
+            ProviderConfig::Oidc {
+                name,
+                issuer,
+                client_id,
+                client_secret,
+            } => (
+                name,
+                Arc::new(
+                    auth::oidc::OidcAuthenticator::new(
+                        repo.clone(),
+                        name.clone(),
+                        issuer.clone(),
+                        client_id.clone(),
+                        client_secret.clone(),
+                        config.base_url.clone(),
+                    )
+                    .unwrap(),
+                ),
+            ),
+        };
+
+        providers = providers.with(name.clone(), authenticator);
+    }
+
+    let local = match (&config.auth.local, &stores.accounts) {
+        (Some(local_config), Some(accounts)) => {
+            let local = Arc::new(
+                local_authenticator(config, accounts.clone(), repo.clone())
+                    .with_open_registration(local_config.open_registration),
+            );
+
+            providers = providers.with(auth::local::PROVIDER.to_string(), local.clone());
+
+            Some(local)
+        }
+        _ => None,
+    };
+
+    if providers.default_name().is_none() {
+        return Err(std::io::Error::other("no login provider configured"));
+    }
+
+    let cookie = session_cookie(config)?;
 
     println!("🏁 starting auth service on {}", config.auth.listen_addr);
 
     auth::http::server(
         sessions,
-        authenticator,
+        providers,
+        repo,
+        stores.directory.clone(),
+        local,
+        Some(stores.tokens.clone()),
         config.base_url.clone(),
-        config.auth.cookie.clone(),
+        cookie,
         config.auth.listen_addr.clone(),
     )
     .await
-    .unwrap();
+}
+
+fn session_cookie(config: &Config) -> std::io::Result<auth::http::CookieConfig> {
+    let mut cookie = auth::http::CookieConfig::new(config.auth.cookie.clone());
+
+    if !config.auth.cookie_domain.is_empty() {
+        cookie.domain = Some(config.auth.cookie_domain.clone());
+    }
+
+    cookie.secure = config.auth.cookie_secure;
+    cookie.http_only = config.auth.cookie_http_only;
+    cookie.same_site =
+        auth::http::same_site(&config.auth.cookie_same_site).map_err(std::io::Error::other)?;
+    cookie.max_age = config.auth.max_lifetime;
+
+    cookie
+        .check(&config.base_url)
+        .map_err(std::io::Error::other)?;
+
+    Ok(cookie)
+}
+
+/// The session repo of the configured store, which also keeps the denylist of signed tokens and cached sessions.
+async fn session_repo(
+    config: &Config,
+) -> std::io::Result<(Arc<dyn auth::Repo>, Arc<dyn auth::signed::Denylist>)> {
+    match config.auth.store {
+        SessionStore::Redis => {
+            let repo = match auth::redis::RedisRepo::new(&config.auth.redis, config.auth.ttl).await
+            {
+                Ok(repo) => Arc::new(repo.with_max_lifetime(config.auth.max_lifetime)),
+                Err(err) => return Err(std::io::Error::other(err)),
+            };
+
+            Ok((repo.clone(), repo))
+        }
+        SessionStore::Postgres => {
+            let repo = match auth::postgres::PostgresRepo::new(config.dsn.clone(), config.auth.ttl)
+                .await
+            {
+                Ok(repo) => Arc::new(repo.with_max_lifetime(config.auth.max_lifetime)),
+                Err(err) => {
+                    eprintln!("Failed to connect to Postgres");
+                    return Err(std::io::Error::other(format!(
+                        "Failed to connect to database {}",
+                        err
+                    )));
+                }
+            };
+
+            tokio::spawn(auth::postgres::sweeper(
+                repo.clone(),
+                Duration::from_secs(config.auth.sweep_interval.get()),
+            ));
+
+            Ok((repo.clone(), repo))
+        }
+        SessionStore::Memory => {
+            let repo = Arc::new(
+                auth::memory::MemoryRepo::new(config.auth.ttl)
+                    .with_max_lifetime(config.auth.max_lifetime),
+            );
+
+            Ok((repo.clone(), repo))
+        }
+    }
+}
+
+fn session_manager(
+    config: &Config,
+    stores: &AuthStores,
+) -> std::io::Result<Arc<dyn auth::SessionManager>> {
+    let mut sessions: Arc<dyn auth::SessionManager> = Arc::new(
+        auth::DefaultSessionManager::new(stores.repo.clone())
+            .with_directory(stores.directory.clone())
+            .with_tokens(stores.tokens.clone()),
+    );
+
+    if let Some(signed) = &config.auth.signed {
+        let keys = signed
+            .keys
+            .iter()
+            .map(|key| auth::signed::SigningKey {
+                id: key.id.clone(),
+                secret: key.secret.clone(),
+            })
+            .collect();
+
+        let signed_sessions = match auth::signed::SignedSessionManager::new(
+            sessions,
+            stores.denylist.clone(),
+            keys,
+            signed.ttl,
+        ) {
+            Ok(signed) => Arc::new(signed),
+            Err(err) => return Err(std::io::Error::other(err)),
+        };
+
+        tokio::spawn(auth::signed::refresher(
+            signed_sessions.clone(),
+            Duration::from_secs(signed.denylist_refresh.get()),
+        ));
+
+        sessions = signed_sessions;
+    }
+
+    if let Some(cache) = &config.auth.cache {
+        let cached = Arc::new(
+            auth::cache::CachingSessionManager::new(sessions, cache.size, cache.ttl)
+                .with_denylist(stores.denylist.clone()),
+        );
+
+        tokio::spawn(auth::cache::refresher(
+            cached.clone(),
+            Duration::from_secs(cache.denylist_refresh.get()),
+        ));
+        tokio::spawn(auth::cache::report(
+            cached.clone(),
+            Duration::from_secs(cache.report_interval.get()),
+        ));
+
+        if let Some(listen_addr) = &cache.metrics_listen_addr {
+            tokio::spawn(auth::cache::metrics_server(
+                cached.clone(),
+                listen_addr.clone(),
+            )?);
+        }
+
+        sessions = cached;
+    }
+
+    Ok(sessions)
+}
+
+async fn directory(config: &Config) -> std::io::Result<Arc<dyn auth::Directory>> {
+    if let Store::Memory = config.store {
+        return Ok(Arc::new(auth::memory::MemoryDirectory::new()));
+    }
+
+    match auth::postgres::PostgresDirectory::new(config.dsn.clone()).await {
+        Ok(directory) => Ok(Arc::new(directory)),
+        Err(err) => {
+            eprintln!("Failed to connect to Postgres");
+            Err(std::io::Error::other(format!(
+                "Failed to connect to database {}",
+                err
+            )))
+        }
+    }
+}
+
+async fn api_tokens(config: &Config) -> std::io::Result<Arc<auth::tokens::ApiTokens>> {
+    if let Store::Memory = config.store {
+        return Ok(Arc::new(auth::tokens::ApiTokens::new(Arc::new(
+            auth::tokens::memory::MemoryTokenRepo::new(),
+        ))));
+    }
+
+    match auth::tokens::postgres::PostgresTokenRepo::new(config.dsn.clone()).await {
+        Ok(repo) => Ok(Arc::new(auth::tokens::ApiTokens::new(Arc::new(repo)))),
+        Err(err) => {
+            eprintln!("Failed to connect to Postgres");
+            Err(std::io::Error::other(format!(
+                "Failed to connect to database {}",
+                err
+            )))
+        }
+    }
+}
+
+async fn accounts(config: &Config) -> std::io::Result<Arc<dyn auth::local::AccountRepo>> {
+    if let Store::Memory = config.store {
+        return Ok(Arc::new(auth::local::memory::MemoryAccountRepo::new()));
+    }
+
+    match auth::local::postgres::PostgresAccountRepo::new(config.dsn.clone()).await {
+        Ok(accounts) => Ok(Arc::new(accounts)),
+        Err(err) => {
+            eprintln!("Failed to connect to Postgres");
+            Err(std::io::Error::other(format!(
+                "Failed to connect to database {}",
+                err
+            )))
+        }
+    }
+}
+
+fn local_authenticator(
+    config: &Config,
+    accounts: Arc<dyn auth::local::AccountRepo>,
+    repo: Arc<dyn auth::Repo>,
+) -> auth::local::LocalAuthenticator {
+    auth::local::LocalAuthenticator::new(accounts, repo, config.base_url.clone())
+}
+
+async fn invite(config: &Config, login: String) -> std::io::Result<()> {
+    let (repo, _) = session_repo(config).await?;
+
+    let token = local_authenticator(config, accounts(config).await?, repo)
+        .invite(login)
+        .await
+        .map_err(std::io::Error::other)?;
+
+    println!("✅ {}/register?invite={}", config.base_url, token);
 
     Ok(())
 }
 
-async fn admin_service(config: &Config) -> std::io::Result<()> {
-    let admin_repo = match blog::postgres::PostgresRepo::new(config.dsn.clone()).await {
-        Ok(repo) => Arc::new(repo),
+async fn reset_password(config: &Config, login: String) -> std::io::Result<()> {
+    let (repo, _) = session_repo(config).await?;
+
+    let token = local_authenticator(config, accounts(config).await?, repo)
+        .reset_token(login)
+        .await
+        .map_err(std::io::Error::other)?;
+
+    println!("✅ {}/reset-password?token={}", config.base_url, token);
+
+    Ok(())
+}
+
+async fn blog_repo(config: &Config) -> std::io::Result<Arc<dyn blog::Repo>> {
+    if let Store::Memory = config.store {
+        return Ok(Arc::new(blog::memory::MemoryRepo::new()));
+    }
+
+    match blog::postgres::PostgresRepo::new(config.dsn.clone()).await {
+        Ok(repo) => Ok(Arc::new(repo)),
         Err(err) => {
             eprintln!("Failed to connect to Postgres");
-            return Err(std::io::Error::new(
-                std::io::ErrorKind::Other,
-                format!("Failed to connect to database {}", err.to_string()),
-            ));
+            Err(std::io::Error::other(format!(
+                "Failed to connect to database {}",
+                err
+            )))
         }
-    };
+    }
+}
 
-    let admin = Arc::new(blog::DefaultAdmin::new(admin_repo, 10));
+async fn serve_admin(
+    config: &Config,
+    repo: Arc<dyn blog::Repo>,
+    stores: &AuthStores,
+    sessions: Arc<dyn auth::SessionManager>,
+) -> std::io::Result<()> {
+    let admin =
+        Arc::new(blog::DefaultAdmin::new(repo, 10).with_admins(config.admin.admins.clone()));
 
-    let auth_repo = Arc::new(
-        auth::redis::RedisRepo::new(&config.auth.redis, config.auth.ttl)
-            .await
-            .unwrap(),
-    );
-    let sessions = Arc::new(auth::DefaultSessionManager::new(auth_repo.clone()));
+    tokio::spawn(blog::scheduler::run(
+        admin.clone(),
+        Duration::from_secs(config.admin.schedule_interval.get()),
+    ));
 
     println!("🏁 starting admin service on {}", config.admin.listen_addr);
 
     blog::http::server(
         admin,
         sessions,
+        stores.directory.clone(),
         config.auth.cookie.clone(),
         config.admin.listen_addr.clone(),
     )
     .await
-    .unwrap();
+}
 
-    Ok(())
+async fn serve_public(config: &Config, repo: Arc<dyn blog::Repo>) -> std::io::Result<()> {
+    let reader = Arc::new(blog::DefaultReader::new(repo, 10));
+
+    println!(
+        "🏁 starting public service on {}",
+        config.public.listen_addr
+    );
+
+    blog::public::server(reader, config.public.listen_addr.clone()).await
 }
 
 async fn openapi(path: Option<String>) -> std::io::Result<()> {
@@ -161,3 +745,98 @@ async fn openapi(path: Option<String>) -> std::io::Result<()> {
 
     Ok(())
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::blog::{Admin, Reader};
+
+    const MEMORY_CONFIG: &str = r#"
+        base_url = "http://127.0.0.1:8010"
+        store = "memory"
+        loki = ""
+
+        [auth]
+        listen_addr = "127.0.0.1:8080"
+        store = "memory"
+        ttl = 3600
+        gh_client_id = ""
+        gh_client_secret = ""
+        cookie = "sid"
+
+        [auth.local]
+        open_registration = true
+
+        [admin]
+        listen_addr = "127.0.0.1:8011"
+        admins = ["local:john_doe"]
+
+        [public]
+        listen_addr = "127.0.0.1:8012"
+    "#;
+
+    #[tokio::test]
+    async fn test_memory_stores_need_one_process() {
+        let config: Config = toml::from_str(MEMORY_CONFIG).unwrap();
+
+        assert!(check_stores_shared(&config).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_memory_stores_shared() {
+        let config: Config = toml::from_str(MEMORY_CONFIG).unwrap();
+
+        // as built by `all_services`
+        let stores = auth_stores(&config).await.unwrap();
+        let sessions = session_manager(&config, &stores).unwrap();
+        let repo = blog_repo(&config).await.unwrap();
+
+        // registering on the auth service
+        let local = local_authenticator(
+            &config,
+            stores.accounts.clone().unwrap(),
+            stores.repo.clone(),
+        )
+        .with_open_registration(true);
+        let session = local
+            .register(
+                None,
+                Some("john_doe".to_string()),
+                "John Doe".to_string(),
+                "correct horse battery".to_string(),
+                auth::ClientInfo::default(),
+            )
+            .await
+            .unwrap();
+        let session = sessions.issue(session).await.unwrap();
+
+        // writing on the admin service with the session
+        let user = sessions.session(session.token).await.unwrap();
+        let admin =
+            blog::DefaultAdmin::new(repo.clone(), 10).with_admins(config.admin.admins.clone());
+        admin
+            .authorize(user.login.clone(), blog::Action::Publish)
+            .await
+            .unwrap();
+
+        let article = admin
+            .create(
+                "Hello".to_string(),
+                "A first article".to_string(),
+                "Hello world".to_string(),
+                user.login,
+                vec![],
+                None,
+            )
+            .await
+            .unwrap();
+        admin.publish(article.id).await.unwrap();
+
+        // reading on the public service
+        let reader = blog::DefaultReader::new(repo, 10);
+        match reader.get_by_slug(article.slug).await.unwrap() {
+            blog::SlugLookup::Found(found) => assert_eq!(found.id, article.id),
+            blog::SlugLookup::Moved(slug) => panic!("article moved to {}", slug),
+        }
+    }
+}
@@ -1,6 +1,7 @@
 use serde::{Deserialize, Serialize};
 use utoipa::ToSchema;
 
+pub mod csrf;
 pub mod openapi;
 
 #[derive(Serialize, Deserialize, ToSchema)]
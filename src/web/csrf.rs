@@ -0,0 +1,148 @@
+use crate::auth::SessionManager;
+use crate::errors::Error;
+use actix_web::body::{EitherBody, MessageBody};
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::middleware::Next;
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use sha2::{Digest, Sha256};
+use std::sync::Arc;
+
+/// Header carrying the token on state-changing requests.
+pub const HEADER: &str = "X-CSRF-Token";
+
+/// Token of the session `session_token`, as given out by `/api/auth/me` through
+/// `SessionManager::csrf_token`.
+///
+/// It can only be computed from the session, which other sites cannot read.
+pub fn token(session_token: &str) -> String {
+    URL_SAFE_NO_PAD.encode(Sha256::digest(format!("csrf:{}", session_token).as_bytes()))
+}
+
+/// Checks the token of a request, only state-changing requests with a session need one.
+fn verify(
+    method_is_safe: bool,
+    session: Option<&str>,
+    header: Option<&str>,
+    sessions: &dyn SessionManager,
+) -> Result<(), Error> {
+    let session = match session {
+        // without a session, the request is not authenticated by the browser
+        Some(session) if !method_is_safe => session,
+        _ => return Ok(()),
+    };
+
+    let expected = sessions.csrf_token(session)?;
+
+    match header {
+        Some(header) if constant_time_eq(header.as_bytes(), expected.as_bytes()) => Ok(()),
+        Some(_) => Err(Error::PermissionDenied("invalid csrf token".to_string())),
+        None => Err(Error::PermissionDenied("missing csrf token".to_string())),
+    }
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// Middleware rejecting state-changing requests made with the session cookie `cookie_name`
+/// that do not carry the matching token in the `X-CSRF-Token` header.
+pub async fn protect(
+    cookie_name: String,
+    sessions: Arc<dyn SessionManager>,
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<EitherBody<impl MessageBody>>, actix_web::Error> {
+    let session = req.cookie(&cookie_name);
+
+    let result = verify(
+        req.method().is_safe(),
+        session.as_ref().map(|cookie| cookie.value()),
+        req.headers()
+            .get(HEADER)
+            .and_then(|value| value.to_str().ok()),
+        sessions.as_ref(),
+    );
+
+    match result {
+        Ok(_) => Ok(next.call(req).await?.map_into_left_body()),
+        Err(err) => Ok(req
+            .into_response(err.to_http_response())
+            .map_into_right_body()),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::auth::MockSessionManager;
+    use actix_web::middleware::from_fn;
+    use actix_web::{App, HttpResponse, http::StatusCode, test, web};
+
+    fn sessions() -> MockSessionManager {
+        let mut sessions = MockSessionManager::new();
+        sessions
+            .expect_csrf_token()
+            .returning(|session| Ok(token(session)));
+        sessions
+    }
+
+    #[actix_web::test]
+    async fn test_verify() {
+        let sessions = sessions();
+        let session = "session_token";
+        let valid = token(session);
+
+        assert!(verify(true, Some(session), None, &sessions).is_ok());
+        assert!(verify(false, None, None, &sessions).is_ok());
+        assert!(verify(false, Some(session), Some(&valid), &sessions).is_ok());
+        assert_eq!(
+            verify(false, Some(session), None, &sessions)
+                .unwrap_err()
+                .to_string(),
+            "permission denied: missing csrf token"
+        );
+        assert_eq!(
+            verify(false, Some(session), Some(&token("other")), &sessions)
+                .unwrap_err()
+                .to_string(),
+            "permission denied: invalid csrf token"
+        );
+    }
+
+    #[actix_web::test]
+    async fn test_protect() {
+        let sessions: Arc<dyn SessionManager> = Arc::new(sessions());
+        let app = test::init_service(
+            App::new()
+                .wrap(from_fn(move |req, next| {
+                    protect("sid".to_string(), sessions.clone(), req, next)
+                }))
+                .route("/", web::get().to(HttpResponse::Ok))
+                .route("/", web::post().to(HttpResponse::Ok)),
+        )
+        .await;
+
+        let req = test::TestRequest::get()
+            .uri("/")
+            .cookie(actix_web::cookie::Cookie::new("sid", "session_token"))
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+
+        let req = test::TestRequest::post()
+            .uri("/")
+            .cookie(actix_web::cookie::Cookie::new("sid", "session_token"))
+            .to_request();
+        assert_eq!(
+            test::call_service(&app, req).await.status(),
+            StatusCode::FORBIDDEN
+        );
+
+        let req = test::TestRequest::post()
+            .uri("/")
+            .cookie(actix_web::cookie::Cookie::new("sid", "session_token"))
+            .insert_header((HEADER, token("session_token")))
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+    }
+}
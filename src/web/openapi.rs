@@ -5,16 +5,53 @@ use utoipa::OpenApi;
     info(description = "Blog API"),
     paths(
         crate::auth::http::me,
+        crate::auth::http::list_providers,
+        crate::auth::http::local_login,
+        crate::auth::http::local_register,
+        crate::auth::http::local_change_password,
+        crate::auth::http::local_reset_password,
+        crate::auth::http::list_sessions,
+        crate::auth::http::revoke_session,
+        crate::auth::http::revoke_other_sessions,
+        crate::auth::http::list_tokens,
+        crate::auth::http::create_token,
+        crate::auth::http::revoke_token,
         crate::blog::http::create_article,
         crate::blog::http::list_articles,
+        crate::blog::http::search_articles,
         crate::blog::http::get_article,
         crate::blog::http::update_article,
         crate::blog::http::publish_article,
+        crate::blog::http::schedule_article,
+        crate::blog::http::unschedule_article,
         crate::blog::http::move_article_to_trash,
         crate::blog::http::move_article_to_draft,
         crate::blog::http::delete_article,
+        crate::blog::http::preview_article,
+        crate::blog::http::list_tags,
+        crate::blog::http::tag_article,
+        crate::blog::http::untag_article,
+        crate::blog::http::list_categories,
+        crate::blog::http::categorize_article,
+        crate::blog::http::uncategorize_article,
+        crate::blog::http::list_revisions,
+        crate::blog::http::diff_revisions,
+        crate::blog::http::restore_revision,
+        crate::blog::http::my_role,
+        crate::blog::http::list_roles,
+        crate::blog::http::assign_role,
+        crate::blog::http::revoke_role,
+        crate::blog::http::list_users,
+        crate::blog::http::get_user,
+        crate::blog::http::disable_user,
+        crate::blog::http::enable_user,
+        crate::blog::http::list_user_sessions,
+        crate::blog::http::revoke_user_session,
+        crate::blog::http::revoke_user_sessions,
+        crate::blog::public::list_published_articles,
+        crate::blog::public::get_published_article,
+        crate::blog::public::get_published_article_by_slug,
     ),
     components(schemas())
 )]
-
 pub struct Doc;